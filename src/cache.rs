@@ -0,0 +1,376 @@
+use crate::{
+    Cli,
+    config::{PluginName, load_config},
+    fetch::PluginFetcher,
+    service::config_path,
+    source::stored_url,
+    upstream::is_upstream_url,
+};
+use anyhow::{Context, Result};
+use bytesize::ByteSize;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::PathBuf};
+use url::Url;
+
+/// A downloaded plugin module in the cache.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CachedPlugin {
+    pub plugin: PluginName,
+    pub source: Url,
+    pub digest: String,
+    pub pulled_at: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+    // Fingerprint of the signature policy an OCI image was last verified against, absent
+    // when it was pulled without verification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_policy: Option<String>,
+}
+
+/// Downloaded plugin modules under `dirs::cache_dir()/hyper-mcp`.
+///
+/// Each module is stored as `{plugin}-{short digest}.wasm` next to a `.json` file that
+/// records where it came from and when it was last loaded.
+#[derive(Clone, Debug)]
+pub struct PluginCache {
+    dir: PathBuf,
+}
+
+impl PluginCache {
+    pub fn open() -> Result<Self> {
+        let dir = dirs::cache_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine the cache directory"))?
+            .join("hyper-mcp");
+        Self::at(dir)
+    }
+
+    pub fn at(dir: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create cache directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    fn stem(plugin: &PluginName, digest: &str) -> String {
+        let short_digest: String = digest
+            .trim_start_matches("sha256:")
+            .chars()
+            .take(12)
+            .collect();
+        format!("{plugin}-{short_digest}")
+    }
+
+    pub fn module_path(&self, plugin: &PluginName, digest: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.wasm", Self::stem(plugin, digest)))
+    }
+
+    fn metadata_path(&self, plugin: &PluginName, digest: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", Self::stem(plugin, digest)))
+    }
+
+    /// Every cached module that still has its module file.
+    pub async fn entries(&self) -> Result<Vec<CachedPlugin>> {
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(file) = dir.next_entry().await? {
+            let path = file.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let entry = match tokio::fs::read(&path)
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(serde_json::from_slice::<CachedPlugin>(&content)?))
+            {
+                Ok(entry) => entry,
+                Err(e) => {
+                    tracing::warn!("Ignoring unreadable cache entry {}: {e}", path.display());
+                    continue;
+                }
+            };
+            if self.module_path(&entry.plugin, &entry.digest).exists() {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| (&a.plugin, b.pulled_at).cmp(&(&b.plugin, a.pulled_at)));
+        Ok(entries)
+    }
+
+    /// The cached module with this digest, if there is one.
+    pub async fn get(&self, plugin: &PluginName, digest: &str) -> Option<CachedPlugin> {
+        let content = tokio::fs::read(self.metadata_path(plugin, digest))
+            .await
+            .ok()?;
+        let entry: CachedPlugin = serde_json::from_slice(&content).ok()?;
+        self.module_path(plugin, digest).exists().then_some(entry)
+    }
+
+    /// The module most recently downloaded for a plugin from this source.
+    pub async fn latest(&self, plugin: &PluginName, source: &Url) -> Option<CachedPlugin> {
+        self.entries()
+            .await
+            .ok()?
+            .into_iter()
+            .find(|entry| &entry.plugin == plugin && entry.source == stored_url(source))
+    }
+
+    /// Writes a downloaded module to the cache and records where it came from.
+    pub async fn insert(
+        &self,
+        plugin: &PluginName,
+        source: &Url,
+        digest: &str,
+        content: &[u8],
+    ) -> Result<CachedPlugin> {
+        let path = self.module_path(plugin, digest);
+        let partial_path = path.with_extension("wasm.partial");
+        tokio::fs::write(&partial_path, content).await?;
+        tokio::fs::rename(&partial_path, &path).await?;
+        self.record(plugin, source, digest).await
+    }
+
+    /// Records a module that was written to `module_path` directly.
+    pub async fn record(
+        &self,
+        plugin: &PluginName,
+        source: &Url,
+        digest: &str,
+    ) -> Result<CachedPlugin> {
+        let now = Utc::now();
+        let entry = CachedPlugin {
+            plugin: plugin.clone(),
+            source: stored_url(source),
+            digest: digest.to_string(),
+            pulled_at: now,
+            last_used: now,
+            verified_policy: None,
+        };
+        self.write_metadata(&entry).await?;
+        Ok(entry)
+    }
+
+    /// Records the signature policy a cached module was verified against.
+    pub async fn set_verified_policy(
+        &self,
+        mut entry: CachedPlugin,
+        verified_policy: Option<String>,
+    ) -> Result<CachedPlugin> {
+        entry.verified_policy = verified_policy;
+        self.write_metadata(&entry).await?;
+        Ok(entry)
+    }
+
+    /// Reads a cached module and marks it as used.
+    pub async fn load(&self, mut entry: CachedPlugin) -> Result<Vec<u8>> {
+        let content = tokio::fs::read(self.module_path(&entry.plugin, &entry.digest)).await?;
+        entry.last_used = Utc::now();
+        if let Err(e) = self.write_metadata(&entry).await {
+            tracing::warn!(
+                "Failed to update cache entry of plugin {}: {e}",
+                entry.plugin
+            );
+        }
+        Ok(content)
+    }
+
+    pub async fn remove(&self, entry: &CachedPlugin) -> Result<()> {
+        tokio::fs::remove_file(self.module_path(&entry.plugin, &entry.digest)).await?;
+        tokio::fs::remove_file(self.metadata_path(&entry.plugin, &entry.digest)).await?;
+        Ok(())
+    }
+
+    /// Removes files that do not belong to any entry, such as modules cached by older
+    /// versions of hyper-mcp or left behind by an interrupted download.
+    async fn remove_orphans(&self) -> Result<Vec<PathBuf>> {
+        let entries = self.entries().await?;
+        let mut removed = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(file) = dir.next_entry().await? {
+            let path = file.path();
+            let owned = entries.iter().any(|entry| {
+                path == self.module_path(&entry.plugin, &entry.digest)
+                    || path == self.metadata_path(&entry.plugin, &entry.digest)
+            });
+            if !owned && file.file_type().await?.is_file() {
+                tokio::fs::remove_file(&path).await?;
+                removed.push(path);
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn write_metadata(&self, entry: &CachedPlugin) -> Result<()> {
+        let path = self.metadata_path(&entry.plugin, &entry.digest);
+        tokio::fs::write(&path, serde_json::to_vec_pretty(entry)?).await?;
+        Ok(())
+    }
+
+    fn size(&self, entry: &CachedPlugin) -> u64 {
+        std::fs::metadata(self.module_path(&entry.plugin, &entry.digest))
+            .map(|metadata| metadata.len())
+            .unwrap_or_default()
+    }
+}
+
+/// Prints every cached module.
+pub async fn list() -> Result<()> {
+    let cache = PluginCache::open()?;
+    let entries = cache.entries().await?;
+    if entries.is_empty() {
+        println!("The plugin cache at {} is empty", cache.dir.display());
+        return Ok(());
+    }
+    println!(
+        "{:<24} {:<19} {:>10}  {:<20}  SOURCE",
+        "PLUGIN", "DIGEST", "SIZE", "LAST USED"
+    );
+    for entry in &entries {
+        println!(
+            "{:<24} {:<19} {:>10}  {:<20}  {}",
+            entry.plugin.as_str(),
+            entry.digest.chars().take(19).collect::<String>(),
+            ByteSize::b(cache.size(entry)).to_string(),
+            entry.last_used.format("%Y-%m-%d %H:%M:%S"),
+            entry.source
+        );
+    }
+    Ok(())
+}
+
+/// Removes cached modules that the config no longer uses, all of them with `all`, and
+/// modules not used for `older_than_days` days.
+///
+/// A module is in use while it is the latest one downloaded from the source its plugin
+/// is configured with.
+pub async fn prune(cli: &Cli, older_than_days: Option<u64>, all: bool) -> Result<()> {
+    let cache = PluginCache::open()?;
+    let config = match all {
+        true => None,
+        false => Some(load_config(&config_path(cli), cli.strict_env).await?),
+    };
+    let cutoff = older_than_days.map(|days| Utc::now() - Duration::days(days as i64));
+
+    let mut in_use = HashSet::new();
+    let mut removed = 0;
+    for entry in cache.entries().await? {
+        // Entries are sorted newest first, so the first match is the latest download
+        let configured = config.as_ref().is_some_and(|config| {
+            config
+                .plugins
+                .get(&entry.plugin)
+                .is_some_and(|plugin_cfg| stored_url(&plugin_cfg.url) == entry.source)
+        });
+        let latest = configured && !in_use.contains(&entry.plugin);
+        let stale = cutoff.is_some_and(|cutoff| entry.last_used < cutoff);
+        if latest && !stale {
+            in_use.insert(entry.plugin.clone());
+            continue;
+        }
+        cache.remove(&entry).await?;
+        println!(
+            "Removed {} {} ({})",
+            entry.plugin, entry.digest, entry.source
+        );
+        removed += 1;
+    }
+    for path in cache.remove_orphans().await? {
+        println!("Removed {}", path.display());
+        removed += 1;
+    }
+    println!("Removed {removed} cached files");
+    Ok(())
+}
+
+/// Downloads every plugin in the config into the cache, so the server can later start
+/// with `--offline`.
+pub async fn prefetch(cli: &Cli) -> Result<()> {
+    let config = load_config(&config_path(cli), cli.strict_env).await?;
+    let fetcher = PluginFetcher::prefetching();
+    for (plugin_name, plugin_cfg) in &config.plugins {
+        if is_upstream_url(&plugin_cfg.url) || plugin_cfg.url.scheme() == "file" {
+            continue;
+        }
+        let fetched = fetcher
+            .fetch(cli, &config, plugin_name, plugin_cfg)
+            .await
+            .with_context(|| format!("Failed to fetch plugin {plugin_name}"))?;
+        println!(
+            "Cached {plugin_name} {} ({})",
+            fetched.digest,
+            stored_url(&plugin_cfg.url)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_plugin_cache_insert_and_latest() {
+        let dir = tempdir().unwrap();
+        let cache = PluginCache::at(dir.path().to_path_buf()).unwrap();
+        let plugin = PluginName::try_from("time").unwrap();
+        let source = Url::parse("https://example.com/time.wasm").unwrap();
+        assert!(cache.latest(&plugin, &source).await.is_none());
+
+        cache
+            .insert(&plugin, &source, "sha256:aaaaaaaaaaaaaaaa", b"old")
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let newest = cache
+            .insert(&plugin, &source, "sha256:bbbbbbbbbbbbbbbb", b"new")
+            .await
+            .unwrap();
+
+        let latest = cache.latest(&plugin, &source).await.unwrap();
+        assert_eq!(latest, newest);
+        assert_eq!(cache.load(latest).await.unwrap(), b"new");
+        assert_eq!(cache.entries().await.unwrap().len(), 2);
+
+        let other = Url::parse("https://example.com/other.wasm").unwrap();
+        assert!(cache.latest(&plugin, &other).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_plugin_cache_leaves_out_sas_token() {
+        let dir = tempdir().unwrap();
+        let cache = PluginCache::at(dir.path().to_path_buf()).unwrap();
+        let plugin = PluginName::try_from("time").unwrap();
+        let source = Url::parse("az://team/plugins/time.wasm?sv=2024&sig=abc").unwrap();
+        let entry = cache
+            .insert(&plugin, &source, "sha256:aaaaaaaaaaaaaaaa", b"wasm")
+            .await
+            .unwrap();
+        assert_eq!(entry.source.as_str(), "az://team/plugins/time.wasm");
+        let metadata = std::fs::read_to_string(dir.path().join("time-aaaaaaaaaaaa.json")).unwrap();
+        assert!(!metadata.contains("sig=abc"));
+
+        let rotated = Url::parse("az://team/plugins/time.wasm?sv=2024&sig=def").unwrap();
+        assert_eq!(cache.latest(&plugin, &rotated).await, Some(entry));
+    }
+
+    #[tokio::test]
+    async fn test_plugin_cache_remove_orphans() {
+        let dir = tempdir().unwrap();
+        let cache = PluginCache::at(dir.path().to_path_buf()).unwrap();
+        let plugin = PluginName::try_from("time").unwrap();
+        let source = Url::parse("https://example.com/time.wasm").unwrap();
+        let entry = cache
+            .insert(&plugin, &source, "sha256:aaaaaaaaaaaaaaaa", b"wasm")
+            .await
+            .unwrap();
+        std::fs::write(dir.path().join("time-0123456789ab.wasm"), b"legacy").unwrap();
+
+        let removed = cache.remove_orphans().await.unwrap();
+        assert_eq!(removed, vec![dir.path().join("time-0123456789ab.wasm")]);
+        assert_eq!(cache.entries().await.unwrap(), vec![entry.clone()]);
+
+        cache.remove(&entry).await.unwrap();
+        assert!(cache.entries().await.unwrap().is_empty());
+    }
+}
@@ -1,11 +1,25 @@
+use crate::{
+    interpolate::interpolate_config,
+    secret::{Secret, SecretSource},
+};
 use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
 use regex::{Regex, RegexSet};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::TryFrom, fmt, path::Path, str::FromStr};
+use serde_with::{DurationSecondsWithFrac, serde_as};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 use url::Url;
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct PluginName(String);
 
 #[derive(Clone, Debug)]
@@ -85,56 +99,275 @@ impl fmt::Display for PluginName {
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum AuthConfig {
-    Basic { username: String, password: String },
-    Token { token: String },
+    Basic {
+        username: String,
+        password: String,
+    },
+    Token {
+        token: String,
+    },
+    // Registry refresh token, exchanged for an access token before pulling
+    #[serde(rename = "identity_token")]
+    IdentityToken {
+        identity_token: String,
+    },
+    // Arbitrary header, such as `X-API-Key` or `PRIVATE-TOKEN`
+    Header {
+        name: String,
+        value: String,
+    },
+    // OAuth2 client credentials, exchanged for an access token that is cached until
+    // it expires
+    #[serde(rename = "oauth2")]
+    OAuth2 {
+        token_url: Url,
+        client_id: String,
+        client_secret: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        scopes: Vec<String>,
+    },
+    // PEM files of a client certificate and its private key, presented over mutual TLS
+    Mtls {
+        cert: PathBuf,
+        key: PathBuf,
+    },
+    // Credentials read from a secret source each time they are needed
+    #[serde(untagged)]
+    Secret(SecretSource),
+}
+
+impl AuthConfig {
+    /// Returns the credentials, reading them from their secret source if they are kept
+    /// in one.
+    ///
+    /// A secret holds either an auth config as JSON, the JSON a docker credential
+    /// helper prints, or a bare bearer token.
+    pub async fn resolve(&self) -> Result<Cow<'_, AuthConfig>> {
+        let AuthConfig::Secret(source) = self else {
+            return Ok(Cow::Borrowed(self));
+        };
+        let secret = source.read().await?;
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct DockerCredential {
+            username: String,
+            secret: String,
+        }
+
+        if let Ok(auth) = serde_json::from_str::<InternalAuthConfig>(&secret) {
+            return match AuthConfig::from(auth) {
+                AuthConfig::Secret(_) => Err(anyhow::anyhow!(
+                    "A secret source must hold credentials, not another secret source"
+                )),
+                auth => Ok(Cow::Owned(auth)),
+            };
+        }
+        let auth = match serde_json::from_str::<DockerCredential>(&secret) {
+            // Docker credential helpers return identity tokens with this user name
+            Ok(credential) if credential.username == "<token>" => AuthConfig::IdentityToken {
+                identity_token: credential.secret,
+            },
+            Ok(credential) => AuthConfig::Basic {
+                username: credential.username,
+                password: credential.secret,
+            },
+            Err(_) => AuthConfig::Token { token: secret },
+        };
+        Ok(Cow::Owned(auth))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 enum InternalAuthConfig {
-    Basic { username: String, password: String },
-    Keyring { service: String, user: String },
-    Token { token: String },
+    Basic {
+        username: String,
+        password: String,
+    },
+    #[serde(rename = "identity_token")]
+    IdentityToken {
+        identity_token: String,
+    },
+    Keyring {
+        service: String,
+        user: String,
+    },
+    Token {
+        token: String,
+    },
+    Header {
+        name: String,
+        value: String,
+    },
+    #[serde(rename = "oauth2")]
+    OAuth2 {
+        token_url: Url,
+        client_id: String,
+        client_secret: String,
+        #[serde(default)]
+        scopes: Vec<String>,
+    },
+    Mtls {
+        cert: PathBuf,
+        key: PathBuf,
+    },
+    File {
+        path: PathBuf,
+    },
+    Env {
+        name: String,
+    },
+    Exec {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
 }
 
-impl<'de> Deserialize<'de> for AuthConfig {
-    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let internal = InternalAuthConfig::deserialize(deserializer)?;
+impl From<InternalAuthConfig> for AuthConfig {
+    fn from(internal: InternalAuthConfig) -> Self {
         match internal {
             InternalAuthConfig::Basic { username, password } => {
-                Ok(AuthConfig::Basic { username, password })
+                AuthConfig::Basic { username, password }
+            }
+            InternalAuthConfig::Token { token } => AuthConfig::Token { token },
+            InternalAuthConfig::IdentityToken { identity_token } => {
+                AuthConfig::IdentityToken { identity_token }
             }
-            InternalAuthConfig::Token { token } => Ok(AuthConfig::Token { token }),
+            InternalAuthConfig::Header { name, value } => AuthConfig::Header { name, value },
+            InternalAuthConfig::OAuth2 {
+                token_url,
+                client_id,
+                client_secret,
+                scopes,
+            } => AuthConfig::OAuth2 {
+                token_url,
+                client_id,
+                client_secret,
+                scopes,
+            },
+            InternalAuthConfig::Mtls { cert, key } => AuthConfig::Mtls { cert, key },
             InternalAuthConfig::Keyring { service, user } => {
-                use keyring::Entry;
-                use serde::de;
-
-                let entry =
-                    Entry::new(service.as_str(), user.as_str()).map_err(de::Error::custom)?;
-                let secret = entry.get_secret().map_err(de::Error::custom)?;
-                Ok(serde_json::from_slice::<AuthConfig>(secret.as_slice())
-                    .map_err(de::Error::custom)?)
+                AuthConfig::Secret(SecretSource::Keyring { service, user })
+            }
+            InternalAuthConfig::File { path } => AuthConfig::Secret(SecretSource::File { path }),
+            InternalAuthConfig::Env { name } => AuthConfig::Secret(SecretSource::Env { name }),
+            InternalAuthConfig::Exec { command, args } => {
+                AuthConfig::Secret(SecretSource::Exec { command, args })
             }
         }
     }
 }
 
+impl<'de> Deserialize<'de> for AuthConfig {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        InternalAuthConfig::deserialize(deserializer).map(AuthConfig::from)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     pub auths: Option<HashMap<Url, AuthConfig>>,
+    // Certificates, proxies and insecure registries used to fetch plugins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkConfig>,
+    // S3 settings by `s3://` URL prefix, such as a bucket, the longest prefix wins
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3: Option<HashMap<Url, S3Config>>,
     pub plugins: HashMap<PluginName, PluginConfig>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3Config {
+    // Endpoint of an S3 compatible store, such as MinIO or Ceph
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_url: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    // Profile of the AWS config and credentials files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    // Address buckets as `endpoint/bucket` instead of `bucket.endpoint`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_path_style: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    // PEM files with root certificates to trust besides the system ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ca_certs: Vec<PathBuf>,
+    // Proxy for https requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https_proxy: Option<String>,
+    // Proxy for plain http requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_proxy: Option<String>,
+    // Comma separated hosts and domains reached without the proxies, as in NO_PROXY
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    // Hosts, with an optional port, whose certificates are not verified and whose
+    // registries are reached over plain http
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub insecure_registries: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct PluginConfig {
     #[serde(rename = "url", alias = "path")]
     pub url: Url,
+    // Expected sha256 digest of the wasm module, checked before it is loaded.
+    #[serde(default, alias = "digest", skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    // Arguments for the command of a `stdio:` upstream MCP server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    // When to pull an `oci://` plugin instead of using the cached module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_policy: Option<PullPolicy>,
+    // Who an `oci://` plugin must be signed by, replacing the global signature flags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SignatureConfig>,
+    // S3 settings of an `s3://` plugin, replacing the config's `s3` entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3: Option<S3Config>,
     pub runtime_config: Option<RuntimeConfig>,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignatureConfig {
+    // PEM encoded cosign public key the image must be signed with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    // OIDC issuer of the keyless signing certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    // Email identity of the keyless signing certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    // URL identity of the keyless signing certificate, requires `issuer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullPolicy {
+    // Check the registry every time and pull when the manifest digest changed
+    Always,
+    // Only contact the registry when nothing is cached yet
+    IfNotPresent,
+    // Never contact the registry, fail when nothing is cached
+    Never,
+}
+
 mod skip_serde {
     use super::*;
     use serde::{Deserializer, Serializer};
@@ -182,6 +415,78 @@ mod skip_serde {
     }
 }
 
+/// A host directory the plugin can access, either a plain path mounted read-write at
+/// the same path, or an object with `host`, `guest` and `mode`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct AllowedPath {
+    pub host: String,
+    // Path the plugin sees, defaults to the host path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest: Option<String>,
+    pub mode: PathMode,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathMode {
+    Ro,
+    #[default]
+    Rw,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InternalAllowedPath {
+    Path(String),
+    Mount(InternalMount),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InternalMount {
+    host: String,
+    #[serde(default)]
+    guest: Option<String>,
+    #[serde(default)]
+    mode: PathMode,
+}
+
+impl<'de> Deserialize<'de> for AllowedPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match InternalAllowedPath::deserialize(deserializer).map_err(|_| {
+            serde::de::Error::custom(
+                "expected a path or an object with host, and optionally guest and mode (ro or rw)",
+            )
+        })? {
+            InternalAllowedPath::Path(host) => Ok(AllowedPath::from(host.as_str())),
+            InternalAllowedPath::Mount(InternalMount { host, guest, mode }) => {
+                Ok(AllowedPath { host, guest, mode })
+            }
+        }
+    }
+}
+
+impl From<&str> for AllowedPath {
+    fn from(host: &str) -> Self {
+        AllowedPath {
+            host: host.to_string(),
+            guest: None,
+            mode: PathMode::Rw,
+        }
+    }
+}
+
+impl AllowedPath {
+    /// The path the plugin sees the directory at.
+    pub fn guest(&self) -> &str {
+        self.guest.as_deref().unwrap_or(&self.host)
+    }
+}
+
+#[serde_as]
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct RuntimeConfig {
     // List of prompts to skip loading at runtime.
@@ -197,12 +502,46 @@ pub struct RuntimeConfig {
     #[serde(with = "skip_serde", default)]
     pub skip_tools: Option<RegexSet>,
     pub allowed_hosts: Option<Vec<String>>,
-    pub allowed_paths: Option<Vec<String>>,
-    pub env_vars: Option<HashMap<String, String>>,
+    pub allowed_paths: Option<Vec<AllowedPath>>,
+    // Values can be read from a secret source when the plugin loads
+    pub env_vars: Option<HashMap<String, Secret>>,
     pub memory_limit: Option<String>,
+    // Maximum number of calls running at once, across sessions, defaults to 1.
+    pub max_instances: Option<NonZeroUsize>,
+    // Wall-clock limit for a single plugin call, in seconds.
+    #[serde_as(as = "Option<DurationSecondsWithFrac<f64>>")]
+    pub call_timeout: Option<Duration>,
+    // Amount of fuel (roughly, wasm instructions) a single plugin call may consume.
+    pub fuel_limit: Option<u64>,
+    // Whether `auths` are added to the plugin's own HTTP requests, defaults to false.
+    pub inject_auths: Option<bool>,
 }
 
-pub async fn load_config(path: &Path) -> Result<Config> {
+// RegexSet has no PartialEq, so skip lists are compared by their patterns.
+impl PartialEq for RuntimeConfig {
+    fn eq(&self, other: &Self) -> bool {
+        fn patterns(set: &Option<RegexSet>) -> Option<&[String]> {
+            set.as_ref().map(|set| set.patterns())
+        }
+
+        patterns(&self.skip_prompts) == patterns(&other.skip_prompts)
+            && patterns(&self.skip_resource_templates) == patterns(&other.skip_resource_templates)
+            && patterns(&self.skip_resources) == patterns(&other.skip_resources)
+            && patterns(&self.skip_tools) == patterns(&other.skip_tools)
+            && self.allowed_hosts == other.allowed_hosts
+            && self.allowed_paths == other.allowed_paths
+            && self.env_vars == other.env_vars
+            && self.memory_limit == other.memory_limit
+            && self.max_instances == other.max_instances
+            && self.call_timeout == other.call_timeout
+            && self.fuel_limit == other.fuel_limit
+            && self.inject_auths == other.inject_auths
+    }
+}
+
+/// Loads the config file, expanding its `${...}` references. With `strict_env`, a
+/// reference that cannot be resolved fails the load.
+pub async fn load_config(path: &Path, strict_env: bool) -> Result<Config> {
     if !path.exists() {
         return Err(anyhow::anyhow!(
             "Config file not found at: {}. Please create a config file first.",
@@ -215,14 +554,15 @@ pub async fn load_config(path: &Path) -> Result<Config> {
         .await
         .with_context(|| format!("Failed to read config file at {}", path.display()))?;
 
-    let config = match ext {
+    let mut value: serde_json::Value = match ext {
         "json" => serde_json::from_str(&content)?,
         "yaml" | "yml" => serde_yaml::from_str(&content)?,
         "toml" => toml::from_str(&content)?,
         _ => return Err(anyhow::anyhow!("Unsupported config format: {ext}")),
     };
+    interpolate_config(&mut value, strict_env)?;
 
-    Ok(config)
+    Ok(serde_json::from_value(value)?)
 }
 
 #[cfg(test)]
@@ -499,7 +839,7 @@ mod tests {
         let path = Path::new("tests/fixtures/valid_config.yaml");
 
         // Load the config
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(config_result.is_ok(), "Failed to load valid YAML config");
 
         let config = config_result.unwrap();
@@ -546,7 +886,7 @@ mod tests {
         let path = Path::new("tests/fixtures/valid_config.json");
 
         // Load the config
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
 
         assert!(config_result.is_ok(), "Failed to load valid JSON config");
 
@@ -573,10 +913,13 @@ mod tests {
         // Verify env vars
         let test_plugin = &config.plugins[&PluginName("test_plugin".to_string())];
         let runtime_config = test_plugin.runtime_config.as_ref().unwrap();
-        assert_eq!(runtime_config.env_vars.as_ref().unwrap()["DEBUG"], "true");
+        assert_eq!(
+            runtime_config.env_vars.as_ref().unwrap()["DEBUG"],
+            "true".into()
+        );
         assert_eq!(
             runtime_config.env_vars.as_ref().unwrap()["LOG_LEVEL"],
-            "info"
+            "info".into()
         );
     }
 
@@ -588,7 +931,7 @@ mod tests {
         let path = Path::new("tests/fixtures/invalid_plugin_name.yaml");
 
         // Load the config
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_err(),
             "Expected error for invalid plugin name"
@@ -603,7 +946,7 @@ mod tests {
         let path = Path::new("tests/fixtures/invalid_url.yaml");
 
         // Load the config
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(config_result.is_err(), "Expected error for invalid URL");
 
         let error = config_result.unwrap_err();
@@ -622,7 +965,7 @@ mod tests {
         let path = Path::new("tests/fixtures/invalid_structure.yaml");
 
         // Load the config
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_err(),
             "Expected error for invalid structure"
@@ -637,7 +980,7 @@ mod tests {
         let nonexistent_path = Path::new("/tmp/definitely_not_a_real_config_file_12345.yaml");
 
         // Load the config
-        let config_result = rt.block_on(load_config(nonexistent_path));
+        let config_result = rt.block_on(load_config(nonexistent_path, false));
         assert!(
             config_result.is_err(),
             "Expected error for nonexistent file"
@@ -657,7 +1000,7 @@ mod tests {
         let path = Path::new("tests/fixtures/unsupported_config.txt");
 
         // Load the config
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_err(),
             "Expected error for unsupported extension"
@@ -670,6 +1013,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_load_config_interpolation() {
+        let rt = Runtime::new().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let token_path = temp_dir.path().join("token");
+        std::fs::write(&token_path, "s3cret\n").unwrap();
+        let path = temp_dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!(
+                r#"
+[auths."https://${{HYPER_MCP_TEST_CONFIG_REGISTRY:-registry.example.com}}"]
+type = "token"
+token = "${{file:{}}}"
+
+[plugins.time]
+url = "oci://${{HYPER_MCP_TEST_CONFIG_REGISTRY:-registry.example.com}}/time:v1"
+
+[plugins.time.runtime_config]
+allowed_hosts = ["${{HYPER_MCP_TEST_CONFIG_UNSET}}"]
+"#,
+                token_path.display()
+            ),
+        )
+        .unwrap();
+
+        let config = rt.block_on(load_config(&path, false)).unwrap();
+        let auths = config.auths.unwrap();
+        let auth = &auths[&Url::parse("https://registry.example.com").unwrap()];
+        assert!(matches!(auth, AuthConfig::Token { token } if token == "s3cret"));
+        let plugin = &config.plugins[&PluginName("time".to_string())];
+        assert_eq!(plugin.url.as_str(), "oci://registry.example.com/time:v1");
+        let runtime_config = plugin.runtime_config.as_ref().unwrap();
+        assert_eq!(
+            runtime_config.allowed_hosts.as_deref(),
+            Some(&["${HYPER_MCP_TEST_CONFIG_UNSET}".to_string()][..])
+        );
+
+        let error = rt
+            .block_on(load_config(&path, true))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("${HYPER_MCP_TEST_CONFIG_UNSET} is not set"),
+            "{error}"
+        );
+    }
+
     #[test]
     fn test_auth_config_basic_serialization() {
         let auth_config = AuthConfig::Basic {
@@ -720,6 +1111,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_auth_config_identity_token_deserialization() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+auths:
+  "oci://registry.example.com/team/":
+    type: identity_token
+    identity_token: "refresh-token-123"
+plugins: {}
+"#,
+        )
+        .unwrap();
+
+        let auths = config.auths.unwrap();
+        let url = Url::parse("oci://registry.example.com/team/").unwrap();
+        match &auths[&url] {
+            AuthConfig::IdentityToken { identity_token } => {
+                assert_eq!(identity_token, "refresh-token-123");
+            }
+            _ => panic!("Expected IdentityToken auth config"),
+        }
+    }
+
     #[test]
     fn test_auth_config_yaml_basic_deserialization() {
         let yaml = r#"
@@ -761,6 +1175,121 @@ token: test-token-123
         assert!(result.is_err(), "Expected error for invalid auth type");
     }
 
+    #[test]
+    fn test_auth_config_header_oauth2_mtls_deserialization() {
+        let auths: HashMap<Url, AuthConfig> = serde_yaml::from_str(
+            r#"
+"https://gitlab.example.com":
+  type: header
+  name: PRIVATE-TOKEN
+  value: glpat-123
+"https://artifacts.example.com":
+  type: oauth2
+  token_url: https://auth.example.com/oauth/token
+  client_id: hyper-mcp
+  client_secret: s3cret
+  scopes: [read]
+"https://internal.example.com":
+  type: mtls
+  cert: /etc/hyper-mcp/client.crt
+  key: /etc/hyper-mcp/client.key
+"#,
+        )
+        .unwrap();
+        let auth = |url: &str| &auths[&Url::parse(url).unwrap()];
+
+        assert!(matches!(
+            auth("https://gitlab.example.com"),
+            AuthConfig::Header { name, value } if name == "PRIVATE-TOKEN" && value == "glpat-123"
+        ));
+        assert!(matches!(
+            auth("https://artifacts.example.com"),
+            AuthConfig::OAuth2 { token_url, client_id, scopes, .. }
+                if token_url.as_str() == "https://auth.example.com/oauth/token"
+                    && client_id == "hyper-mcp"
+                    && scopes == &["read"]
+        ));
+        assert!(matches!(
+            auth("https://internal.example.com"),
+            AuthConfig::Mtls { cert, .. } if cert == Path::new("/etc/hyper-mcp/client.crt")
+        ));
+        assert_eq!(
+            serde_json::to_value(auth("https://internal.example.com")).unwrap(),
+            serde_json::json!({
+                "type": "mtls",
+                "cert": "/etc/hyper-mcp/client.crt",
+                "key": "/etc/hyper-mcp/client.key"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auth_config_secret_sources() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("auth.json");
+        std::fs::write(
+            &path,
+            r#"{"type":"basic","username":"team","password":"pass"}"#,
+        )
+        .unwrap();
+        let auth: AuthConfig =
+            serde_yaml::from_str(&format!("type: file\npath: {}", path.display())).unwrap();
+        assert!(matches!(
+            &auth,
+            AuthConfig::Secret(SecretSource::File { .. })
+        ));
+        assert!(matches!(
+            auth.resolve().await.as_deref(),
+            Ok(AuthConfig::Basic { username, password }) if username == "team" && password == "pass"
+        ));
+
+        // SAFETY: no other test reads this variable
+        unsafe { std::env::set_var("HYPER_MCP_TEST_AUTH_TOKEN", "bare-token") };
+        let auth: AuthConfig =
+            serde_json::from_str(r#"{"type":"env","name":"HYPER_MCP_TEST_AUTH_TOKEN"}"#).unwrap();
+        assert!(matches!(
+            auth.resolve().await.as_deref(),
+            Ok(AuthConfig::Token { token }) if token == "bare-token"
+        ));
+
+        // Docker credential helpers print their own format
+        let auth: AuthConfig = serde_json::from_str(
+            r#"{"type":"exec","command":"sh","args":["-c","echo '{\"ServerURL\":\"ghcr.io\",\"Username\":\"<token>\",\"Secret\":\"refresh\"}'"]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            auth.resolve().await.as_deref(),
+            Ok(AuthConfig::IdentityToken { identity_token }) if identity_token == "refresh"
+        ));
+
+        std::fs::write(&path, r#"{"type":"env","name":"OTHER"}"#).unwrap();
+        let auth = AuthConfig::Secret(SecretSource::File { path });
+        assert!(auth.resolve().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_env_vars_secret_sources_are_not_logged() {
+        let runtime_config: RuntimeConfig = serde_yaml::from_str(
+            r#"
+env_vars:
+  PLAIN: "plain-value"
+  API_KEY:
+    type: exec
+    command: printf
+    args: ["%s%s", "secret-", "value"]
+"#,
+        )
+        .unwrap();
+        let env_vars = runtime_config.env_vars.as_ref().unwrap();
+        assert_eq!(env_vars["PLAIN"].resolve().await.unwrap(), "plain-value");
+        assert_eq!(env_vars["API_KEY"].resolve().await.unwrap(), "secret-value");
+
+        let debug = format!("{runtime_config:?}");
+        assert!(!debug.contains("plain-value"), "{debug}");
+        assert!(!debug.contains("secret-value"), "{debug}");
+        assert!(debug.contains("API_KEY"), "{debug}");
+    }
+
     #[test]
     fn test_auth_config_missing_fields() {
         // Missing username for basic auth
@@ -950,7 +1479,7 @@ plugins:
         let rt = Runtime::new().unwrap();
         let path = Path::new("tests/fixtures/config_with_auths.yaml");
 
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_ok(),
             "Failed to load config with auths from YAML"
@@ -989,7 +1518,7 @@ plugins:
         let rt = Runtime::new().unwrap();
         let path = Path::new("tests/fixtures/config_with_auths.json");
 
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_ok(),
             "Failed to load config with auths from JSON"
@@ -1020,7 +1549,7 @@ plugins:
         let rt = Runtime::new().unwrap();
         let path = Path::new("tests/fixtures/invalid_auth_config.yaml");
 
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_err(),
             "Expected error for invalid auth config"
@@ -1062,6 +1591,8 @@ plugins:
 
         let config = Config {
             auths: Some(auths),
+            network: None,
+            s3: None,
             plugins: HashMap::new(),
         };
 
@@ -1162,7 +1693,7 @@ plugins:
         let rt = Runtime::new().unwrap();
         let path = Path::new("tests/fixtures/documentation_example.yaml");
 
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_ok(),
             "Documentation YAML example should be valid"
@@ -1226,7 +1757,7 @@ plugins:
         let rt = Runtime::new().unwrap();
         let path = Path::new("tests/fixtures/documentation_example.json");
 
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_ok(),
             "Documentation JSON example should be valid"
@@ -1260,7 +1791,10 @@ plugins:
 
         let myip_plugin = &config.plugins[&PluginName("myip".to_string())];
         let runtime_config = myip_plugin.runtime_config.as_ref().unwrap();
-        assert_eq!(runtime_config.env_vars.as_ref().unwrap()["FOO"], "bar");
+        assert_eq!(
+            runtime_config.env_vars.as_ref().unwrap()["FOO"],
+            "bar".into()
+        );
         assert_eq!(runtime_config.memory_limit.as_ref().unwrap(), "512Mi");
     }
 
@@ -1376,6 +1910,7 @@ plugins:
 
     #[test]
     #[ignore] // Requires system keyring access - run with `cargo test -- --ignored`
+    #[allow(clippy::collapsible_if)]
     fn test_keyring_auth_integration() {
         use std::process::Command;
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -1491,16 +2026,21 @@ plugins:
                     assert_eq!(user, user_name);
 
                     // Test the actual keyring deserialization through AuthConfig
-                    let auth_config: Result<AuthConfig, _> =
-                        serde_json::from_str(&keyring_config_json);
+                    // Keyring entries are only read when the credentials are needed
+                    let auth_config: Result<AuthConfig, anyhow::Error> =
+                        serde_json::from_str::<AuthConfig>(&keyring_config_json)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|auth| {
+                                Ok(Runtime::new()?.block_on(auth.resolve())?.into_owned())
+                            });
 
                     match auth_config {
                         Ok(AuthConfig::Basic { username, password }) => {
                             assert_eq!(username, "keyring-test-user");
                             assert_eq!(password, "keyring-test-pass");
                         }
-                        Ok(AuthConfig::Token { .. }) => {
-                            panic!("Expected Basic auth from keyring, got Token");
+                        Ok(other) => {
+                            panic!("Expected Basic auth from keyring, got {other:?}");
                         }
                         Err(e) => {
                             println!(
@@ -1533,6 +2073,7 @@ plugins:
 
     #[test]
     #[ignore] // Requires system keyring access and file creation - run with `cargo test -- --ignored`
+    #[allow(clippy::collapsible_if)]
     fn test_keyring_auth_complete_config_integration() {
         use std::process::Command;
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -1668,7 +2209,7 @@ plugins:
         }
 
         // Test loading the config file (this should trigger keyring lookup)
-        let load_result = rt.block_on(load_config(config_path));
+        let load_result = rt.block_on(load_config(config_path, false));
 
         // Cleanup keyring entry before checking results
         if let Ok(output) = remove_result {
@@ -1703,14 +2244,17 @@ plugins:
                     "Expected keyring auth URL to be present"
                 );
 
-                match &auths[&keyring_url] {
-                    AuthConfig::Token { token } => {
+                match rt.block_on(auths[&keyring_url].resolve()).as_deref() {
+                    Ok(AuthConfig::Token { token }) => {
                         assert_eq!(
                             token, "test-keyring-token-from-complete-config",
                             "Token from keyring should match stored value"
                         );
                     }
-                    _ => panic!("Expected Token auth from keyring resolution"),
+                    Ok(_) => panic!("Expected Token auth from keyring resolution"),
+                    Err(e) => println!(
+                        "Keyring lookup failed (keyring service may not be available): {e}. This is acceptable for CI environments."
+                    ),
                 }
 
                 // Verify basic auth still works alongside keyring auth
@@ -1795,8 +2339,13 @@ plugins:
                         r#"{{"type":"keyring","service":"{service_name}","user":"{user_name}"}}"#
                     );
 
-                    let auth_result: Result<AuthConfig, _> =
-                        serde_json::from_str(&keyring_config_json);
+                    // Keyring entries are only read when the credentials are needed
+                    let auth_result: Result<AuthConfig, anyhow::Error> =
+                        serde_json::from_str::<AuthConfig>(&keyring_config_json)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|auth| {
+                                Ok(Runtime::new()?.block_on(auth.resolve())?.into_owned())
+                            });
 
                     // Cleanup first
                     let _ = Command::new("security")
@@ -1845,8 +2394,13 @@ plugins:
                         r#"{{"type":"keyring","service":"{service_name}","user":"{user_name}"}}"#
                     );
 
-                    let auth_result: Result<AuthConfig, _> =
-                        serde_json::from_str(&keyring_config_json);
+                    // Keyring entries are only read when the credentials are needed
+                    let auth_result: Result<AuthConfig, anyhow::Error> =
+                        serde_json::from_str::<AuthConfig>(&keyring_config_json)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|auth| {
+                                Ok(Runtime::new()?.block_on(auth.resolve())?.into_owned())
+                            });
 
                     // Cleanup first
                     let _ = Command::new("secret-tool")
@@ -1889,8 +2443,13 @@ plugins:
                         r#"{{"type":"keyring","service":"{service_name}","user":"{user_name}"}}"#
                     );
 
-                    let auth_result: Result<AuthConfig, _> =
-                        serde_json::from_str(&keyring_config_json);
+                    // Keyring entries are only read when the credentials are needed
+                    let auth_result: Result<AuthConfig, anyhow::Error> =
+                        serde_json::from_str::<AuthConfig>(&keyring_config_json)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|auth| {
+                                Ok(Runtime::new()?.block_on(auth.resolve())?.into_owned())
+                            });
 
                     // Cleanup first
                     let _ = Command::new("cmdkey")
@@ -2167,6 +2726,8 @@ plugins:
 
         let config = Config {
             auths: Some(auths),
+            network: None,
+            s3: None,
             plugins: HashMap::new(),
         };
 
@@ -2236,6 +2797,10 @@ plugins:
             allowed_paths: None,
             env_vars: None,
             memory_limit: None,
+            max_instances: None,
+            call_timeout: None,
+            fuel_limit: None,
+            inject_auths: None,
         };
 
         // Test serialization
@@ -2343,6 +2908,10 @@ plugins:
             allowed_paths: None,
             env_vars: None,
             memory_limit: None,
+            max_instances: None,
+            call_timeout: None,
+            fuel_limit: None,
+            inject_auths: None,
         };
 
         // Serialize
@@ -2454,6 +3023,191 @@ allowed_hosts:
     }
 
     #[test]
+    fn test_max_instances_deserialization() {
+        let runtime_config: RuntimeConfig =
+            serde_json::from_str(r#"{"max_instances": 4}"#).unwrap();
+        assert_eq!(runtime_config.max_instances.map(|n| n.get()), Some(4));
+
+        let runtime_config: RuntimeConfig = serde_json::from_str("{}").unwrap();
+        assert!(runtime_config.max_instances.is_none());
+
+        let result: Result<RuntimeConfig, _> = serde_json::from_str(r#"{"max_instances": 0}"#);
+        assert!(result.is_err(), "max_instances must be at least 1");
+    }
+
+    #[test]
+    fn test_upstream_plugin_config_deserialization() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+plugins:
+  git:
+    url: "stdio:uvx"
+    args: ["mcp-server-git", "--repository", "."]
+  remote:
+    url: "mcp+https://mcp.example.com/mcp"
+"#,
+        )
+        .unwrap();
+
+        let git = &config.plugins[&PluginName("git".to_string())];
+        assert_eq!(git.url.scheme(), "stdio");
+        assert_eq!(git.url.path(), "uvx");
+        assert_eq!(
+            git.args.as_deref(),
+            Some(
+                &[
+                    "mcp-server-git".to_string(),
+                    "--repository".to_string(),
+                    ".".to_string()
+                ][..]
+            )
+        );
+
+        let remote = &config.plugins[&PluginName("remote".to_string())];
+        assert_eq!(remote.url.scheme(), "mcp+https");
+        assert!(remote.args.is_none());
+    }
+
+    #[test]
+    fn test_pull_policy_deserialization() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+plugins:
+  always:
+    url: "oci://ghcr.io/tuananh/time-plugin:latest"
+    pull_policy: always
+  if_not_present:
+    url: "oci://ghcr.io/tuananh/time-plugin:v1"
+    pull_policy: if-not-present
+  never:
+    url: "oci://ghcr.io/tuananh/time-plugin:v1"
+    pull_policy: never
+  default:
+    url: "oci://ghcr.io/tuananh/time-plugin:v1"
+"#,
+        )
+        .unwrap();
+
+        let policy = |name: &str| config.plugins[&PluginName(name.to_string())].pull_policy;
+        assert_eq!(policy("always"), Some(PullPolicy::Always));
+        assert_eq!(policy("if_not_present"), Some(PullPolicy::IfNotPresent));
+        assert_eq!(policy("never"), Some(PullPolicy::Never));
+        assert_eq!(policy("default"), None);
+
+        let result: Result<PluginConfig, _> = serde_json::from_str(
+            r#"{"url": "oci://example.com/plugin", "pull_policy": "sometimes"}"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_signature_config_deserialization() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+plugins:
+  internal:
+    url: "oci://registry.example.com/plugins/internal:v1"
+    signature:
+      key: "/etc/hyper-mcp/cosign.pub"
+  community:
+    url: "oci://ghcr.io/example/plugin:v1"
+    signature:
+      issuer: "https://token.actions.githubusercontent.com"
+      url: "https://github.com/example/plugin/.github/workflows/release.yml@refs/heads/main"
+  unsigned:
+    url: "oci://ghcr.io/example/other:v1"
+"#,
+        )
+        .unwrap();
+
+        let signature = |name: &str| {
+            config.plugins[&PluginName(name.to_string())]
+                .signature
+                .clone()
+        };
+        assert_eq!(
+            signature("internal"),
+            Some(SignatureConfig {
+                key: Some(PathBuf::from("/etc/hyper-mcp/cosign.pub")),
+                ..Default::default()
+            })
+        );
+        let community = signature("community").unwrap();
+        assert!(community.key.is_none());
+        assert_eq!(
+            community.issuer.as_deref(),
+            Some("https://token.actions.githubusercontent.com")
+        );
+        assert!(community.url.is_some());
+        assert!(signature("unsigned").is_none());
+
+        let result: Result<SignatureConfig, _> = serde_json::from_str(r#"{"subject": "me"}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_call_limits_deserialization() {
+        let runtime_config: RuntimeConfig =
+            serde_json::from_str(r#"{"call_timeout": 1.5, "fuel_limit": 1000000}"#).unwrap();
+        assert_eq!(
+            runtime_config.call_timeout,
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(runtime_config.fuel_limit, Some(1_000_000));
+
+        let runtime_config: RuntimeConfig =
+            serde_yaml::from_str("call_timeout: 30\nfuel_limit: 500").unwrap();
+        assert_eq!(runtime_config.call_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(runtime_config.fuel_limit, Some(500));
+
+        let runtime_config: RuntimeConfig = serde_json::from_str("{}").unwrap();
+        assert!(runtime_config.call_timeout.is_none());
+        assert!(runtime_config.fuel_limit.is_none());
+
+        let result: Result<RuntimeConfig, _> = serde_json::from_str(r#"{"call_timeout": -1}"#);
+        assert!(result.is_err(), "call_timeout must not be negative");
+    }
+
+    #[test]
+    fn test_allowed_paths_deserialization() {
+        let runtime_config: RuntimeConfig = serde_yaml::from_str(
+            r#"
+allowed_paths:
+  - /tmp
+  - host: /srv/team/data
+    guest: /data
+    mode: ro
+  - host: /var/cache/plugin
+"#,
+        )
+        .unwrap();
+        let allowed_paths = runtime_config.allowed_paths.unwrap();
+        assert_eq!(allowed_paths[0], AllowedPath::from("/tmp"));
+        assert_eq!(allowed_paths[0].guest(), "/tmp");
+        assert_eq!(
+            allowed_paths[1],
+            AllowedPath {
+                host: "/srv/team/data".to_string(),
+                guest: Some("/data".to_string()),
+                mode: PathMode::Ro,
+            }
+        );
+        assert_eq!(allowed_paths[1].guest(), "/data");
+        assert_eq!(allowed_paths[2].mode, PathMode::Rw);
+        assert_eq!(allowed_paths[2].guest(), "/var/cache/plugin");
+
+        for invalid in [
+            r#"{"allowed_paths": [{"host": "/tmp", "mode": "rx"}]}"#,
+            r#"{"allowed_paths": [{"guest": "/data"}]}"#,
+            r#"{"allowed_paths": [{"host": "/tmp", "readonly": true}]}"#,
+        ] {
+            let result: Result<RuntimeConfig, _> = serde_json::from_str(invalid);
+            assert!(result.is_err(), "{invalid} should not parse");
+        }
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec)]
     fn test_skip_tools_matching_functionality() {
         let patterns = vec![
             "exact".to_string(),
@@ -2489,7 +3243,7 @@ allowed_hosts:
 
         // Load the skip_tools examples config
         let path = Path::new("tests/fixtures/skip_tools_examples.yaml");
-        let config_result = rt.block_on(load_config(path));
+        let config_result = rt.block_on(load_config(path, false));
         assert!(
             config_result.is_ok(),
             "Failed to load skip_tools examples config"
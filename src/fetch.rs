@@ -0,0 +1,174 @@
+use crate::{
+    Cli,
+    cache::PluginCache,
+    config::{Config, NetworkConfig, PluginConfig, PluginName},
+    source::{FetchRequest, PluginSource, default_sources},
+};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, sync::Arc};
+
+/// Wasm content of a plugin and the digest it resolved to: the manifest digest for OCI
+/// images, the sha256 of the content for every other scheme.
+#[derive(Clone, Debug)]
+pub struct FetchedPlugin {
+    pub content: Vec<u8>,
+    pub digest: String,
+}
+
+/// Returns the `sha256:<hex>` digest of some content.
+pub fn sha256_digest(content: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(content)))
+}
+
+/// Checks wasm content against a pinned `sha256` digest, given as hex with or without
+/// a `sha256:` prefix.
+fn verify_digest(expected: &str, content: &[u8]) -> Result<()> {
+    let expected = expected.strip_prefix("sha256:").unwrap_or(expected);
+    let actual = hex::encode(Sha256::digest(content));
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(anyhow::anyhow!(
+            "sha256 digest mismatch: expected {expected}, got {actual}"
+        ));
+    }
+    Ok(())
+}
+
+/// Downloads plugin wasm modules through the [`PluginSource`] of their URL scheme,
+/// creating each kind of client the first time it is needed.
+pub struct PluginFetcher {
+    // By URL scheme
+    sources: HashMap<&'static str, Arc<dyn PluginSource>>,
+    cache: Option<PluginCache>,
+    prefetch: bool,
+}
+
+impl Default for PluginFetcher {
+    fn default() -> Self {
+        let cache = PluginCache::open()
+            .inspect_err(|e| tracing::warn!("Plugins will not be cached: {e}"))
+            .ok();
+        let mut fetcher = Self {
+            sources: HashMap::new(),
+            cache,
+            prefetch: false,
+        };
+        for source in default_sources() {
+            fetcher.add_source(source);
+        }
+        fetcher
+    }
+}
+
+impl PluginFetcher {
+    /// A fetcher that also pulls OCI plugins whose `pull_policy` is `never`.
+    pub fn prefetching() -> Self {
+        Self {
+            prefetch: true,
+            ..Default::default()
+        }
+    }
+
+    /// Keeps downloads in `cache` instead of the user's cache directory.
+    #[cfg(test)]
+    pub fn with_cache(mut self, cache: PluginCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Downloads the schemes of `source` with it, instead of any source added before.
+    pub fn add_source(&mut self, source: Box<dyn PluginSource>) {
+        let source: Arc<dyn PluginSource> = Arc::from(source);
+        for scheme in source.schemes() {
+            self.sources.insert(scheme, Arc::clone(&source));
+        }
+    }
+
+    /// Loads a plugin through the source of its scheme and checks it against its
+    /// `sha256` pin, if it has one.
+    pub async fn fetch(
+        &self,
+        cli: &Cli,
+        config: &Config,
+        plugin_name: &PluginName,
+        plugin_cfg: &PluginConfig,
+    ) -> Result<FetchedPlugin> {
+        let scheme = plugin_cfg.url.scheme();
+        let Some(source) = self.sources.get(scheme) else {
+            tracing::error!("Unsupported plugin URL scheme: {scheme}");
+            return Err(anyhow::anyhow!("Unsupported plugin URL scheme: {scheme}"));
+        };
+        let network = NetworkConfig::from_cli(cli, config.network.as_ref());
+        let fetched = source
+            .load(&FetchRequest {
+                cli,
+                config,
+                network: &network,
+                plugin_name,
+                plugin_cfg,
+                cache: self.cache.as_ref(),
+                prefetch: self.prefetch,
+            })
+            .await?;
+        if let Some(expected) = &plugin_cfg.sha256
+            && let Err(e) = verify_digest(expected, &fetched.content)
+        {
+            tracing::error!("Refusing to load plugin {plugin_name}: {e}");
+            return Err(anyhow::anyhow!(
+                "Refusing to load plugin {plugin_name}: {e}"
+            ));
+        }
+        Ok(fetched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use clap::Parser;
+
+    struct StaticSource;
+
+    #[async_trait(?Send)]
+    impl PluginSource for StaticSource {
+        fn schemes(&self) -> &'static [&'static str] {
+            &["static"]
+        }
+
+        fn cached(&self) -> bool {
+            false
+        }
+
+        async fn fetch(&self, _request: &FetchRequest<'_>) -> Result<Vec<u8>> {
+            Ok(b"wasm".to_vec())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_fetcher_verifies_digest() {
+        let mut fetcher = PluginFetcher::default();
+        fetcher.add_source(Box::new(StaticSource));
+        let cli = Cli::parse_from(["hyper-mcp"]);
+        let fetch = |sha256: &str| {
+            let config: Config = serde_yaml::from_str(&format!(
+                "plugins:\n  time:\n    url: static://time.wasm\n    sha256: \"{sha256}\"\n"
+            ))
+            .unwrap();
+            let fetcher = &fetcher;
+            let cli = &cli;
+            async move {
+                let (plugin_name, plugin_cfg) = config.plugins.iter().next().unwrap();
+                fetcher.fetch(cli, &config, plugin_name, plugin_cfg).await
+            }
+        };
+
+        let digest = sha256_digest(b"wasm");
+        let fetched = fetch(&digest).await.unwrap();
+        assert_eq!(fetched.content, b"wasm");
+        assert_eq!(fetched.digest, digest);
+
+        let error = fetch(&"0".repeat(64)).await.unwrap_err().to_string();
+        assert!(error.contains("sha256 digest mismatch"), "{error}");
+    }
+}
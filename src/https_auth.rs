@@ -1,33 +1,198 @@
-use crate::config::AuthConfig;
-use reqwest::RequestBuilder;
-use std::{cmp::Reverse, collections::HashMap};
+use crate::{config::AuthConfig, fetch::sha256_digest};
+use anyhow::{Context, Result, anyhow};
+use dashmap::DashMap;
+use reqwest::{Identity, RequestBuilder};
+use serde::Deserialize;
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    path::Path,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
 use url::Url;
 
+// OAuth2 access tokens are fetched again this long before they expire
+const OAUTH2_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+// How long a token is kept when the token endpoint does not say when it expires
+const OAUTH2_DEFAULT_LIFETIME: Duration = Duration::from_secs(300);
+
+static OAUTH2_TOKENS: LazyLock<DashMap<OAuth2Client, CachedToken>> = LazyLock::new(DashMap::new);
+
+#[derive(Clone, Eq, Hash, PartialEq)]
+struct OAuth2Client {
+    token_url: Url,
+    client_id: String,
+    // Digest of the client secret, so a rotated secret fetches a new token
+    secret_digest: String,
+    scopes: Vec<String>,
+}
+
+#[derive(Clone)]
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+/// Returns the auth config whose URL is the longest prefix of `url`, if any.
+pub fn find_auth<'a>(
+    auths: &'a Option<HashMap<Url, AuthConfig>>,
+    url: &Url,
+) -> Option<&'a AuthConfig> {
+    let mut auths: Vec<(&str, &AuthConfig)> = auths
+        .iter()
+        .flatten()
+        .map(|(k, v)| (k.as_str(), v))
+        .collect();
+    auths.sort_by_key(|c| Reverse(c.0.len()));
+    let url = url.to_string();
+    auths
+        .into_iter()
+        .find(|(k, _)| url.starts_with(k))
+        .map(|(_, v)| v)
+}
+
+/// Returns the client certificate to present to `url`, when the auth matching it uses
+/// mutual TLS. Clients need rustls to present it.
+pub async fn client_identity(
+    auths: &Option<HashMap<Url, AuthConfig>>,
+    url: &Url,
+) -> Result<Option<Identity>> {
+    let Some(auth) = find_auth(auths, url) else {
+        return Ok(None);
+    };
+    let AuthConfig::Mtls { cert, key } = auth.resolve().await?.into_owned() else {
+        return Ok(None);
+    };
+    mtls_identity(&cert, &key).map(Some)
+}
+
+/// Reads a client certificate and its key, both PEM encoded.
+pub fn mtls_identity(cert: &Path, key: &Path) -> Result<Identity> {
+    let mut pem = std::fs::read(cert)
+        .with_context(|| format!("Failed to read client certificate {}", cert.display()))?;
+    pem.push(b'\n');
+    pem.extend(
+        std::fs::read(key)
+            .with_context(|| format!("Failed to read client key {}", key.display()))?,
+    );
+    Identity::from_pem(&pem)
+        .with_context(|| format!("Invalid client certificate {}", cert.display()))
+}
+
+/// Returns an access token for an OAuth2 client, from the cache while it is valid.
+///
+/// The token is requested with `http_client`, so it should trust the configured
+/// certificates and go through the configured proxies.
+pub async fn oauth2_token(
+    http_client: &reqwest::Client,
+    token_url: &Url,
+    client_id: &str,
+    client_secret: &str,
+    scopes: &[String],
+) -> Result<String> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        access_token: String,
+        expires_in: Option<u64>,
+    }
+
+    let client = OAuth2Client {
+        token_url: token_url.clone(),
+        client_id: client_id.to_string(),
+        secret_digest: sha256_digest(client_secret.as_bytes()),
+        scopes: scopes.to_vec(),
+    };
+    if let Some(cached) = OAUTH2_TOKENS.get(&client)
+        && Instant::now() + OAUTH2_EXPIRY_MARGIN < cached.expires_at
+    {
+        return Ok(cached.access_token.clone());
+    }
+
+    let mut form = vec![("grant_type", "client_credentials".to_string())];
+    if !scopes.is_empty() {
+        form.push(("scope", scopes.join(" ")));
+    }
+    let response = http_client
+        .post(token_url.as_str())
+        .basic_auth(client_id, Some(client_secret))
+        .form(&form)
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!(
+            "{token_url} refused the client credentials of {client_id} with {status}: {}",
+            response.text().await.unwrap_or_default()
+        ));
+    }
+    let token: TokenResponse = response.json().await?;
+    tracing::debug!("Fetched an OAuth2 access token for {client_id} from {token_url}");
+    OAUTH2_TOKENS.insert(
+        client,
+        CachedToken {
+            access_token: token.access_token.clone(),
+            expires_at: Instant::now()
+                + token
+                    .expires_in
+                    .map_or(OAUTH2_DEFAULT_LIFETIME, Duration::from_secs),
+        },
+    );
+    Ok(token.access_token)
+}
+
 pub trait Authenticator {
     /// Adds authentication headers to the request if present in auths.
-    fn add_auth(self, auths: &Option<HashMap<Url, AuthConfig>>, url: &Url) -> RequestBuilder;
+    ///
+    /// Credentials that cannot be read or fetched are logged and left out.
+    async fn add_auth(self, auths: &Option<HashMap<Url, AuthConfig>>, url: &Url) -> RequestBuilder;
 }
 
 impl Authenticator for RequestBuilder {
-    fn add_auth(self, auths: &Option<HashMap<Url, AuthConfig>>, url: &Url) -> RequestBuilder {
-        if let Some(auths) = auths {
-            let mut auths: Vec<(&str, &AuthConfig)> =
-                auths.iter().map(|(k, v)| (k.as_str(), v)).collect();
-            auths.sort_by_key(|c| Reverse(c.0.len()));
-            let url = url.to_string();
-            for (k, v) in auths {
-                if url.starts_with(k) {
-                    return match v {
-                        AuthConfig::Basic { username, password } => {
-                            self.basic_auth(username, Some(password))
-                        }
-                        AuthConfig::Token { token } => self.bearer_auth(token),
-                    };
+    async fn add_auth(self, auths: &Option<HashMap<Url, AuthConfig>>, url: &Url) -> RequestBuilder {
+        let Some(auth) = find_auth(auths, url) else {
+            return self;
+        };
+        let auth = match auth.resolve().await {
+            Ok(auth) => auth,
+            Err(e) => {
+                tracing::error!("Failed to read credentials for {url}: {e}");
+                return self;
+            }
+        };
+        match auth.as_ref() {
+            AuthConfig::Basic { username, password } => self.basic_auth(username, Some(password)),
+            AuthConfig::Token { token } => self.bearer_auth(token),
+            AuthConfig::Header { name, value } => self.header(name, value),
+            AuthConfig::OAuth2 {
+                token_url,
+                client_id,
+                client_secret,
+                scopes,
+            } => {
+                // Ask for the token with the client the request is sent with, which has
+                // the network settings. Only requests with streaming bodies cannot be
+                // cloned to get at it
+                let http_client = self
+                    .try_clone()
+                    .map(|builder| builder.build_split().0)
+                    .unwrap_or_default();
+                match oauth2_token(&http_client, token_url, client_id, client_secret, scopes).await
+                {
+                    Ok(token) => self.bearer_auth(token),
+                    Err(e) => {
+                        tracing::error!("Failed to get an OAuth2 access token for {url}: {e}");
+                        self
+                    }
                 }
             }
+            // Identity tokens only mean something to OCI registries, and client
+            // certificates belong to the client, see `client_identity`
+            AuthConfig::IdentityToken { .. } | AuthConfig::Mtls { .. } | AuthConfig::Secret(_) => {
+                self
+            }
         }
-
-        self
     }
 }
 
@@ -38,8 +203,8 @@ mod tests {
     use std::collections::HashMap;
     use url::Url;
 
-    #[test]
-    fn test_add_auth_basic_authentication() {
+    #[tokio::test]
+    async fn test_add_auth_basic_authentication() {
         let client = Client::new();
         let mut auths = HashMap::new();
 
@@ -53,7 +218,7 @@ mod tests {
         );
 
         let request = client.get("https://api.example.com/endpoint");
-        let authenticated_request = request.add_auth(&Some(auths), &url);
+        let authenticated_request = request.add_auth(&Some(auths), &url).await;
 
         // We can't easily test the actual header since reqwest doesn't expose it,
         // but we can verify the method doesn't panic and returns a RequestBuilder
@@ -61,8 +226,8 @@ mod tests {
         drop(authenticated_request);
     }
 
-    #[test]
-    fn test_add_auth_token_authentication() {
+    #[tokio::test]
+    async fn test_add_auth_token_authentication() {
         let client = Client::new();
         let mut auths = HashMap::new();
 
@@ -75,42 +240,42 @@ mod tests {
         );
 
         let request = client.get("https://api.example.com/endpoint");
-        let authenticated_request = request.add_auth(&Some(auths), &url);
+        let authenticated_request = request.add_auth(&Some(auths), &url).await;
 
         // Verify the method completes without error
         // The fact that we got here without panicking means the method worked
         drop(authenticated_request);
     }
 
-    #[test]
-    fn test_add_auth_no_auths_provided() {
+    #[tokio::test]
+    async fn test_add_auth_no_auths_provided() {
         let client = Client::new();
         let url = Url::parse("https://api.example.com").unwrap();
 
         let request = client.get("https://api.example.com/endpoint");
-        let result_request = request.add_auth(&None, &url);
+        let result_request = request.add_auth(&None, &url).await;
 
         // Should return the original request unchanged
         // The fact that we got here without panicking means the method worked
         drop(result_request);
     }
 
-    #[test]
-    fn test_add_auth_empty_auths_map() {
+    #[tokio::test]
+    async fn test_add_auth_empty_auths_map() {
         let client = Client::new();
         let auths = HashMap::new();
         let url = Url::parse("https://api.example.com").unwrap();
 
         let request = client.get("https://api.example.com/endpoint");
-        let result_request = request.add_auth(&Some(auths), &url);
+        let result_request = request.add_auth(&Some(auths), &url).await;
 
         // Should return the original request unchanged when no matching auth
         // The fact that we got here without panicking means the method worked
         drop(result_request);
     }
 
-    #[test]
-    fn test_add_auth_url_prefix_matching() {
+    #[tokio::test]
+    async fn test_add_auth_url_prefix_matching() {
         let client = Client::new();
         let mut auths = HashMap::new();
 
@@ -136,15 +301,15 @@ mod tests {
         // Test that longer prefix wins
         let target_url = Url::parse("https://example.com/api/v1/data").unwrap();
         let request = client.get(target_url.as_str());
-        let authenticated_request = request.add_auth(&Some(auths), &target_url);
+        let authenticated_request = request.add_auth(&Some(auths), &target_url).await;
 
         // The API token should be used (longest prefix)
         // The fact that we got here without panicking means the method worked
         drop(authenticated_request);
     }
 
-    #[test]
-    fn test_add_auth_url_no_match() {
+    #[tokio::test]
+    async fn test_add_auth_url_no_match() {
         let client = Client::new();
         let mut auths = HashMap::new();
 
@@ -160,15 +325,15 @@ mod tests {
         // Request to different domain
         let target_url = Url::parse("https://different.com/endpoint").unwrap();
         let request = client.get(target_url.as_str());
-        let result_request = request.add_auth(&Some(auths), &target_url);
+        let result_request = request.add_auth(&Some(auths), &target_url).await;
 
         // Should return the original request unchanged when no URL match
         // The fact that we got here without panicking means the method worked
         drop(result_request);
     }
 
-    #[test]
-    fn test_add_auth_multiple_auths_longest_prefix_wins() {
+    #[tokio::test]
+    async fn test_add_auth_multiple_auths_longest_prefix_wins() {
         let client = Client::new();
         let mut auths = HashMap::new();
 
@@ -199,7 +364,7 @@ mod tests {
         // Test with URL that matches all three (longest should win)
         let target_url = Url::parse("https://example.com/api/v1/endpoint").unwrap();
         let request = client.get(target_url.as_str());
-        let authenticated_request = request.add_auth(&Some(auths), &target_url);
+        let authenticated_request = request.add_auth(&Some(auths), &target_url).await;
 
         // Should use the v1 auth (longest prefix)
         // The fact that we got here without panicking means the method worked
@@ -207,7 +372,40 @@ mod tests {
     }
 
     #[test]
-    fn test_add_auth_exact_url_match() {
+    fn test_find_auth_longest_prefix_wins() {
+        let mut auths = HashMap::new();
+        auths.insert(
+            Url::parse("https://registry.example.com").unwrap(),
+            AuthConfig::Token {
+                token: "registry_token".to_string(),
+            },
+        );
+        auths.insert(
+            Url::parse("https://registry.example.com/team/").unwrap(),
+            AuthConfig::Token {
+                token: "team_token".to_string(),
+            },
+        );
+        let auths = Some(auths);
+
+        let token = |url: &str| match find_auth(&auths, &Url::parse(url).unwrap()) {
+            Some(AuthConfig::Token { token }) => Some(token.as_str()),
+            _ => None,
+        };
+        assert_eq!(
+            token("https://registry.example.com/team/plugin"),
+            Some("team_token")
+        );
+        assert_eq!(
+            token("https://registry.example.com/other/plugin"),
+            Some("registry_token")
+        );
+        assert_eq!(token("https://other.example.com/team/plugin"), None);
+        assert!(find_auth(&None, &Url::parse("https://a.b").unwrap()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_add_auth_exact_url_match() {
         let client = Client::new();
         let mut auths = HashMap::new();
 
@@ -220,14 +418,14 @@ mod tests {
         );
 
         let request = client.get(exact_url.as_str());
-        let authenticated_request = request.add_auth(&Some(auths), &exact_url);
+        let authenticated_request = request.add_auth(&Some(auths), &exact_url).await;
 
         // The fact that we got here without panicking means the method worked
         drop(authenticated_request);
     }
 
-    #[test]
-    fn test_add_auth_case_sensitive_urls() {
+    #[tokio::test]
+    async fn test_add_auth_case_sensitive_urls() {
         let client = Client::new();
         let mut auths = HashMap::new();
 
@@ -243,15 +441,15 @@ mod tests {
         // Test with lowercase URL
         let target_url = Url::parse("https://api.example.com/endpoint").unwrap();
         let request = client.get(target_url.as_str());
-        let result_request = request.add_auth(&Some(auths), &target_url);
+        let result_request = request.add_auth(&Some(auths), &target_url).await;
 
         // Should not match due to case sensitivity
         // The fact that we got here without panicking means the method worked
         drop(result_request);
     }
 
-    #[test]
-    fn test_auth_config_types_comprehensive() {
+    #[tokio::test]
+    async fn test_auth_config_types_comprehensive() {
         // Test all AuthConfig variants can be created and used
         let basic_auth = AuthConfig::Basic {
             username: "basic_user".to_string(),
@@ -270,7 +468,7 @@ mod tests {
         auths.insert(url.clone(), basic_auth);
 
         let request1 = client.get(url.as_str());
-        let result1 = request1.add_auth(&Some(auths), &url);
+        let result1 = request1.add_auth(&Some(auths), &url).await;
         // The fact that we got here without panicking means the method worked
         drop(result1);
 
@@ -278,8 +476,164 @@ mod tests {
         auths.insert(url.clone(), token_auth);
 
         let request2 = client.get(url.as_str());
-        let result2 = request2.add_auth(&Some(auths), &url);
+        let result2 = request2.add_auth(&Some(auths), &url).await;
         // The fact that we got here without panicking means the method worked
         drop(result2);
     }
+
+    #[tokio::test]
+    async fn test_add_auth_header() {
+        let url = Url::parse("https://gitlab.example.com/api").unwrap();
+        let auths = Some(HashMap::from([(
+            url.clone(),
+            AuthConfig::Header {
+                name: "PRIVATE-TOKEN".to_string(),
+                value: "glpat-123".to_string(),
+            },
+        )]));
+
+        let request = Client::new()
+            .get(url.as_str())
+            .add_auth(&auths, &url)
+            .await
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["PRIVATE-TOKEN"], "glpat-123");
+    }
+
+    #[tokio::test]
+    async fn test_add_auth_oauth2_caches_token() {
+        use axum::{Form, Json, Router, routing::post};
+        use std::sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        };
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        let app = Router::new().route(
+            "/token",
+            post(
+                move |headers: axum::http::HeaderMap, Form(form): Form<HashMap<String, String>>| {
+                    let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move {
+                        assert_eq!(form["grant_type"], "client_credentials");
+                        assert_eq!(form["scope"], "read write");
+                        // client_id:client_secret, then client_id:rotated_secret
+                        assert!(
+                            [
+                                "Basic Y2xpZW50X2lkOmNsaWVudF9zZWNyZXQ=",
+                                "Basic Y2xpZW50X2lkOnJvdGF0ZWRfc2VjcmV0"
+                            ]
+                            .contains(&headers["authorization"].to_str().unwrap())
+                        );
+                        Json(serde_json::json!({
+                            "access_token": format!("token-{n}"),
+                            "token_type": "Bearer",
+                            "expires_in": 3600
+                        }))
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let url = Url::parse("https://artifacts.example.com").unwrap();
+        let auths = |client_secret: &str| {
+            Some(HashMap::from([(
+                url.clone(),
+                AuthConfig::OAuth2 {
+                    token_url: Url::parse(&format!("http://{addr}/token")).unwrap(),
+                    client_id: "client_id".to_string(),
+                    client_secret: client_secret.to_string(),
+                    scopes: vec!["read".to_string(), "write".to_string()],
+                },
+            )]))
+        };
+        let authorization = |auths| {
+            let url = &url;
+            async move {
+                let request = Client::new()
+                    .get(url.as_str())
+                    .add_auth(&auths, url)
+                    .await
+                    .build()
+                    .unwrap();
+                request.headers()["authorization"].clone()
+            }
+        };
+        for _ in 0..2 {
+            assert_eq!(
+                authorization(auths("client_secret")).await,
+                "Bearer token-1"
+            );
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            authorization(auths("rotated_secret")).await,
+            "Bearer token-2"
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_add_auth_oauth2_uses_request_client() {
+        use axum::{Json, Router};
+
+        // Stands in for the only proxy that can reach the token endpoint
+        let app = Router::new().fallback(|| async {
+            Json(serde_json::json!({ "access_token": "proxied", "token_type": "Bearer" }))
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let url = Url::parse("https://artifacts.example.com").unwrap();
+        let auths = Some(HashMap::from([(
+            url.clone(),
+            AuthConfig::OAuth2 {
+                token_url: Url::parse("http://oauth.invalid/token").unwrap(),
+                client_id: "proxied_client".to_string(),
+                client_secret: "client_secret".to_string(),
+                scopes: vec![],
+            },
+        )]));
+        let client = Client::builder()
+            .proxy(reqwest::Proxy::http(format!("http://{addr}")).unwrap())
+            .build()
+            .unwrap();
+        let request = client
+            .get(url.as_str())
+            .add_auth(&auths, &url)
+            .await
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["authorization"], "Bearer proxied");
+    }
+
+    #[tokio::test]
+    async fn test_client_identity() {
+        let url = Url::parse("https://internal.example.com").unwrap();
+        let mtls = |cert: &str, key: &str| {
+            Some(HashMap::from([(
+                url.clone(),
+                AuthConfig::Mtls {
+                    cert: cert.into(),
+                    key: key.into(),
+                },
+            )]))
+        };
+
+        let auths = mtls("tests/fixtures/client.crt", "tests/fixtures/client.key");
+        assert!(client_identity(&auths, &url).await.unwrap().is_some());
+        let other = Url::parse("https://other.example.com").unwrap();
+        assert!(client_identity(&auths, &other).await.unwrap().is_none());
+
+        let auths = mtls("tests/fixtures/missing.crt", "tests/fixtures/client.key");
+        let error = client_identity(&auths, &url).await.unwrap_err().to_string();
+        assert!(error.contains("missing.crt"), "{error}");
+    }
 }
@@ -0,0 +1,201 @@
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+
+/// Expands the `${...}` references in every string of a parsed config, keys included.
+///
+/// - `${NAME}` is the environment variable `NAME`.
+/// - `${NAME:-default}` falls back to `default` when `NAME` is unset or empty.
+/// - `${file:/path}` is the content of a file without its trailing newline, as Docker
+///   and Kubernetes mount secrets.
+/// - `$${` is a literal `${`.
+///
+/// A reference that cannot be resolved is kept as is with a warning, or is an error
+/// when `strict` is set. Every unresolved reference is reported at once.
+pub fn interpolate_config(value: &mut Value, strict: bool) -> Result<()> {
+    let mut unresolved = Vec::new();
+    interpolate_value(value, "", &mut unresolved);
+    if unresolved.is_empty() {
+        return Ok(());
+    }
+    if strict {
+        return Err(anyhow!(
+            "Failed to resolve references in the config:\n  {}",
+            unresolved.join("\n  ")
+        ));
+    }
+    for reference in unresolved {
+        tracing::warn!("Keeping unresolved reference {reference}");
+    }
+    Ok(())
+}
+
+fn interpolate_value(value: &mut Value, path: &str, unresolved: &mut Vec<String>) {
+    match value {
+        Value::String(s) => *s = interpolate(s, path, unresolved),
+        Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                interpolate_value(value, &format!("{path}[{i}]"), unresolved);
+            }
+        }
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    let key = interpolate(&key, path, unresolved);
+                    let path = match path.is_empty() {
+                        true => key.clone(),
+                        false => format!("{path}.{key}"),
+                    };
+                    interpolate_value(&mut value, &path, unresolved);
+                    (key, value)
+                })
+                .collect();
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// Expands the references in one string, adding the ones it cannot resolve, with
+/// where they are and why, to `unresolved`.
+fn interpolate(s: &str, path: &str, unresolved: &mut Vec<String>) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(after) = rest.strip_prefix("${") else {
+            result.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let Some(end) = after.find('}') else {
+            unresolved.push(format!("{path}: {rest} is not closed"));
+            result.push_str(rest);
+            return result;
+        };
+        let reference = &after[..end];
+        match resolve(reference) {
+            Ok(value) => result.push_str(&value),
+            Err(e) => {
+                unresolved.push(format!("{path}: ${{{reference}}} {e}"));
+                result.push_str(&rest[..end + 3]);
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn resolve(reference: &str) -> Result<String> {
+    let (name, default) = match reference.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (reference, None),
+    };
+    let value = match name.strip_prefix("file:") {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(content) => Some(content.trim_end_matches(['\n', '\r']).to_string()),
+            Err(_) if default.is_some() => None,
+            Err(e) => return Err(anyhow!("cannot be read: {e}")),
+        },
+        None => std::env::var(name).ok(),
+    };
+    match (value, default) {
+        (Some(value), Some(default)) if value.is_empty() => Ok(default.to_string()),
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => Ok(default.to_string()),
+        (None, None) => Err(anyhow!("is not set")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_interpolate() {
+        // SAFETY: no other test reads these variables
+        unsafe {
+            std::env::set_var("HYPER_MCP_TEST_HOST", "registry.example.com");
+            std::env::set_var("HYPER_MCP_TEST_EMPTY", "");
+        }
+        let temp_dir = tempfile::tempdir().unwrap();
+        let secret = temp_dir.path().join("token");
+        std::fs::write(&secret, "s3cret\n").unwrap();
+
+        let expand = |s: &str| {
+            let mut unresolved = Vec::new();
+            let expanded = interpolate(s, "x", &mut unresolved);
+            assert!(unresolved.is_empty(), "{unresolved:?}");
+            expanded
+        };
+        assert_eq!(
+            expand("oci://${HYPER_MCP_TEST_HOST}/time:v1"),
+            "oci://registry.example.com/time:v1"
+        );
+        assert_eq!(expand("${HYPER_MCP_TEST_UNSET:-fallback}"), "fallback");
+        assert_eq!(expand("${HYPER_MCP_TEST_EMPTY:-fallback}"), "fallback");
+        assert_eq!(
+            expand("${HYPER_MCP_TEST_HOST:-fallback}"),
+            "registry.example.com"
+        );
+        assert_eq!(expand(&format!("${{file:{}}}", secret.display())), "s3cret");
+        assert_eq!(expand("${file:/nonexistent/token:-none}"), "none");
+        assert_eq!(
+            expand("$${HYPER_MCP_TEST_HOST} costs $5"),
+            "${HYPER_MCP_TEST_HOST} costs $5"
+        );
+
+        let mut unresolved = Vec::new();
+        assert_eq!(
+            interpolate(
+                "a${HYPER_MCP_TEST_UNSET}b${HYPER_MCP_TEST_HOST",
+                "x",
+                &mut unresolved
+            ),
+            "a${HYPER_MCP_TEST_UNSET}b${HYPER_MCP_TEST_HOST"
+        );
+        assert_eq!(
+            unresolved,
+            vec![
+                "x: ${HYPER_MCP_TEST_UNSET} is not set",
+                "x: ${HYPER_MCP_TEST_HOST is not closed"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpolate_config() {
+        // SAFETY: no other test reads these variables
+        unsafe { std::env::set_var("HYPER_MCP_TEST_TOKEN", "abc") };
+        let config = json!({
+            "auths": {"https://${HYPER_MCP_TEST_REGISTRY:-ghcr.io}": {"type": "token", "token": "${HYPER_MCP_TEST_TOKEN}"}},
+            "plugins": {"time": {"url": "oci://ghcr.io/time", "runtime_config": {"allowed_hosts": ["${HYPER_MCP_TEST_MISSING}"], "max_instances": 2}}}
+        });
+
+        let mut value = config.clone();
+        interpolate_config(&mut value, false).unwrap();
+        assert_eq!(value["auths"]["https://ghcr.io"]["token"], json!("abc"));
+        assert_eq!(
+            value["plugins"]["time"]["runtime_config"]["allowed_hosts"][0],
+            json!("${HYPER_MCP_TEST_MISSING}")
+        );
+
+        let mut value = config;
+        let error = interpolate_config(&mut value, true)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains(
+                "plugins.time.runtime_config.allowed_hosts[0]: ${HYPER_MCP_TEST_MISSING} is not set"
+            ),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    Cli,
+    config::{PluginName, load_config},
+    fetch::PluginFetcher,
+    service::config_path,
+    source::stored_url,
+    upstream::is_upstream_url,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+pub const LOCKFILE_NAME: &str = "hyper-mcp.lock";
+
+const LOCKFILE_HEADER: &str =
+    "# This file is generated by `hyper-mcp lock`. Do not edit it by hand.\n\n";
+
+/// Returns the path of the lockfile that belongs to a config file.
+pub fn lockfile_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(LOCKFILE_NAME)
+}
+
+/// What each plugin resolved to when the lockfile was last updated.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub plugins: BTreeMap<PluginName, LockedPlugin>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LockedPlugin {
+    pub url: Url,
+    // OCI manifest digest, or the sha256 of the downloaded content for other schemes
+    pub digest: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl Lockfile {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read lockfile {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse lockfile {}", path.display()))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let content = format!("{LOCKFILE_HEADER}{}", toml::to_string_pretty(self)?);
+        tokio::fs::write(path, content)
+            .await
+            .with_context(|| format!("Failed to write lockfile {}", path.display()))
+    }
+
+    /// Fails unless the plugin is locked to this url and digest.
+    pub fn check(&self, plugin_name: &PluginName, url: &Url, digest: &str) -> Result<()> {
+        let locked = self.plugins.get(plugin_name).ok_or_else(|| {
+            anyhow::anyhow!("Plugin {plugin_name} is not in the lockfile, run `hyper-mcp lock`")
+        })?;
+        let url = stored_url(url);
+        if stored_url(&locked.url) != url {
+            return Err(anyhow::anyhow!(
+                "Plugin {plugin_name} is locked to {}, but is configured as {url}",
+                locked.url
+            ));
+        }
+        if locked.digest != digest {
+            return Err(anyhow::anyhow!(
+                "Plugin {plugin_name} resolved to {digest}, but is locked to {}",
+                locked.digest
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Resolves every configured plugin and writes the result to the lockfile.
+///
+/// Entries that still resolve to the same digest keep their original fetch time.
+pub async fn update_lockfile(cli: &Cli) -> Result<()> {
+    let config_path = config_path(cli);
+    let config = load_config(&config_path, cli.strict_env).await?;
+    let path = lockfile_path(&config_path);
+    let previous = match tokio::fs::try_exists(&path).await? {
+        true => Lockfile::load(&path).await?,
+        false => Lockfile::default(),
+    };
+
+    let fetcher = PluginFetcher::default();
+    let mut lockfile = Lockfile::default();
+    for (plugin_name, plugin_cfg) in &config.plugins {
+        if is_upstream_url(&plugin_cfg.url) {
+            continue;
+        }
+        let fetched = fetcher
+            .fetch(cli, &config, plugin_name, plugin_cfg)
+            .await
+            .with_context(|| format!("Failed to fetch plugin {plugin_name}"))?;
+        let locked = match previous.plugins.get(plugin_name) {
+            Some(locked)
+                if locked.url == stored_url(&plugin_cfg.url) && locked.digest == fetched.digest =>
+            {
+                locked.clone()
+            }
+            _ => {
+                tracing::info!("Locked plugin {plugin_name} to {}", fetched.digest);
+                LockedPlugin {
+                    url: stored_url(&plugin_cfg.url),
+                    digest: fetched.digest,
+                    fetched_at: Utc::now(),
+                }
+            }
+        };
+        lockfile.plugins.insert(plugin_name.clone(), locked);
+    }
+
+    lockfile.save(&path).await?;
+    tracing::info!("Wrote lockfile {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn locked_plugin(url: &str, digest: &str) -> LockedPlugin {
+        LockedPlugin {
+            url: Url::parse(url).unwrap(),
+            digest: digest.to_string(),
+            fetched_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lockfile_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = lockfile_path(&dir.path().join("config.json"));
+        assert_eq!(path, dir.path().join(LOCKFILE_NAME));
+
+        let mut lockfile = Lockfile::default();
+        lockfile.plugins.insert(
+            PluginName::try_from("time").unwrap(),
+            locked_plugin("oci://ghcr.io/tuananh/time-plugin:latest", "sha256:abc"),
+        );
+        lockfile.save(&path).await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(LOCKFILE_HEADER));
+        assert_eq!(Lockfile::load(&path).await.unwrap(), lockfile);
+    }
+
+    #[test]
+    fn test_lockfile_check() {
+        let name = PluginName::try_from("time").unwrap();
+        let url = Url::parse("oci://ghcr.io/tuananh/time-plugin:latest").unwrap();
+        let mut lockfile = Lockfile::default();
+        assert!(lockfile.check(&name, &url, "sha256:abc").is_err());
+
+        lockfile
+            .plugins
+            .insert(name.clone(), locked_plugin(url.as_str(), "sha256:abc"));
+        assert!(lockfile.check(&name, &url, "sha256:abc").is_ok());
+
+        let err = lockfile.check(&name, &url, "sha256:def").unwrap_err();
+        assert!(err.to_string().contains("locked to sha256:abc"));
+
+        let other = Url::parse("oci://ghcr.io/tuananh/time-plugin:v2").unwrap();
+        let err = lockfile.check(&name, &other, "sha256:abc").unwrap_err();
+        assert!(err.to_string().contains("is configured as"));
+    }
+
+    #[test]
+    fn test_lockfile_check_ignores_sas_token() {
+        let name = PluginName::try_from("time").unwrap();
+        let mut lockfile = Lockfile::default();
+        lockfile.plugins.insert(
+            name.clone(),
+            locked_plugin("az://team/plugins/time.wasm", "sha256:abc"),
+        );
+
+        let rotated = Url::parse("az://team/plugins/time.wasm?sv=2024&sig=def").unwrap();
+        assert!(lockfile.check(&name, &rotated, "sha256:abc").is_ok());
+
+        let other = Url::parse("az://team/plugins/other.wasm?sig=def").unwrap();
+        let err = lockfile.check(&name, &other, "sha256:abc").unwrap_err();
+        assert!(!err.to_string().contains("sig=def"));
+    }
+}
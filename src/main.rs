@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use rmcp::transport::sse_server::SseServer;
 use rmcp::transport::streamable_http_server::{
     StreamableHttpService, session::local::LocalSessionManager,
@@ -8,19 +8,34 @@ use rmcp::{ServiceExt, transport::stdio};
 use std::path::PathBuf;
 use tokio::{runtime::Handle, task::block_in_place};
 
+mod cache;
 mod config;
+mod fetch;
 mod https_auth;
+mod interpolate;
+mod lockfile;
 mod logging;
+mod metadata;
+mod network;
 mod oci;
+mod outbound;
 mod plugin;
+mod push;
+mod reload;
+mod secret;
 mod service;
+mod source;
+mod upstream;
 
 pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:3001";
 
 #[derive(Parser, Clone)]
 #[command(author = "Tuan Anh Tran <me@tuananh.org>", version = env!("CARGO_PKG_VERSION"), about, long_about = None)]
 struct Cli {
-    #[arg(short, long, value_name = "FILE")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, value_name = "FILE", global = true)]
     config_file: Option<PathBuf>,
 
     #[arg(
@@ -44,6 +59,7 @@ struct Cli {
         long = "insecure-skip-signature",
         help = "Skip OCI image signature verification",
         env = "HYPER_MCP_INSECURE_SKIP_SIGNATURE",
+        global = true,
         default_value = "false"
     )]
     insecure_skip_signature: bool,
@@ -52,6 +68,7 @@ struct Cli {
         long = "use-sigstore-tuf-data",
         help = "Use Sigstore TUF data for verification",
         env = "HYPER_MCP_USE_SIGSTORE_TUF_DATA",
+        global = true,
         default_value = "true"
     )]
     use_sigstore_tuf_data: bool,
@@ -59,44 +76,192 @@ struct Cli {
     #[arg(
         long = "rekor-pub-keys",
         help = "Path to Rekor public keys for verification",
-        env = "HYPER_MCP_REKOR_PUB_KEYS"
+        env = "HYPER_MCP_REKOR_PUB_KEYS",
+        global = true
     )]
     rekor_pub_keys: Option<PathBuf>,
 
     #[arg(
         long = "fulcio-certs",
         help = "Path to Fulcio certificates for verification",
-        env = "HYPER_MCP_FULCIO_CERTS"
+        env = "HYPER_MCP_FULCIO_CERTS",
+        global = true
     )]
     fulcio_certs: Option<PathBuf>,
 
     #[arg(
         long = "cert-issuer",
         help = "Certificate issuer to verify against",
-        env = "HYPER_MCP_CERT_ISSUER"
+        env = "HYPER_MCP_CERT_ISSUER",
+        global = true
     )]
     cert_issuer: Option<String>,
 
     #[arg(
         long = "cert-email",
         help = "Certificate email to verify against",
-        env = "HYPER_MCP_CERT_EMAIL"
+        env = "HYPER_MCP_CERT_EMAIL",
+        global = true
     )]
     cert_email: Option<String>,
 
     #[arg(
         long = "cert-url",
         help = "Certificate URL to verify against",
-        env = "HYPER_MCP_CERT_URL"
+        env = "HYPER_MCP_CERT_URL",
+        global = true
     )]
     cert_url: Option<String>,
+
+    #[arg(
+        long = "cosign-key",
+        help = "Path to a cosign public key that OCI plugins must be signed with",
+        env = "HYPER_MCP_COSIGN_KEY",
+        global = true
+    )]
+    cosign_key: Option<PathBuf>,
+
+    #[arg(
+        long = "locked",
+        help = "Refuse to load plugins that do not resolve to the digest in hyper-mcp.lock",
+        env = "HYPER_MCP_LOCKED",
+        global = true,
+        default_value = "false"
+    )]
+    locked: bool,
+
+    #[arg(
+        long = "offline",
+        help = "Never fetch plugins over the network, only load them from disk or the plugin cache",
+        env = "HYPER_MCP_OFFLINE",
+        global = true,
+        default_value = "false"
+    )]
+    offline: bool,
+
+    #[arg(
+        long = "strict-capabilities",
+        help = "Refuse to load plugins that need hosts or paths their runtime_config does not allow",
+        env = "HYPER_MCP_STRICT_CAPABILITIES",
+        global = true,
+        default_value = "false"
+    )]
+    strict_capabilities: bool,
+
+    #[arg(
+        long = "record-hosts",
+        value_name = "FILE",
+        help = "Record the hosts each plugin tries to reach in a JSON file",
+        env = "HYPER_MCP_RECORD_HOSTS",
+        global = true
+    )]
+    record_hosts: Option<PathBuf>,
+
+    #[arg(
+        long = "strict-env",
+        help = "Fail to load the config when one of its ${...} references cannot be resolved",
+        env = "HYPER_MCP_STRICT_ENV",
+        global = true,
+        default_value = "false"
+    )]
+    strict_env: bool,
+
+    #[arg(
+        long = "ca-cert",
+        value_name = "FILE",
+        help = "PEM file with root certificates to trust when fetching plugins, can be repeated",
+        env = "HYPER_MCP_CA_CERTS",
+        value_delimiter = ',',
+        global = true
+    )]
+    ca_certs: Vec<PathBuf>,
+
+    #[arg(
+        long = "https-proxy",
+        value_name = "URL",
+        help = "Proxy for https requests when fetching plugins",
+        env = "HTTPS_PROXY",
+        global = true
+    )]
+    https_proxy: Option<String>,
+
+    #[arg(
+        long = "http-proxy",
+        value_name = "URL",
+        help = "Proxy for plain http requests when fetching plugins",
+        env = "HTTP_PROXY",
+        global = true
+    )]
+    http_proxy: Option<String>,
+
+    #[arg(
+        long = "no-proxy",
+        value_name = "HOSTS",
+        help = "Comma separated hosts and domains to reach without a proxy",
+        env = "NO_PROXY",
+        global = true
+    )]
+    no_proxy: Option<String>,
+
+    #[arg(
+        long = "insecure-registry",
+        value_name = "HOST",
+        help = "Host, with an optional port, to fetch plugins from over plain http or without verifying its certificate, can be repeated",
+        env = "HYPER_MCP_INSECURE_REGISTRIES",
+        value_delimiter = ',',
+        global = true
+    )]
+    insecure_registries: Vec<String>,
+}
+
+#[derive(Subcommand, Clone)]
+enum Command {
+    /// Resolve every plugin and write the digests to hyper-mcp.lock next to the config file
+    Lock,
+    /// Manage the cache of downloaded plugins
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Publish a plugin module to an OCI registry
+    Push(push::PushArgs),
+}
+
+#[derive(Subcommand, Clone)]
+enum CacheCommand {
+    /// List cached plugins with their source, digest, size and last use
+    List,
+    /// Remove cached plugins that the config no longer uses
+    Prune {
+        #[arg(
+            long = "older-than",
+            value_name = "DAYS",
+            help = "Also remove plugins that have not been loaded for this many days"
+        )]
+        older_than: Option<u64>,
+
+        #[arg(long = "all", help = "Remove every cached plugin")]
+        all: bool,
+    },
+    /// Download every plugin in the config into the cache without starting a server
+    Prefetch,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    match &cli.command {
+        Some(Command::Lock) => return lockfile::update_lockfile(&cli).await,
+        Some(Command::Cache(CacheCommand::List)) => return cache::list().await,
+        Some(Command::Cache(CacheCommand::Prune { older_than, all })) => {
+            return cache::prune(&cli, *older_than, *all).await;
+        }
+        Some(Command::Cache(CacheCommand::Prefetch)) => return cache::prefetch(&cli).await,
+        Some(Command::Push(args)) => return push::push(&cli, args).await,
+        None => {}
+    }
+
     tracing::info!("Starting hyper-mcp server");
+    reload::spawn_config_watcher(cli.clone());
 
     match cli.transport.as_str() {
         "stdio" => {
@@ -168,3 +333,77 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Flags that decide how OCI plugins are verified, for every subcommand that pulls them
+    const VERIFICATION_FLAGS: [&str; 9] = [
+        "--use-sigstore-tuf-data",
+        "--rekor-pub-keys=rekor.pub",
+        "--fulcio-certs=fulcio.crt",
+        "--cert-issuer=https://token.actions.githubusercontent.com",
+        "--cert-email=me@example.com",
+        "--cert-url=https://github.com/me/plugin",
+        "--cosign-key=cosign.pub",
+        "--insecure-skip-signature",
+        "--locked",
+    ];
+
+    fn parse_after(subcommand: &[&str]) -> Cli {
+        let args = ["hyper-mcp"]
+            .iter()
+            .chain(subcommand)
+            .chain(VERIFICATION_FLAGS.iter());
+        Cli::try_parse_from(args).unwrap_or_else(|e| panic!("{subcommand:?}: {e}"))
+    }
+
+    fn assert_verification_flags(cli: &Cli) {
+        assert_eq!(cli.cosign_key, Some(PathBuf::from("cosign.pub")));
+        assert_eq!(cli.rekor_pub_keys, Some(PathBuf::from("rekor.pub")));
+        assert_eq!(cli.fulcio_certs, Some(PathBuf::from("fulcio.crt")));
+        assert_eq!(
+            cli.cert_issuer.as_deref(),
+            Some("https://token.actions.githubusercontent.com")
+        );
+        assert_eq!(cli.cert_email.as_deref(), Some("me@example.com"));
+        assert_eq!(
+            cli.cert_url.as_deref(),
+            Some("https://github.com/me/plugin")
+        );
+        assert!(cli.use_sigstore_tuf_data);
+        assert!(cli.insecure_skip_signature);
+        assert!(cli.locked);
+    }
+
+    #[test]
+    fn test_lock_accepts_verification_flags() {
+        let cli = parse_after(&["lock"]);
+        assert!(matches!(cli.command, Some(Command::Lock)));
+        assert_verification_flags(&cli);
+    }
+
+    #[test]
+    fn test_cache_prefetch_accepts_verification_flags() {
+        let cli = parse_after(&["cache", "prefetch"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Cache(CacheCommand::Prefetch))
+        ));
+        assert_verification_flags(&cli);
+    }
+
+    #[test]
+    fn test_subcommands_accept_capability_flags() {
+        for subcommand in [&["lock"][..], &["cache", "prefetch"]] {
+            let args = ["hyper-mcp"]
+                .iter()
+                .chain(subcommand)
+                .chain(&["--strict-capabilities", "--record-hosts=hosts.json"]);
+            let cli = Cli::try_parse_from(args).unwrap_or_else(|e| panic!("{subcommand:?}: {e}"));
+            assert!(cli.strict_capabilities);
+            assert_eq!(cli.record_hosts, Some(PathBuf::from("hosts.json")));
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use crate::{
+    config::{PluginName, RuntimeConfig},
+    outbound::host_allowed,
+};
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{ops::RangeInclusive, path::Path};
+use wasmparser::{BinaryReader, ExternalKind, Parser, Payload};
+
+/// Media type of the OCI config blob that holds a plugin's metadata.
+pub const PLUGIN_CONFIG_MEDIA_TYPE: &str = "application/vnd.hyper-mcp.plugin.config.v1+json";
+
+/// Custom wasm section in which a module embeds its metadata as JSON.
+pub const METADATA_SECTION: &str = "hyper-mcp";
+
+/// Plugin ABI versions this hyper-mcp can load.
+pub const SUPPORTED_ABI_VERSIONS: RangeInclusive<u32> = 1..=2;
+
+/// What a plugin says about itself. Fields this hyper-mcp does not know, such as those
+/// added by newer versions, are ignored.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PluginMetadata {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    // Plugin ABI the module implements: 1 exports `call` and `describe`, 2 the
+    // MCP-style functions such as `call_tool` and `list_tools`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abi_version: Option<u32>,
+
+    // The plugin functions the module exports, such as `call` and `describe`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exports: Vec<String>,
+
+    // Hosts the plugin makes HTTP requests to, in `allowed_hosts` syntax
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_hosts: Vec<String>,
+
+    // Paths the plugin reads or writes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_paths: Vec<String>,
+
+    // Keys the plugin reads from its config, set with `env_vars`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_env_vars: Vec<String>,
+}
+
+impl PluginMetadata {
+    /// Reads the metadata a module embeds in its `hyper-mcp` custom section, if any.
+    ///
+    /// A module that cannot be parsed has no metadata; compiling it reports the error.
+    pub fn from_module(wasm: &[u8]) -> Result<Option<Self>> {
+        for payload in Parser::new(0).parse_all(wasm) {
+            let Ok(payload) = payload else { break };
+            if let Payload::CustomSection(reader) = payload
+                && reader.name() == METADATA_SECTION
+            {
+                let metadata = serde_json::from_slice(reader.data())
+                    .with_context(|| format!("Invalid {METADATA_SECTION} custom section"))?;
+                return Ok(Some(metadata));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the module with this metadata in its `hyper-mcp` custom section, in place
+    /// of the one it had, so that hyper-mcp finds it wherever the module is loaded from.
+    pub fn embed(&self, wasm: &[u8]) -> Result<Vec<u8>> {
+        let (header, sections) = wasm
+            .split_at_checked(8)
+            .filter(|(header, _)| header.starts_with(b"\0asm"))
+            .ok_or_else(|| anyhow!("Not a wasm module"))?;
+        let mut module = header.to_vec();
+        let mut reader = BinaryReader::new(sections, 8);
+        while !reader.eof() {
+            let start = reader.current_position();
+            let id = reader.read_u8()?;
+            let size = reader.read_var_u32()? as usize;
+            let content = reader.read_bytes(size)?;
+            let is_metadata = id == 0
+                && BinaryReader::new(content, 0)
+                    .read_string()
+                    .is_ok_and(|name| name == METADATA_SECTION);
+            if !is_metadata {
+                module.extend_from_slice(&sections[start..reader.current_position()]);
+            }
+        }
+
+        let mut content = Vec::new();
+        write_var_u32(&mut content, METADATA_SECTION.len());
+        content.extend_from_slice(METADATA_SECTION.as_bytes());
+        content.extend(serde_json::to_vec(self)?);
+        module.push(0);
+        write_var_u32(&mut module, content.len());
+        module.extend(content);
+        Ok(module)
+    }
+
+    /// Checks that the plugin can run with its runtime config.
+    ///
+    /// An unsupported ABI or a missing `env_vars` key is an error. Hosts and paths the
+    /// config does not allow are only an error when `strict` is set, since the plugin
+    /// may work without them; otherwise they are logged with a config that allows them.
+    pub fn check(
+        &self,
+        plugin_name: &PluginName,
+        runtime_cfg: Option<&RuntimeConfig>,
+        strict: bool,
+    ) -> Result<()> {
+        if let Some(abi_version) = self.abi_version
+            && !SUPPORTED_ABI_VERSIONS.contains(&abi_version)
+        {
+            return Err(anyhow!(
+                "Plugin {plugin_name} needs plugin ABI version {abi_version}, but this hyper-mcp only supports versions {} to {}. Upgrade hyper-mcp to load it.",
+                SUPPORTED_ABI_VERSIONS.start(),
+                SUPPORTED_ABI_VERSIONS.end()
+            ));
+        }
+
+        let missing_env_vars = self.missing_env_vars(runtime_cfg);
+        if !missing_env_vars.is_empty() {
+            let example: String = missing_env_vars
+                .iter()
+                .map(|key| format!("\n          {key}: \"...\""))
+                .collect();
+            return Err(anyhow!(
+                "Plugin {plugin_name} requires env_vars {} in its runtime_config, for example:\n\n  plugins:\n    {plugin_name}:\n      runtime_config:\n        env_vars:{example}",
+                missing_env_vars.join(", ")
+            ));
+        }
+
+        let undeclared_hosts = self.undeclared_hosts(runtime_cfg);
+        if !undeclared_hosts.is_empty() {
+            tracing::info!(
+                "Plugin {plugin_name} is allowed hosts it does not declare: {}",
+                undeclared_hosts.join(", ")
+            );
+        }
+
+        let missing_hosts = self.missing_hosts(runtime_cfg);
+        let missing_paths = self.missing_paths(runtime_cfg);
+        if missing_hosts.is_empty() && missing_paths.is_empty() {
+            return Ok(());
+        }
+        let mut missing = Vec::new();
+        if !missing_hosts.is_empty() {
+            missing.push(format!("hosts {}", missing_hosts.join(", ")));
+        }
+        if !missing_paths.is_empty() {
+            missing.push(format!("paths {}", missing_paths.join(", ")));
+        }
+        let message = format!(
+            "Plugin {plugin_name} needs {} that its runtime_config does not allow. To allow what it declares, use:\n\n{}",
+            missing.join(" and "),
+            self.capabilities_snippet(plugin_name)
+        );
+        if strict {
+            return Err(anyhow!(
+                "{message}\n\nRefusing to load it because of --strict-capabilities."
+            ));
+        }
+        tracing::warn!("{message}");
+        Ok(())
+    }
+
+    /// A `runtime_config` that allows the hosts and paths the plugin declares, ready to
+    /// paste into the config file.
+    pub fn capabilities_snippet(&self, plugin_name: &PluginName) -> String {
+        let mut snippet = format!("  plugins:\n    {plugin_name}:\n      runtime_config:");
+        for (key, values) in [
+            ("allowed_hosts", &self.required_hosts),
+            ("allowed_paths", &self.required_paths),
+        ] {
+            if !values.is_empty() {
+                snippet.push_str(&format!("\n        {key}:"));
+                for value in values {
+                    snippet.push_str(&format!("\n          - {value:?}"));
+                }
+            }
+        }
+        snippet
+    }
+
+    pub fn missing_env_vars(&self, runtime_cfg: Option<&RuntimeConfig>) -> Vec<&str> {
+        let env_vars = runtime_cfg.and_then(|rc| rc.env_vars.as_ref());
+        self.required_env_vars
+            .iter()
+            .filter(|key| env_vars.is_none_or(|env_vars| !env_vars.contains_key(*key)))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Required hosts that no `allowed_hosts` pattern matches. Patterns are globs, as
+    /// extism matches them.
+    pub fn missing_hosts(&self, runtime_cfg: Option<&RuntimeConfig>) -> Vec<&str> {
+        let allowed_hosts = runtime_cfg
+            .and_then(|rc| rc.allowed_hosts.as_deref())
+            .unwrap_or_default();
+        self.required_hosts
+            .iter()
+            .filter(|host| !allowed_hosts.contains(*host) && !host_allowed(allowed_hosts, host))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// `allowed_hosts` entries that allow none of the required hosts, such as `*`.
+    pub fn undeclared_hosts<'a>(&self, runtime_cfg: Option<&'a RuntimeConfig>) -> Vec<&'a str> {
+        runtime_cfg
+            .and_then(|rc| rc.allowed_hosts.as_deref())
+            .unwrap_or_default()
+            .iter()
+            .filter(|allowed| {
+                !self.required_hosts.iter().any(|host| {
+                    *allowed == host || host_allowed(std::slice::from_ref(*allowed), host)
+                })
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Required paths that are not under the guest path of any `allowed_paths` entry.
+    pub fn missing_paths(&self, runtime_cfg: Option<&RuntimeConfig>) -> Vec<&str> {
+        let allowed_paths = runtime_cfg
+            .and_then(|rc| rc.allowed_paths.as_deref())
+            .unwrap_or_default();
+        self.required_paths
+            .iter()
+            .filter(|path| {
+                !allowed_paths
+                    .iter()
+                    .any(|allowed| Path::new(path).starts_with(allowed.guest()))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Appends `value` as an unsigned LEB128 number, as wasm encodes sizes.
+fn write_var_u32(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Returns the names of the functions a wasm module exports.
+pub fn module_exports(wasm: &[u8]) -> Result<Vec<String>> {
+    let mut exports = Vec::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        if let Payload::ExportSection(reader) = payload? {
+            for export in reader {
+                let export = export?;
+                if export.kind == ExternalKind::Func {
+                    exports.push(export.name.to_string());
+                }
+            }
+        }
+    }
+    Ok(exports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_module_exports() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "call") (result i32) i32.const 0)
+                (func (export "describe") (result i32) i32.const 0))"#,
+        )
+        .unwrap();
+        assert_eq!(module_exports(&wasm).unwrap(), vec!["call", "describe"]);
+        assert!(module_exports(b"not wasm").is_err());
+    }
+
+    #[test]
+    fn test_metadata_from_module() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (@custom "hyper-mcp" "{\"version\":\"1.0.0\",\"abi_version\":2,\"required_env_vars\":[\"API_KEY\"]}")
+                (func (export "call_tool") (result i32) i32.const 0))"#,
+        )
+        .unwrap();
+        let metadata = PluginMetadata::from_module(&wasm).unwrap().unwrap();
+        assert_eq!(metadata.version.as_deref(), Some("1.0.0"));
+        assert_eq!(metadata.abi_version, Some(2));
+        assert_eq!(metadata.required_env_vars, vec!["API_KEY"]);
+
+        let wasm = wat::parse_str(
+            r#"(module (@custom "hyper-mcp" "{\"version\":\"1.0.0\",\"homepage\":\"https://example.com\"}"))"#,
+        )
+        .unwrap();
+        let metadata = PluginMetadata::from_module(&wasm).unwrap().unwrap();
+        assert_eq!(metadata.version.as_deref(), Some("1.0.0"));
+
+        let wasm = wat::parse_str("(module)").unwrap();
+        assert_eq!(PluginMetadata::from_module(&wasm).unwrap(), None);
+
+        let wasm = wat::parse_str(r#"(module (@custom "hyper-mcp" "not json"))"#).unwrap();
+        assert!(PluginMetadata::from_module(&wasm).is_err());
+    }
+
+    #[test]
+    fn test_metadata_embed() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (@custom "hyper-mcp" "{\"version\":\"1.0.0\"}")
+                (func (export "call") (result i32) i32.const 0)
+                (@custom "other" "kept"))"#,
+        )
+        .unwrap();
+        let metadata = PluginMetadata {
+            name: "time".to_string(),
+            version: Some("1.1.0".to_string()),
+            description: Some("Tells the time ".repeat(20)),
+            exports: vec!["call".to_string()],
+            ..Default::default()
+        };
+        let embedded = metadata.embed(&wasm).unwrap();
+        assert_eq!(
+            PluginMetadata::from_module(&embedded).unwrap(),
+            Some(metadata)
+        );
+        assert_eq!(module_exports(&embedded).unwrap(), vec!["call"]);
+        let custom_sections: Vec<String> = Parser::new(0)
+            .parse_all(&embedded)
+            .filter_map(|payload| match payload.unwrap() {
+                Payload::CustomSection(reader) => Some(reader.name().to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(custom_sections, vec!["other", "hyper-mcp"]);
+        wasmparser::validate(&embedded).unwrap();
+
+        assert!(PluginMetadata::default().embed(b"not wasm").is_err());
+    }
+
+    #[test]
+    fn test_metadata_check() {
+        let plugin_name = PluginName::try_from("weather").unwrap();
+        let metadata = PluginMetadata {
+            required_env_vars: vec!["API_KEY".to_string(), "UNITS".to_string()],
+            required_hosts: vec!["api.weather.com".to_string()],
+            required_paths: vec!["/tmp/weather/cache".to_string()],
+            ..Default::default()
+        };
+
+        let error = metadata
+            .check(&plugin_name, None, false)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("requires env_vars API_KEY, UNITS in its runtime_config"),
+            "{error}"
+        );
+        assert!(error.contains("    weather:\n"), "{error}");
+
+        let runtime_cfg = RuntimeConfig {
+            env_vars: Some(HashMap::from([
+                ("API_KEY".to_string(), "secret".into()),
+                ("UNITS".to_string(), "metric".into()),
+            ])),
+            allowed_hosts: Some(vec!["*.weather.com".to_string()]),
+            allowed_paths: Some(vec!["/tmp/weather".into()]),
+            ..Default::default()
+        };
+        metadata
+            .check(&plugin_name, Some(&runtime_cfg), true)
+            .unwrap();
+        assert!(metadata.missing_hosts(Some(&runtime_cfg)).is_empty());
+        assert!(metadata.missing_paths(Some(&runtime_cfg)).is_empty());
+
+        let runtime_cfg = RuntimeConfig {
+            allowed_hosts: Some(vec!["weather.com".to_string()]),
+            allowed_paths: Some(vec!["/tmp/other".into()]),
+            ..runtime_cfg
+        };
+        assert_eq!(
+            metadata.missing_hosts(Some(&runtime_cfg)),
+            vec!["api.weather.com"]
+        );
+        assert_eq!(
+            metadata.missing_paths(Some(&runtime_cfg)),
+            vec!["/tmp/weather/cache"]
+        );
+        assert_eq!(
+            metadata.undeclared_hosts(Some(&runtime_cfg)),
+            vec!["weather.com"]
+        );
+        metadata
+            .check(&plugin_name, Some(&runtime_cfg), false)
+            .unwrap();
+        let error = metadata
+            .check(&plugin_name, Some(&runtime_cfg), true)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("needs hosts api.weather.com and paths /tmp/weather/cache"),
+            "{error}"
+        );
+        assert!(
+            error.contains(
+                "    weather:\n      runtime_config:\n        allowed_hosts:\n          - \"api.weather.com\"\n        allowed_paths:\n          - \"/tmp/weather/cache\""
+            ),
+            "{error}"
+        );
+        assert!(error.contains("--strict-capabilities"), "{error}");
+
+        let metadata = PluginMetadata {
+            abi_version: Some(3),
+            ..Default::default()
+        };
+        let error = metadata
+            .check(&plugin_name, None, false)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("ABI version 3"), "{error}");
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{Cli, config::NetworkConfig};
+use anyhow::{Context, Result, anyhow};
+use aws_sdk_s3::config::SharedHttpClient;
+use aws_smithy_http_client::{
+    Builder as S3ClientBuilder, Connector,
+    proxy::ProxyConfig,
+    tls::{Provider, TlsContext, TrustStore, rustls_provider::CryptoMode},
+};
+use sigstore::registry::{Certificate, CertificateEncoding, ClientConfig, ClientProtocol};
+use url::Url;
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+impl NetworkConfig {
+    /// The `network` section of the config with the command line on top: proxies given
+    /// there, or in the usual environment variables, win, while certificates and
+    /// insecure registries are added to the config's.
+    pub fn from_cli(cli: &Cli, config: Option<&NetworkConfig>) -> Self {
+        let config = config.cloned().unwrap_or_default();
+        let non_empty = |value: &Option<String>| value.clone().filter(|value| !value.is_empty());
+        Self {
+            ca_certs: config
+                .ca_certs
+                .into_iter()
+                .chain(cli.ca_certs.iter().cloned())
+                .collect(),
+            https_proxy: non_empty(&cli.https_proxy).or(config.https_proxy),
+            http_proxy: non_empty(&cli.http_proxy).or(config.http_proxy),
+            no_proxy: non_empty(&cli.no_proxy).or(config.no_proxy),
+            insecure_registries: config
+                .insecure_registries
+                .into_iter()
+                .chain(cli.insecure_registries.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// Whether `insecure_registries` lists the host of a URL, alone or with its port.
+    pub fn is_insecure(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host_port = url.port().map(|port| format!("{host}:{port}"));
+        self.insecure_registries
+            .iter()
+            .any(|insecure| insecure == host || Some(insecure) == host_port.as_ref())
+    }
+
+    /// Reads every certificate of the `ca_certs` files, each as its own PEM block.
+    pub fn certificates(&self) -> Result<Vec<Vec<u8>>> {
+        let mut certificates = Vec::new();
+        for path in &self.ca_certs {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read CA certificates {}", path.display()))?;
+            let found = certificates.len();
+            let mut rest = content.as_str();
+            while let Some(start) = rest.find(PEM_BEGIN) {
+                let end = rest[start..]
+                    .find(PEM_END)
+                    .map(|end| start + end + PEM_END.len())
+                    .ok_or_else(|| anyhow!("Unterminated certificate in {}", path.display()))?;
+                certificates.push(format!("{}\n", &rest[start..end]).into_bytes());
+                rest = &rest[end..];
+            }
+            if certificates.len() == found {
+                return Err(anyhow!("No PEM certificate found in {}", path.display()));
+            }
+        }
+        Ok(certificates)
+    }
+
+    /// A builder for HTTP clients that trust `ca_certs` and go through the proxies.
+    pub fn reqwest_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder();
+        for certificate in self.certificates()? {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&certificate)?);
+        }
+        let no_proxy = self
+            .no_proxy
+            .as_deref()
+            .and_then(reqwest::NoProxy::from_string);
+        if let Some(proxy) = &self.https_proxy {
+            builder = builder.proxy(reqwest::Proxy::https(proxy)?.no_proxy(no_proxy.clone()));
+        }
+        if let Some(proxy) = &self.http_proxy {
+            builder = builder.proxy(reqwest::Proxy::http(proxy)?.no_proxy(no_proxy));
+        }
+        Ok(builder)
+    }
+
+    /// The scheme a registry is reached with, plain http for insecure registries as in
+    /// `registry_config`.
+    pub fn registry_scheme(&self, registry: &str) -> &'static str {
+        match self
+            .insecure_registries
+            .iter()
+            .any(|insecure| insecure == registry)
+        {
+            true => "http",
+            false => "https",
+        }
+    }
+
+    /// Client settings for OCI registries and their signatures. Insecure registries are
+    /// reached over plain http.
+    pub fn registry_config(&self) -> Result<ClientConfig> {
+        Ok(ClientConfig {
+            protocol: match self.insecure_registries.is_empty() {
+                true => ClientProtocol::Https,
+                false => ClientProtocol::HttpsExcept(self.insecure_registries.clone()),
+            },
+            extra_root_certificates: self
+                .certificates()?
+                .into_iter()
+                .map(|data| Certificate {
+                    encoding: CertificateEncoding::Pem,
+                    data,
+                })
+                .collect(),
+            https_proxy: self.https_proxy.clone(),
+            http_proxy: self.http_proxy.clone(),
+            no_proxy: self.no_proxy.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// The HTTP client for S3, or `None` when the SDK's own does the job. S3 requests go
+    /// through `https_proxy`, or `http_proxy` when only that one is set.
+    pub fn s3_http_client(&self) -> Result<Option<SharedHttpClient>> {
+        let proxy = self.https_proxy.as_ref().or(self.http_proxy.as_ref());
+        if self.ca_certs.is_empty() && proxy.is_none() {
+            return Ok(None);
+        }
+        let trust_store = self
+            .certificates()?
+            .into_iter()
+            .fold(TrustStore::default(), TrustStore::with_pem_certificate);
+        let tls_context = TlsContext::builder()
+            .with_trust_store(trust_store)
+            .build()?;
+        let proxy_config = match proxy {
+            Some(proxy) => {
+                let proxy_config = ProxyConfig::all(proxy.as_str())?;
+                match &self.no_proxy {
+                    Some(no_proxy) => proxy_config.no_proxy(no_proxy),
+                    None => proxy_config,
+                }
+            }
+            None => ProxyConfig::disabled(),
+        };
+        // The SDK's builder has no proxy setting, so build its connector the same way
+        // with one
+        Ok(Some(S3ClientBuilder::new().build_with_connector_fn(
+            move |settings, components| {
+                let mut builder = Connector::builder();
+                builder.set_connector_settings(settings.cloned());
+                if let Some(components) = components {
+                    builder.set_sleep_impl(components.sleep_impl());
+                }
+                builder
+                    .proxy_config(proxy_config.clone())
+                    .tls_provider(Provider::Rustls(CryptoMode::AwsLc))
+                    .tls_context(tls_context.clone())
+                    .build()
+            },
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_network_config() {
+        let config = NetworkConfig {
+            ca_certs: vec!["tests/fixtures/client.crt".into()],
+            https_proxy: Some("http://config-proxy:3128".to_string()),
+            no_proxy: Some("localhost".to_string()),
+            insecure_registries: vec!["registry.local:5000".to_string()],
+            ..Default::default()
+        };
+        // Every flag is given, even empty, so that HTTPS_PROXY, HTTP_PROXY and NO_PROXY
+        // of the host running the tests are not read instead
+        let cli = Cli::parse_from([
+            "hyper-mcp",
+            "--https-proxy",
+            "http://proxy.corp:3128",
+            "--http-proxy=",
+            "--no-proxy=",
+            "--ca-cert",
+            "tests/fixtures/client.crt",
+            "--insecure-registry",
+            "plugins.internal",
+        ]);
+        let network = NetworkConfig::from_cli(&cli, Some(&config));
+        assert_eq!(
+            network.https_proxy.as_deref(),
+            Some("http://proxy.corp:3128")
+        );
+        assert_eq!(network.no_proxy.as_deref(), Some("localhost"));
+        assert_eq!(network.http_proxy, None);
+        assert_eq!(network.ca_certs.len(), 2);
+
+        let insecure = |url: &str| network.is_insecure(&Url::parse(url).unwrap());
+        assert!(insecure("oci://registry.local:5000/team/time"));
+        assert!(!insecure("oci://registry.local/team/time"));
+        assert!(insecure("https://plugins.internal:8443/time.wasm"));
+        assert!(!insecure("https://plugins.example.com/time.wasm"));
+
+        assert_eq!(network.registry_scheme("registry.local:5000"), "http");
+        assert_eq!(network.registry_scheme("ghcr.io"), "https");
+
+        let registry_config = network.registry_config().unwrap();
+        assert_eq!(registry_config.extra_root_certificates.len(), 2);
+        assert!(matches!(
+            registry_config.protocol,
+            ClientProtocol::HttpsExcept(registries) if registries == ["registry.local:5000", "plugins.internal"]
+        ));
+        assert!(network.reqwest_builder().unwrap().build().is_ok());
+        assert!(network.s3_http_client().unwrap().is_some());
+        assert!(NetworkConfig::default().s3_http_client().unwrap().is_none());
+
+        let network = NetworkConfig {
+            ca_certs: vec!["tests/fixtures/client.key".into()],
+            ..Default::default()
+        };
+        let error = network.registry_config().unwrap_err().to_string();
+        assert!(error.contains("No PEM certificate found"), "{error}");
+    }
+}
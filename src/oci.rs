@@ -1,33 +1,114 @@
-use crate::Cli;
+use crate::{
+    Cli,
+    config::{AuthConfig, NetworkConfig, SignatureConfig},
+    https_auth::{client_identity, find_auth, oauth2_token},
+    metadata::{PLUGIN_CONFIG_MEDIA_TYPE, PluginMetadata},
+};
 use anyhow::anyhow;
 use docker_credential::{CredentialRetrievalError, DockerCredential};
 use flate2::read::GzDecoder;
 use oci_client::Reference;
-use oci_client::{Client, manifest, manifest::OciDescriptor, secrets::RegistryAuth};
+use oci_client::client::{Config, ImageLayer};
+use oci_client::manifest::{OciDescriptor, OciImageManifest};
+use oci_client::{Client, annotations, manifest, secrets::RegistryAuth};
+use regex::Regex;
+use reqwest::header::WWW_AUTHENTICATE;
+use serde::Deserialize;
+use sigstore::cosign::constraint::{Constraint, PrivateKeySigner};
 use sigstore::cosign::verification_constraint::cert_subject_email_verifier::StringVerifier;
 use sigstore::cosign::verification_constraint::{
-    CertSubjectEmailVerifier, CertSubjectUrlVerifier, VerificationConstraintVec,
+    CertSubjectEmailVerifier, CertSubjectUrlVerifier, PublicKeyVerifier, VerificationConstraintVec,
 };
-use sigstore::cosign::{ClientBuilder, CosignCapabilities, verify_constraints};
+use sigstore::cosign::{ClientBuilder, CosignCapabilities, SignatureLayer, verify_constraints};
+use sigstore::crypto::{SigningScheme, signing_key::SigStoreKeyPair};
 use sigstore::errors::SigstoreVerifyConstraintsError;
-use sigstore::registry::{Auth, OciReference};
+use sigstore::registry::{Auth, ClientConfig as SigstoreClientConfig, OciReference};
 use sigstore::trust::sigstore::SigstoreTrustRoot;
 use sigstore::trust::{ManualTrustRoot, TrustRoot};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::Read;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::LazyLock;
 use tar::Archive;
+use url::Url;
+
+/// Layer annotation that marks the layer holding the plugin module, when an image has
+/// several layers. Its value must be `true`.
+pub const PLUGIN_LAYER_ANNOTATION: &str = "io.hyper-mcp.plugin";
 
-fn build_auth(reference: &Reference) -> RegistryAuth {
+/// Returns the credentials for the registry of an image.
+///
+/// The `auths` entry matching `oci://{registry}/{repository}` wins, then one matching
+/// `https://{registry}/{repository}`, and the docker credential store is used when
+/// neither exists. Identity tokens are exchanged for an access token to the repository.
+pub async fn build_auth(
+    reference: &Reference,
+    auths: &Option<HashMap<Url, AuthConfig>>,
+    network: &NetworkConfig,
+) -> RegistryAuth {
     let server = reference
         .resolve_registry()
         .strip_suffix('/')
         .unwrap_or_else(|| reference.resolve_registry());
 
-    // if cli.anonymous {
-    //     return RegistryAuth::Anonymous;
-    // }
+    let configured = [
+        format!("oci://{}/{}", reference.registry(), reference.repository()),
+        format!("https://{server}/{}", reference.repository()),
+    ]
+    .iter()
+    .filter_map(|url| Url::parse(url).ok())
+    .find_map(|url| find_auth(auths, &url));
+    if let Some(auth) = configured {
+        tracing::info!("Using credentials from auths for {server}");
+        let auth = match auth.resolve().await {
+            Ok(auth) => auth,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to read credentials for {server}: {e}. Using anonymous auth"
+                );
+                return RegistryAuth::Anonymous;
+            }
+        };
+        return match auth.as_ref() {
+            AuthConfig::Basic { username, password } => {
+                RegistryAuth::Basic(username.clone(), password.clone())
+            }
+            AuthConfig::Token { token } => RegistryAuth::Bearer(token.clone()),
+            AuthConfig::IdentityToken { identity_token } => {
+                exchange_identity_token(reference, identity_token, network).await
+            }
+            AuthConfig::OAuth2 {
+                token_url,
+                client_id,
+                client_secret,
+                scopes,
+            } => match registry_oauth2_token(
+                auths,
+                network,
+                token_url,
+                client_id,
+                client_secret,
+                scopes,
+            )
+            .await
+            {
+                Ok(token) => RegistryAuth::Bearer(token),
+                Err(e) => {
+                    tracing::warn!("{e}. Using anonymous auth");
+                    RegistryAuth::Anonymous
+                }
+            },
+            AuthConfig::Header { .. } | AuthConfig::Mtls { .. } => {
+                tracing::warn!(
+                    "Registries only accept basic, token, identity_token and oauth2 auths. Using anonymous auth for {server}"
+                );
+                RegistryAuth::Anonymous
+            }
+            AuthConfig::Secret(_) => unreachable!("resolved credentials"),
+        };
+    }
 
     match docker_credential::get_credential(server) {
         Err(CredentialRetrievalError::ConfigNotFound) => RegistryAuth::Anonymous,
@@ -40,15 +121,124 @@ fn build_auth(reference: &Reference) -> RegistryAuth {
             tracing::info!("Found docker credentials");
             RegistryAuth::Basic(username, password)
         }
-        Ok(DockerCredential::IdentityToken(_)) => {
-            tracing::info!(
-                "Cannot use contents of docker config, identity token not supported. Using anonymous auth"
+        Ok(DockerCredential::IdentityToken(identity_token)) => {
+            tracing::info!("Found docker identity token");
+            exchange_identity_token(reference, &identity_token, network).await
+        }
+    }
+}
+
+/// Fetches an OAuth2 access token with the network settings, presenting the client
+/// certificate `auths` holds for the token endpoint, if any.
+async fn registry_oauth2_token(
+    auths: &Option<HashMap<Url, AuthConfig>>,
+    network: &NetworkConfig,
+    token_url: &Url,
+    client_id: &str,
+    client_secret: &str,
+    scopes: &[String],
+) -> Result<String, anyhow::Error> {
+    let mut builder = network.reqwest_builder()?;
+    if let Some(identity) = client_identity(auths, token_url).await? {
+        builder = builder.use_rustls_tls().identity(identity);
+    }
+    oauth2_token(
+        &builder.build()?,
+        token_url,
+        client_id,
+        client_secret,
+        scopes,
+    )
+    .await
+}
+
+/// Trades a registry identity (refresh) token for an access token that can pull the
+/// image, falling back to anonymous access when the exchange fails.
+async fn exchange_identity_token(
+    reference: &Reference,
+    identity_token: &str,
+    network: &NetworkConfig,
+) -> RegistryAuth {
+    match request_access_token(reference, identity_token, network).await {
+        Ok(token) => RegistryAuth::Bearer(token),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to exchange identity token for {}: {e}. Using anonymous auth",
+                reference.resolve_registry()
             );
             RegistryAuth::Anonymous
         }
     }
 }
 
+async fn request_access_token(
+    reference: &Reference,
+    identity_token: &str,
+    network: &NetworkConfig,
+) -> Result<String, anyhow::Error> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        #[serde(alias = "token")]
+        access_token: String,
+    }
+
+    let client = network.reqwest_builder()?.build()?;
+    let registry = reference.resolve_registry();
+    // The registry names its token service in the challenge to an unauthenticated request
+    let response = client
+        .get(format!(
+            "{}://{registry}/v2/",
+            network.registry_scheme(registry)
+        ))
+        .send()
+        .await?;
+    let (realm, service) = response
+        .headers()
+        .get(WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_bearer_challenge)
+        .ok_or_else(|| anyhow!("registry did not send a bearer challenge"))?;
+
+    let scope = format!("repository:{}:pull", reference.repository());
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", identity_token),
+        ("client_id", "hyper-mcp"),
+        ("scope", scope.as_str()),
+    ];
+    if let Some(service) = &service {
+        form.push(("service", service));
+    }
+    let response = client
+        .post(realm)
+        .form(&form)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json::<TokenResponse>().await?.access_token)
+}
+
+/// Returns the realm and service of a `Bearer` `WWW-Authenticate` challenge.
+fn parse_bearer_challenge(header: &str) -> Option<(String, Option<String>)> {
+    static PARAM_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"(\w+)="([^"]*)""#).expect("Failed to compile regex"));
+
+    let params = header
+        .trim_start()
+        .strip_prefix("Bearer ")
+        .or_else(|| header.trim_start().strip_prefix("bearer "))?;
+    let mut realm = None;
+    let mut service = None;
+    for captures in PARAM_REGEX.captures_iter(params) {
+        match &captures[1] {
+            "realm" => realm = Some(captures[2].to_string()),
+            "service" => service = Some(captures[2].to_string()),
+            _ => {}
+        }
+    }
+    Some((realm?, service))
+}
+
 async fn setup_trust_repository(cli: &Cli) -> Result<Box<dyn TrustRoot>, anyhow::Error> {
     if cli.use_sigstore_tuf_data {
         // Use Sigstore TUF data from the official repository
@@ -116,15 +306,122 @@ async fn setup_trust_repository(cli: &Cli) -> Result<Box<dyn TrustRoot>, anyhow:
     Ok(Box::new(data))
 }
 
-async fn verify_image_signature(cli: &Cli, image_reference: &str) -> Result<bool, anyhow::Error> {
+/// The signature policy of plugins without a `signature` block, taken from the CLI flags.
+fn default_signature_policy(cli: &Cli) -> SignatureConfig {
+    SignatureConfig {
+        key: cli.cosign_key.clone(),
+        issuer: cli.cert_issuer.clone(),
+        email: cli.cert_email.clone(),
+        url: cli.cert_url.clone(),
+    }
+}
+
+/// The signature policy an image must satisfy: the plugin's own `signature`, or the
+/// global signature flags when it has none. `None` when verification is disabled.
+pub fn signature_policy(cli: &Cli, signature: Option<&SignatureConfig>) -> Option<SignatureConfig> {
+    match cli.insecure_skip_signature {
+        true => None,
+        false => Some(
+            signature
+                .cloned()
+                .unwrap_or_else(|| default_signature_policy(cli)),
+        ),
+    }
+}
+
+/// Identifies a signature policy together with the key it trusts, so that a cached image
+/// can tell whether it was verified against the policy that is configured now.
+pub fn policy_fingerprint(signature: &SignatureConfig) -> Result<String, anyhow::Error> {
+    let mut content = serde_json::to_vec(signature)?;
+    if let Some(key_path) = &signature.key {
+        content.extend(
+            fs::read(key_path)
+                .map_err(|e| anyhow!("Failed to read cosign key {}: {e}", key_path.display()))?,
+        );
+    }
+    Ok(crate::fetch::sha256_digest(&content))
+}
+
+/// Fails unless the image is signed according to `signature`.
+pub async fn check_image_signature(
+    cli: &Cli,
+    client_config: &SigstoreClientConfig,
+    image_reference: &str,
+    signature: &SignatureConfig,
+    auth: &RegistryAuth,
+) -> Result<(), anyhow::Error> {
+    match verify_image_signature(cli, client_config, image_reference, signature, auth).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(anyhow!(
+            "No valid signatures found for the image {image_reference}"
+        )),
+        Err(e) => Err(anyhow!("Image signature verification failed: {e}")),
+    }
+}
+
+fn verification_constraints(
+    signature: &SignatureConfig,
+) -> Result<VerificationConstraintVec, anyhow::Error> {
+    let mut verification_constraints: VerificationConstraintVec = Vec::new();
+
+    if let Some(key_path) = &signature.key {
+        let key = fs::read(key_path)
+            .map_err(|e| anyhow!("Failed to read cosign key {}: {e}", key_path.display()))?;
+        let verifier = PublicKeyVerifier::try_from(&key)
+            .map_err(|e| anyhow!("Invalid cosign key {}: {e}", key_path.display()))?;
+        verification_constraints.push(Box::new(verifier));
+    }
+
+    if let Some(cert_email) = &signature.email {
+        let issuer = signature
+            .issuer
+            .as_ref()
+            .map(|i| StringVerifier::ExactMatch(i.to_string()));
+
+        verification_constraints.push(Box::new(CertSubjectEmailVerifier {
+            email: StringVerifier::ExactMatch(cert_email.to_string()),
+            issuer,
+        }));
+    }
+
+    if let Some(cert_url) = &signature.url {
+        match signature.issuer.as_ref() {
+            Some(issuer) => {
+                verification_constraints.push(Box::new(CertSubjectUrlVerifier {
+                    url: cert_url.to_string(),
+                    issuer: issuer.to_string(),
+                }));
+            }
+            None => {
+                tracing::warn!(
+                    "A certificate issuer is required to check the certificate URL {cert_url}, ignoring it"
+                );
+            }
+        }
+    }
+
+    Ok(verification_constraints)
+}
+
+async fn verify_image_signature(
+    cli: &Cli,
+    client_config: &SigstoreClientConfig,
+    image_reference: &str,
+    signature: &SignatureConfig,
+    auth: &RegistryAuth,
+) -> Result<bool, anyhow::Error> {
     tracing::info!("Verifying signature for {image_reference}");
 
+    // Build the constraints first, so a bad key fails before contacting the registry
+    let verification_constraints = verification_constraints(signature)?;
+
     // Set up the trust repository based on CLI arguments
     let repo = setup_trust_repository(cli).await?;
-    let auth = &Auth::Anonymous;
+    // Signatures live next to the image, so read them with the same credentials
+    let auth = &Auth::from(auth);
 
     // Create a client builder
-    let client_builder = ClientBuilder::default();
+    let client_builder = ClientBuilder::default().with_oci_client_config(client_config.clone());
 
     // Create client with trust repository
     let client_builder = match client_builder.with_trust_repository(repo.as_ref()) {
@@ -171,35 +468,6 @@ async fn verify_image_signature(cli: &Cli, image_reference: &str) -> Result<bool
         return Ok(false);
     }
 
-    // Build verification constraints based on CLI options
-    let mut verification_constraints: VerificationConstraintVec = Vec::new();
-
-    if let Some(cert_email) = &cli.cert_email {
-        let issuer = cli
-            .cert_issuer
-            .as_ref()
-            .map(|i| StringVerifier::ExactMatch(i.to_string()));
-
-        verification_constraints.push(Box::new(CertSubjectEmailVerifier {
-            email: StringVerifier::ExactMatch(cert_email.to_string()),
-            issuer,
-        }));
-    }
-
-    if let Some(cert_url) = &cli.cert_url {
-        match cli.cert_issuer.as_ref() {
-            Some(issuer) => {
-                verification_constraints.push(Box::new(CertSubjectUrlVerifier {
-                    url: cert_url.to_string(),
-                    issuer: issuer.to_string(),
-                }));
-            }
-            None => {
-                tracing::warn!("'cert-issuer' is required when 'cert-url' is specified");
-            }
-        }
-    }
-
     // Verify the constraints
     match verify_constraints(&signature_layers, verification_constraints.iter()) {
         Ok(()) => {
@@ -217,92 +485,865 @@ async fn verify_image_signature(cli: &Cli, image_reference: &str) -> Result<bool
     }
 }
 
+/// Resolves an image reference to the digest of the manifest it currently points at.
+pub async fn resolve_manifest_digest(
+    client: &Client,
+    image_reference: &str,
+    auth: &RegistryAuth,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let reference = Reference::try_from(image_reference)?;
+    if let Some(digest) = reference.digest() {
+        return Ok(digest.to_string());
+    }
+    Ok(client.fetch_manifest_digest(&reference, auth).await?)
+}
+
+/// Pulls an image and extracts the plugin module from it.
+///
+/// Unless signature verification is disabled, the image must be signed according to
+/// `signature`, or to the global signature flags when the plugin has no policy of its own.
 pub async fn pull_and_extract_oci_image(
     cli: &Cli,
-    client: &Client,
+    client_config: &SigstoreClientConfig,
     image_reference: &str,
+    auth: &RegistryAuth,
+    signature: Option<&SignatureConfig>,
     target_file_path: &str,
     local_output_path: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if Path::new(local_output_path).exists() {
-        tracing::info!(
-            "Plugin {image_reference} already cached at: {local_output_path}. Skipping downloading."
-        );
-        return Ok(());
-    }
-
     tracing::info!("Pulling {image_reference} ...");
 
     let reference = Reference::try_from(image_reference)?;
-    let auth = build_auth(&reference);
 
     // Verify the image signature if it's an OCI image and verification is enabled
-    if !cli.insecure_skip_signature {
-        tracing::info!("Signature verification enabled for {image_reference}");
-        match verify_image_signature(cli, image_reference).await {
-            Ok(verified) => {
-                if !verified {
-                    return Err(format!(
-                        "No valid signatures found for the image {image_reference}"
+    match signature_policy(cli, signature) {
+        Some(signature) => {
+            tracing::info!("Signature verification enabled for {image_reference}");
+            check_image_signature(cli, client_config, image_reference, &signature, auth).await?;
+        }
+        None => tracing::warn!("Signature verification disabled for {image_reference}"),
+    }
+
+    let client = Client::new(client_config.clone().into());
+    let (manifest, _) = client.pull_image_manifest(&reference, auth).await?;
+
+    // An annotated layer is the plugin, otherwise look through every layer in order
+    let annotated: Vec<&OciDescriptor> = manifest
+        .layers
+        .iter()
+        .filter(|layer| {
+            layer
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(PLUGIN_LAYER_ANNOTATION))
+                .is_some_and(|value| value == "true")
+        })
+        .collect();
+    let layers = match annotated.is_empty() {
+        true => manifest.layers.iter().collect(),
+        false => annotated,
+    };
+
+    for layer in layers {
+        let mut buf = Vec::new();
+        client.pull_blob(&reference, layer, &mut buf).await?;
+
+        let format = LayerFormat::detect(&layer.media_type, &buf);
+        tracing::debug!("Layer {} of {image_reference} is {format:?}", layer.digest);
+        if let Some(content) = extract_module(format, buf, target_file_path)? {
+            if let Some(parent) = Path::new(local_output_path).parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Write next to the target and rename so an interrupted pull never leaves a
+            // truncated module in the cache
+            let partial_path = format!("{local_output_path}.partial");
+            fs::write(&partial_path, content)?;
+            fs::rename(&partial_path, local_output_path)?;
+            tracing::info!("Successfully extracted to: {local_output_path}");
+            return Ok(());
+        }
+    }
+
+    Err("Target file not found in any layer".into())
+}
+
+/// How the plugin module is stored in an image layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LayerFormat {
+    Wasm,
+    Tar,
+    TarGzip,
+    TarZstd,
+}
+
+impl LayerFormat {
+    /// Detects the format from the layer's media type, or from its first bytes when the
+    /// media type is not one we know, such as `application/octet-stream`.
+    fn detect(media_type: &str, content: &[u8]) -> Self {
+        match media_type {
+            "application/wasm"
+            | manifest::WASM_LAYER_MEDIA_TYPE
+            | "application/vnd.module.wasm.content.layer.v1+wasm" => Self::Wasm,
+            media_type if media_type.ends_with("+gzip") || media_type.ends_with(".tar.gzip") => {
+                Self::TarGzip
+            }
+            media_type if media_type.ends_with("+zstd") || media_type.ends_with(".tar.zstd") => {
+                Self::TarZstd
+            }
+            manifest::IMAGE_LAYER_MEDIA_TYPE | manifest::IMAGE_DOCKER_LAYER_TAR_MEDIA_TYPE => {
+                Self::Tar
+            }
+            _ if content.starts_with(b"\0asm") => Self::Wasm,
+            _ if content.starts_with(&[0x1f, 0x8b]) => Self::TarGzip,
+            _ if content.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) => Self::TarZstd,
+            _ => Self::Tar,
+        }
+    }
+}
+
+/// Returns the plugin module in a layer: the layer itself for raw wasm, otherwise the
+/// archive entry at `target_file_path` or named `plugin.wasm`.
+fn extract_module(
+    format: LayerFormat,
+    content: Vec<u8>,
+    target_file_path: &str,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let archive: Box<dyn Read + '_> = match format {
+        LayerFormat::Wasm => return Ok(Some(content)),
+        LayerFormat::Tar => Box::new(&content[..]),
+        LayerFormat::TarGzip => Box::new(GzDecoder::new(&content[..])),
+        LayerFormat::TarZstd => Box::new(zstd::Decoder::new(&content[..])?),
+    };
+    let mut archive_extract = Archive::new(archive);
+
+    for entry_result in archive_extract.entries()? {
+        match entry_result {
+            Ok(mut entry) => {
+                if let Ok(path) = entry.path() {
+                    let path_str = path.to_string_lossy();
+                    if path_str.ends_with(target_file_path) || path_str.ends_with("plugin.wasm") {
+                        let mut module = Vec::new();
+                        entry.read_to_end(&mut module)?;
+                        return Ok(Some(module));
+                    }
+                }
+            }
+            Err(e) => tracing::info!("Error during extraction: {e}"),
+        }
+    }
+    Ok(None)
+}
+
+/// Pushes a plugin module to a registry as a single raw wasm layer, with its metadata
+/// embedded in the module, as the image config and as standard OCI annotations. Returns
+/// the manifest digest.
+pub async fn push_plugin_image(
+    client: &Client,
+    image_reference: &str,
+    auth: &RegistryAuth,
+    wasm: Vec<u8>,
+    metadata: &PluginMetadata,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let reference = Reference::try_from(image_reference)?;
+
+    // The loader only reads the module, not the image config
+    let layer = ImageLayer::new(
+        metadata.embed(&wasm)?,
+        manifest::WASM_LAYER_MEDIA_TYPE.to_string(),
+        Some(BTreeMap::from([
+            (PLUGIN_LAYER_ANNOTATION.to_string(), "true".to_string()),
+            (
+                annotations::ORG_OPENCONTAINERS_IMAGE_TITLE.to_string(),
+                "plugin.wasm".to_string(),
+            ),
+        ])),
+    );
+    let config = Config::new(
+        serde_json::to_vec(metadata)?,
+        PLUGIN_CONFIG_MEDIA_TYPE.to_string(),
+        None,
+    );
+
+    let mut annotations = BTreeMap::from([
+        (
+            annotations::ORG_OPENCONTAINERS_IMAGE_TITLE.to_string(),
+            metadata.name.clone(),
+        ),
+        (
+            annotations::ORG_OPENCONTAINERS_IMAGE_CREATED.to_string(),
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        ),
+    ]);
+    if let Some(version) = &metadata.version {
+        annotations.insert(
+            annotations::ORG_OPENCONTAINERS_IMAGE_VERSION.to_string(),
+            version.clone(),
+        );
+    }
+    if let Some(description) = &metadata.description {
+        annotations.insert(
+            annotations::ORG_OPENCONTAINERS_IMAGE_DESCRIPTION.to_string(),
+            description.clone(),
+        );
+    }
+    let layers = [layer];
+    let image_manifest = OciImageManifest::build(&layers, &config, Some(annotations));
+
+    tracing::info!("Pushing {image_reference} ...");
+    client
+        .push(&reference, &layers, config, auth, Some(image_manifest))
+        .await?;
+    Ok(client.fetch_manifest_digest(&reference, auth).await?)
+}
+
+/// Signs an image with a cosign private key and pushes the signature next to it, where
+/// `verify_image_signature` looks for it.
+///
+/// Keys made by `cosign generate-key-pair` are encrypted; `password` decrypts them.
+pub async fn sign_image(
+    client_config: SigstoreClientConfig,
+    image_reference: &str,
+    auth: &RegistryAuth,
+    private_key: &[u8],
+    password: &[u8],
+) -> Result<(), anyhow::Error> {
+    let key_pair = match String::from_utf8_lossy(private_key).contains("ENCRYPTED") {
+        true => SigStoreKeyPair::from_encrypted_pem(private_key, password),
+        false => SigStoreKeyPair::from_pem(private_key),
+    }
+    .map_err(|e| anyhow!("Failed to read the signing key: {e}"))?;
+    let signer = PrivateKeySigner::new_with_signer(
+        key_pair
+            .to_sigstore_signer(&SigningScheme::default())
+            .map_err(|e| anyhow!("Unsupported signing key: {e}"))?,
+    );
+
+    let mut client = ClientBuilder::default()
+        .with_oci_client_config(client_config)
+        .build()
+        .map_err(|e| anyhow!("Failed to build Sigstore client: {e}"))?;
+    let image_ref = OciReference::from_str(image_reference)
+        .map_err(|e| anyhow!("Invalid image reference: {e}"))?;
+    let auth = &Auth::from(auth);
+
+    let (cosign_signature_image, source_image_digest) = client
+        .triangulate(&image_ref, auth)
+        .await
+        .map_err(|e| anyhow!("Failed to resolve the signature image: {e}"))?;
+    let mut signature_layer = SignatureLayer::new_unsigned(&image_ref, &source_image_digest)
+        .map_err(|e| anyhow!("Failed to create the signature: {e}"))?;
+    signer
+        .add_constraint(&mut signature_layer)
+        .map_err(|e| anyhow!("Failed to sign {image_reference}: {e}"))?;
+    client
+        .push_signature(None, auth, &cosign_signature_image, vec![signature_layer])
+        .await
+        .map_err(|e| anyhow!("Failed to push the signature of {image_reference}: {e}"))?;
+    tracing::info!("Pushed signature {cosign_signature_image}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        assert_eq!(
+            parse_bearer_challenge(
+                r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:user/image:pull""#
+            ),
+            Some((
+                "https://ghcr.io/token".to_string(),
+                Some("ghcr.io".to_string())
+            ))
+        );
+        assert_eq!(
+            parse_bearer_challenge(r#"Bearer realm="https://auth.example.com/token""#),
+            Some(("https://auth.example.com/token".to_string(), None))
+        );
+        assert_eq!(parse_bearer_challenge(r#"Basic realm="registry""#), None);
+        assert_eq!(parse_bearer_challenge(r#"Bearer service="ghcr.io""#), None);
+    }
+
+    #[tokio::test]
+    async fn test_request_access_token_insecure_registry() {
+        use axum::{
+            Form, Json, Router,
+            http::{HeaderMap, StatusCode},
+            routing::{get, post},
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let challenge = format!(r#"Bearer realm="http://{addr}/token",service="test""#);
+        let app = Router::new()
+            .route(
+                "/v2/",
+                get(move || async move {
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        HeaderMap::from_iter([(WWW_AUTHENTICATE, challenge.parse().unwrap())]),
                     )
-                    .into());
+                }),
+            )
+            .route(
+                "/token",
+                post(|Form(form): Form<HashMap<String, String>>| async move {
+                    assert_eq!(form["refresh_token"], "identity");
+                    assert_eq!(form["service"], "test");
+                    Json(serde_json::json!({ "token": "access" }))
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let reference = Reference::try_from(format!("{addr}/team/time:v1").as_str()).unwrap();
+        let network = NetworkConfig {
+            insecure_registries: vec![addr.to_string()],
+            ..Default::default()
+        };
+        let token = request_access_token(&reference, "identity", &network)
+            .await
+            .unwrap();
+        assert_eq!(token, "access");
+    }
+
+    #[tokio::test]
+    async fn test_build_auth_prefers_oci_auths() {
+        let mut auths = HashMap::new();
+        auths.insert(
+            Url::parse("https://registry.example.com").unwrap(),
+            AuthConfig::Token {
+                token: "https_token".to_string(),
+            },
+        );
+        auths.insert(
+            Url::parse("oci://registry.example.com/team/").unwrap(),
+            AuthConfig::Basic {
+                username: "team".to_string(),
+                password: "secret".to_string(),
+            },
+        );
+        let auths = Some(auths);
+
+        let reference = Reference::try_from("registry.example.com/team/plugin:v1").unwrap();
+        assert_eq!(
+            build_auth(&reference, &auths, &NetworkConfig::default()).await,
+            RegistryAuth::Basic("team".to_string(), "secret".to_string())
+        );
+        let reference = Reference::try_from("registry.example.com/other/plugin:v1").unwrap();
+        assert_eq!(
+            build_auth(&reference, &auths, &NetworkConfig::default()).await,
+            RegistryAuth::Bearer("https_token".to_string())
+        );
+    }
+
+    fn tar_with(path: &str, content: &[u8]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, content).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    const WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+    #[test]
+    fn test_layer_format_detect() {
+        let tar = tar_with("plugin.wasm", WASM);
+        let zstd = zstd::encode_all(&tar[..], 0).unwrap();
+        let gzip = gzip(&tar);
+
+        assert_eq!(
+            LayerFormat::detect("application/wasm", WASM),
+            LayerFormat::Wasm
+        );
+        assert_eq!(
+            LayerFormat::detect(manifest::WASM_LAYER_MEDIA_TYPE, WASM),
+            LayerFormat::Wasm
+        );
+        assert_eq!(
+            LayerFormat::detect(manifest::IMAGE_LAYER_MEDIA_TYPE, &tar),
+            LayerFormat::Tar
+        );
+        assert_eq!(
+            LayerFormat::detect(manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE, &gzip),
+            LayerFormat::TarGzip
+        );
+        assert_eq!(
+            LayerFormat::detect("application/vnd.oci.image.layer.v1.tar+zstd", &zstd),
+            LayerFormat::TarZstd
+        );
+
+        // Unknown media types fall back to the content
+        for (content, format) in [
+            (WASM, LayerFormat::Wasm),
+            (&tar[..], LayerFormat::Tar),
+            (&gzip[..], LayerFormat::TarGzip),
+            (&zstd[..], LayerFormat::TarZstd),
+        ] {
+            assert_eq!(
+                LayerFormat::detect("application/octet-stream", content),
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_module() {
+        let tar = tar_with("usr/lib/plugin.wasm", WASM);
+        for (format, content) in [
+            (LayerFormat::Wasm, WASM.to_vec()),
+            (LayerFormat::Tar, tar.clone()),
+            (LayerFormat::TarGzip, gzip(&tar)),
+            (LayerFormat::TarZstd, zstd::encode_all(&tar[..], 0).unwrap()),
+        ] {
+            assert_eq!(
+                extract_module(format, content, "/plugin.wasm").unwrap(),
+                Some(WASM.to_vec()),
+                "{format:?}"
+            );
+        }
+
+        let other = tar_with("README.md", b"readme");
+        assert_eq!(
+            extract_module(LayerFormat::Tar, other, "/plugin.wasm").unwrap(),
+            None
+        );
+    }
+
+    // Media type and content by path under /v2/
+    type Blobs = HashMap<String, (String, Vec<u8>)>;
+
+    /// Starts a local registry stand-in holding `blobs`. It serves blobs and manifests
+    /// and accepts chunked and monolithic pushes.
+    async fn serve_registry(blobs: Blobs) -> std::net::SocketAddr {
+        use axum::{
+            body::Bytes,
+            extract::{Path, Query, State},
+            http::{HeaderMap, Method, StatusCode, header},
+            response::IntoResponse,
+        };
+        use std::sync::{Arc, Mutex};
+
+        async fn handle(
+            State(blobs): State<Arc<Mutex<Blobs>>>,
+            method: Method,
+            Path(path): Path<String>,
+            Query(query): Query<HashMap<String, String>>,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> axum::response::Response {
+            let mut blobs = blobs.lock().unwrap();
+            let created = |location: String| {
+                (StatusCode::CREATED, [(header::LOCATION, location)]).into_response()
+            };
+            if let Some(name) = path.strip_suffix("/blobs/uploads/") {
+                // Start an upload session
+                let upload = format!("{name}/blobs/uploads/{}", blobs.len());
+                blobs.insert(upload.clone(), (String::new(), Vec::new()));
+                return (
+                    StatusCode::ACCEPTED,
+                    [(header::LOCATION, format!("/v2/{upload}"))],
+                )
+                    .into_response();
+            }
+            if path.contains("/blobs/uploads/") {
+                let Some((_, content)) = blobs.get_mut(&path) else {
+                    return StatusCode::NOT_FOUND.into_response();
+                };
+                content.extend_from_slice(&body);
+                if method == Method::PATCH {
+                    return (
+                        StatusCode::ACCEPTED,
+                        [(header::LOCATION, format!("/v2/{path}"))],
+                    )
+                        .into_response();
                 }
+                let (_, content) = blobs.remove(&path).unwrap();
+                let digest = &query["digest"];
+                let (name, _) = path.split_once("/blobs/uploads/").unwrap();
+                let key = format!("{name}/blobs/{digest}");
+                blobs.insert(
+                    key.clone(),
+                    ("application/octet-stream".to_string(), content),
+                );
+                return created(format!("/v2/{key}"));
             }
-            Err(e) => {
-                return Err(format!("Image signature verification failed: {e}").into());
+            if method == Method::PUT {
+                let (name, _) = path.split_once("/manifests/").unwrap();
+                let media_type = headers[header::CONTENT_TYPE].to_str().unwrap().to_string();
+                let digest = crate::fetch::sha256_digest(&body);
+                let manifest = (media_type, body.to_vec());
+                blobs.insert(format!("{name}/manifests/{digest}"), manifest.clone());
+                blobs.insert(path.clone(), manifest);
+                return created(format!("/v2/{path}"));
+            }
+            match blobs.get(&path) {
+                Some((media_type, content)) => (
+                    [
+                        (header::CONTENT_TYPE, media_type.clone()),
+                        (
+                            header::HeaderName::from_static("docker-content-digest"),
+                            crate::fetch::sha256_digest(content),
+                        ),
+                    ],
+                    content.clone(),
+                )
+                    .into_response(),
+                None => StatusCode::NOT_FOUND.into_response(),
             }
         }
-    } else {
-        tracing::warn!("Signature verification disabled for {image_reference}");
-    }
-
-    // Accept both OCI and Docker manifest types
-    let manifest = client
-        .pull(
-            &reference,
-            &auth,
-            vec![
-                manifest::IMAGE_MANIFEST_MEDIA_TYPE,
-                manifest::IMAGE_DOCKER_LAYER_GZIP_MEDIA_TYPE,
-                manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE,
-            ],
+
+        let router = axum::Router::new()
+            .route("/v2/", axum::routing::get(|| async { "{}" }))
+            .route("/v2/{*path}", axum::routing::any(handle))
+            .with_state(Arc::new(Mutex::new(blobs)));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    /// Serves `layers` as the image `test/plugin:v1` from a local registry stand-in and
+    /// returns its reference.
+    async fn serve_image(layers: Vec<(&str, Option<&str>, Vec<u8>)>) -> String {
+        let mut blobs = Blobs::new();
+        let mut descriptor = |media_type: &str, content: Vec<u8>| {
+            let digest = crate::fetch::sha256_digest(&content);
+            let descriptor = serde_json::json!({
+                "mediaType": media_type,
+                "digest": digest,
+                "size": content.len(),
+            });
+            blobs.insert(
+                format!("test/plugin/blobs/{digest}"),
+                (media_type.to_string(), content),
+            );
+            descriptor
+        };
+        let config = descriptor("application/vnd.oci.image.config.v1+json", b"{}".to_vec());
+        let layers: Vec<_> = layers
+            .into_iter()
+            .map(|(media_type, annotation, content)| {
+                let mut layer = descriptor(media_type, content);
+                if let Some(value) = annotation {
+                    layer["annotations"] = serde_json::json!({ PLUGIN_LAYER_ANNOTATION: value });
+                }
+                layer
+            })
+            .collect();
+        let manifest = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": manifest::OCI_IMAGE_MEDIA_TYPE,
+            "config": config,
+            "layers": layers,
+        }))
+        .unwrap();
+        let manifest_digest = crate::fetch::sha256_digest(&manifest);
+        for key in [
+            "test/plugin/manifests/v1".to_string(),
+            format!("test/plugin/manifests/{manifest_digest}"),
+        ] {
+            blobs.insert(
+                key,
+                (manifest::OCI_IMAGE_MEDIA_TYPE.to_string(), manifest.clone()),
+            );
+        }
+
+        let addr = serve_registry(blobs).await;
+        format!("{addr}/test/plugin:v1")
+    }
+
+    fn http_client() -> Client {
+        Client::new(oci_client::client::ClientConfig {
+            protocol: oci_client::client::ClientProtocol::Http,
+            ..Default::default()
+        })
+    }
+
+    async fn pull(image_reference: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        use clap::Parser;
+
+        let cli = Cli::parse_from(["hyper-mcp", "--insecure-skip-signature"]);
+        // The test registry only speaks plain http
+        let network = NetworkConfig {
+            insecure_registries: vec![Reference::try_from(image_reference)?.registry().to_string()],
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("plugin.wasm");
+        pull_and_extract_oci_image(
+            &cli,
+            &network.registry_config()?,
+            image_reference,
+            &RegistryAuth::Anonymous,
+            None,
+            "/plugin.wasm",
+            output.to_str().unwrap(),
         )
         .await?;
+        Ok(fs::read(output)?)
+    }
 
-    for layer in manifest.layers.iter() {
-        let mut buf = Vec::new();
-        let desc = OciDescriptor {
-            digest: layer.sha256_digest().clone(),
-            media_type: "application/vnd.docker.image.rootfs.diff.tar.gzip".to_string(),
+    #[tokio::test]
+    async fn test_pull_raw_wasm_layer() {
+        let image = serve_image(vec![("application/wasm", None, WASM.to_vec())]).await;
+        assert_eq!(pull(&image).await.unwrap(), WASM);
+    }
+
+    #[tokio::test]
+    async fn test_pull_zstd_layer() {
+        let layer = zstd::encode_all(&tar_with("plugin.wasm", WASM)[..], 0).unwrap();
+        let image = serve_image(vec![(
+            "application/vnd.oci.image.layer.v1.tar+zstd",
+            None,
+            layer,
+        )])
+        .await;
+        assert_eq!(pull(&image).await.unwrap(), WASM);
+    }
+
+    #[tokio::test]
+    async fn test_pull_annotated_layer() {
+        let other = b"\0asm\x01\0\0\0other".to_vec();
+        let image = serve_image(vec![
+            ("application/wasm", None, other.clone()),
+            (
+                manifest::IMAGE_LAYER_MEDIA_TYPE,
+                Some("true"),
+                tar_with("plugin.wasm", WASM),
+            ),
+            ("application/wasm", Some("false"), other),
+        ])
+        .await;
+        assert_eq!(pull(&image).await.unwrap(), WASM);
+
+        let image = serve_image(vec![(
+            manifest::IMAGE_LAYER_MEDIA_TYPE,
+            None,
+            tar_with("README.md", b"readme"),
+        )])
+        .await;
+        assert!(pull(&image).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_push_plugin_image_round_trip() {
+        let addr = serve_registry(Blobs::new()).await;
+        let image = format!("{addr}/team/time:v1");
+        let metadata = PluginMetadata {
+            name: "time".to_string(),
+            version: Some("1.0.0".to_string()),
+            description: Some("Tells the time".to_string()),
+            exports: vec!["call".to_string(), "describe".to_string()],
             ..Default::default()
         };
-        client.pull_blob(&reference, &desc, &mut buf).await.unwrap();
-
-        let gz_extract = GzDecoder::new(&buf[..]);
-        let mut archive_extract = Archive::new(gz_extract);
-
-        for entry_result in archive_extract.entries()? {
-            match entry_result {
-                Ok(mut entry) => {
-                    if let Ok(path) = entry.path() {
-                        let path_str = path.to_string_lossy();
-                        if path_str.ends_with(target_file_path) || path_str.ends_with("plugin.wasm")
-                        {
-                            if let Some(parent) = Path::new(local_output_path).parent() {
-                                fs::create_dir_all(parent)?;
-                            }
-                            let mut content = Vec::new();
-                            entry.read_to_end(&mut content)?;
-                            fs::write(local_output_path, content)?;
-                            tracing::info!("Successfully extracted to: {local_output_path}");
-                            return Ok(());
-                        }
-                    }
-                }
-                Err(e) => tracing::info!("Error during extraction: {e}"),
+
+        let client = http_client();
+        let digest = push_plugin_image(
+            &client,
+            &image,
+            &RegistryAuth::Anonymous,
+            WASM.to_vec(),
+            &metadata,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            resolve_manifest_digest(&client, &image, &RegistryAuth::Anonymous)
+                .await
+                .unwrap(),
+            digest
+        );
+        // The loader reads the metadata from the module itself
+        let module = pull(&image).await.unwrap();
+        assert_eq!(module, metadata.embed(WASM).unwrap());
+        assert_eq!(
+            PluginMetadata::from_module(&module).unwrap().as_ref(),
+            Some(&metadata)
+        );
+
+        let reference = Reference::try_from(image.as_str()).unwrap();
+        let (manifest, _, config) = client
+            .pull_manifest_and_config(&reference, &RegistryAuth::Anonymous)
+            .await
+            .unwrap();
+        assert_eq!(manifest.config.media_type, PLUGIN_CONFIG_MEDIA_TYPE);
+        assert_eq!(
+            serde_json::from_str::<PluginMetadata>(&config).unwrap(),
+            metadata
+        );
+        let annotations = manifest.annotations.unwrap();
+        assert_eq!(
+            annotations[annotations::ORG_OPENCONTAINERS_IMAGE_VERSION],
+            "1.0.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sign_image_pushes_signature() {
+        let addr = serve_registry(Blobs::new()).await;
+        let image = format!("{addr}/team/time:v1");
+        let client = http_client();
+        let metadata = PluginMetadata {
+            name: "time".to_string(),
+            ..Default::default()
+        };
+        let digest = push_plugin_image(
+            &client,
+            &image,
+            &RegistryAuth::Anonymous,
+            WASM.to_vec(),
+            &metadata,
+        )
+        .await
+        .unwrap();
+
+        let key_pair = sigstore::crypto::signing_key::SigStoreKeyPair::ECDSA(
+            sigstore::crypto::signing_key::ecdsa::ECDSAKeys::new(
+                sigstore::crypto::signing_key::ecdsa::EllipticCurve::P256,
+            )
+            .unwrap(),
+        );
+        let private_key = key_pair
+            .private_key_to_encrypted_pem(b"secret")
+            .unwrap()
+            .to_string();
+        let client_config = SigstoreClientConfig {
+            protocol: sigstore::registry::ClientProtocol::Http,
+            ..Default::default()
+        };
+
+        let pinned = format!("{addr}/team/time@{digest}");
+        let error = sign_image(
+            client_config.clone(),
+            &pinned,
+            &RegistryAuth::Anonymous,
+            private_key.as_bytes(),
+            b"wrong",
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("signing key"), "{error}");
+
+        sign_image(
+            client_config,
+            &pinned,
+            &RegistryAuth::Anonymous,
+            private_key.as_bytes(),
+            b"secret",
+        )
+        .await
+        .unwrap();
+        let signature_tag = format!("{addr}/team/time:{}.sig", digest.replace(':', "-"));
+        let signature = Reference::try_from(signature_tag.as_str()).unwrap();
+        let (manifest, _) = client
+            .pull_image_manifest(&signature, &RegistryAuth::Anonymous)
+            .await
+            .unwrap();
+        assert_eq!(manifest.layers.len(), 1);
+        assert!(
+            manifest.layers[0]
+                .annotations
+                .as_ref()
+                .unwrap()
+                .contains_key("dev.cosignproject.cosign/signature")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_image_signature_with_key() {
+        use clap::Parser;
+        use sigstore::crypto::signing_key::ecdsa::{ECDSAKeys, EllipticCurve};
+
+        let addr = serve_registry(Blobs::new()).await;
+        let image = format!("{addr}/team/time:v1");
+        let metadata = PluginMetadata {
+            name: "time".to_string(),
+            ..Default::default()
+        };
+        let digest = push_plugin_image(
+            &http_client(),
+            &image,
+            &RegistryAuth::Anonymous,
+            WASM.to_vec(),
+            &metadata,
+        )
+        .await
+        .unwrap();
+        let pinned = format!("{addr}/team/time@{digest}");
+        let client_config = SigstoreClientConfig {
+            protocol: sigstore::registry::ClientProtocol::Http,
+            ..Default::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut public_keys = Vec::new();
+        for name in ["signer", "other"] {
+            let key_pair = SigStoreKeyPair::ECDSA(ECDSAKeys::new(EllipticCurve::P256).unwrap());
+            if name == "signer" {
+                let private_key = key_pair.private_key_to_pem().unwrap().to_string();
+                sign_image(
+                    client_config.clone(),
+                    &pinned,
+                    &RegistryAuth::Anonymous,
+                    private_key.as_bytes(),
+                    b"",
+                )
+                .await
+                .unwrap();
             }
+            let path = dir.path().join(format!("{name}.pub"));
+            fs::write(&path, key_pair.public_key_to_pem().unwrap()).unwrap();
+            public_keys.push(path);
         }
-    }
 
-    Err("Target file not found in any layer".into())
+        let mut cli = Cli::parse_from(["hyper-mcp"]);
+        // Key verification does not need the public Sigstore trust root
+        cli.use_sigstore_tuf_data = false;
+        let verify = |key: &Path| {
+            let signature = SignatureConfig {
+                key: Some(key.to_path_buf()),
+                ..Default::default()
+            };
+            let cli = &cli;
+            let client_config = &client_config;
+            let pinned = &pinned;
+            async move {
+                check_image_signature(
+                    cli,
+                    client_config,
+                    pinned,
+                    &signature,
+                    &RegistryAuth::Anonymous,
+                )
+                .await
+            }
+        };
+        verify(&public_keys[0]).await.unwrap();
+        let error = verify(&public_keys[1]).await.unwrap_err();
+        assert!(error.to_string().contains("No valid signatures"), "{error}");
+        assert_ne!(
+            policy_fingerprint(&SignatureConfig {
+                key: Some(public_keys[0].clone()),
+                ..Default::default()
+            })
+            .unwrap(),
+            policy_fingerprint(&SignatureConfig {
+                key: Some(public_keys[1].clone()),
+                ..Default::default()
+            })
+            .unwrap()
+        );
+    }
 }
@@ -0,0 +1,516 @@
+use crate::{
+    config::{AuthConfig, PluginName},
+    https_auth::{Authenticator, find_auth, mtls_identity},
+    plugin::current_session,
+    service::session_config,
+};
+use anyhow::{Result, anyhow};
+use dashmap::DashMap;
+use extism::{CurrentPlugin, EXTISM_ENV_MODULE, Function, PTR, UserData, Val, ValType};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+    time::SystemTime,
+};
+use tokio::runtime::Handle;
+use url::Url;
+
+// Extism's own limit for HTTP response bodies when the manifest sets none
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 50 * 1024 * 1024;
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+// Clients presenting the certificate of an `mtls` auth, by certificate and key file,
+// with the modification times of both when the client was built
+static MTLS_CLIENTS: LazyLock<DashMap<(PathBuf, PathBuf), (FileTimes, reqwest::Client)>> =
+    LazyLock::new(DashMap::new);
+
+type FileTimes = (Option<SystemTime>, Option<SystemTime>);
+
+static HOST_RECORDERS: LazyLock<DashMap<PathBuf, Arc<HostRecorder>>> = LazyLock::new(DashMap::new);
+
+/// Whether a host is allowed by `allowed_hosts`, whose entries are globs as in extism.
+pub fn host_allowed(allowed_hosts: &[String], host: &str) -> bool {
+    allowed_hosts
+        .iter()
+        .any(|allowed| match glob::Pattern::new(allowed) {
+            Ok(pattern) => pattern.matches(host),
+            Err(_) => allowed == host,
+        })
+}
+
+/// Records the hosts plugins try to reach in a JSON file, by plugin, and whether
+/// `allowed_hosts` let them.
+#[derive(Debug)]
+pub struct HostRecorder {
+    path: PathBuf,
+    hosts: Mutex<RecordedHosts>,
+}
+
+type RecordedHosts = BTreeMap<PluginName, BTreeMap<String, HostAccess>>;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostAccess {
+    Allowed,
+    Denied,
+}
+
+impl HostRecorder {
+    /// Returns the recorder writing to `path`, adding to what the file already holds.
+    pub fn open(path: &Path) -> Result<Arc<Self>> {
+        if let Some(recorder) = HOST_RECORDERS.get(path) {
+            return Ok(Arc::clone(&recorder));
+        }
+        let hosts = match std::fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content)
+                .map_err(|e| anyhow!("Failed to read recorded hosts {}: {e}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RecordedHosts::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let recorder = Arc::new(Self {
+            path: path.to_path_buf(),
+            hosts: Mutex::new(hosts),
+        });
+        HOST_RECORDERS.insert(path.to_path_buf(), Arc::clone(&recorder));
+        Ok(recorder)
+    }
+
+    fn record(&self, plugin_name: &PluginName, host: &str, access: HostAccess) {
+        let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        let previous = hosts
+            .entry(plugin_name.clone())
+            .or_default()
+            .insert(host.to_string(), access);
+        if previous == Some(access) {
+            return;
+        }
+        tracing::info!("Plugin {plugin_name} tried to reach {host} ({access:?})");
+        let result = serde_json::to_vec_pretty(&*hosts)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(std::fs::write(&self.path, content)?));
+        if let Err(e) = result {
+            tracing::warn!(
+                "Failed to write recorded hosts to {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// What a plugin asks `http_request` for, as extism-pdk serializes it.
+#[derive(Deserialize)]
+struct HttpRequest {
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    method: Option<String>,
+}
+
+#[derive(Debug)]
+struct HttpContext {
+    plugin_name: PluginName,
+    recorder: Option<Arc<HostRecorder>>,
+    inject_auths: bool,
+}
+
+// Plugin var holding the status of the instance's last response, so it goes away with
+// the instance
+const HTTP_STATUS_VAR: &str = "hyper-mcp:http_status";
+
+/// Host functions that take over extism's `http_request` and `http_status_code`, so
+/// hyper-mcp makes the HTTP requests of plugins itself.
+///
+/// Requests are checked against the manifest's `allowed_hosts` exactly as extism does,
+/// and recorded by `recorder` when there is one. With `inject_auths`, the `auths` of
+/// the calling session are added to them, so the plugin never holds the credentials.
+pub fn http_functions(
+    plugin_name: &PluginName,
+    recorder: Option<Arc<HostRecorder>>,
+    inject_auths: bool,
+) -> [Function; 2] {
+    let context = UserData::new(HttpContext {
+        plugin_name: plugin_name.clone(),
+        recorder,
+        inject_auths,
+    });
+    [
+        Function::new(
+            "http_request",
+            [PTR, PTR],
+            [PTR],
+            context.clone(),
+            http_request,
+        )
+        .with_namespace(EXTISM_ENV_MODULE),
+        Function::new(
+            "http_status_code",
+            [],
+            [ValType::I32],
+            context,
+            http_status_code,
+        )
+        .with_namespace(EXTISM_ENV_MODULE),
+    ]
+}
+
+fn http_request(
+    plugin: &mut CurrentPlugin,
+    inputs: &[Val],
+    outputs: &mut [Val],
+    context: UserData<HttpContext>,
+) -> Result<()> {
+    let context = context.get()?;
+    let request_offset = inputs[0].unwrap_i64() as u64;
+    let body_offset = inputs[1].unwrap_i64() as u64;
+
+    let handle = plugin
+        .memory_handle(request_offset)
+        .ok_or_else(|| anyhow!("invalid handle offset for http request: {request_offset}"))?;
+    let request: HttpRequest = serde_json::from_slice(plugin.memory_bytes(handle)?)?;
+    plugin.memory_free(handle)?;
+    let body = match plugin.memory_handle(body_offset) {
+        Some(handle) if body_offset > 0 => {
+            let body = plugin.memory_bytes(handle)?.to_vec();
+            plugin.memory_free(handle)?;
+            Some(body)
+        }
+        _ => None,
+    };
+
+    let url = Url::parse(&request.url).map_err(|e| anyhow!("Invalid URL: {e:?}"))?;
+    let host = url.host_str().unwrap_or_default().to_string();
+    let allowed = plugin
+        .manifest()
+        .allowed_hosts
+        .as_deref()
+        .is_some_and(|allowed_hosts| host_allowed(allowed_hosts, &host));
+    plugin.vars_mut().remove(HTTP_STATUS_VAR);
+    let (plugin_name, recorder, inject_auths) = {
+        let context = context.lock().unwrap_or_else(PoisonError::into_inner);
+        (
+            context.plugin_name.clone(),
+            context.recorder.clone(),
+            context.inject_auths,
+        )
+    };
+    if let Some(recorder) = recorder {
+        let access = match allowed {
+            true => HostAccess::Allowed,
+            false => HostAccess::Denied,
+        };
+        recorder.record(&plugin_name, &host, access);
+    }
+    if !allowed {
+        return Err(anyhow!("HTTP request to {} is not allowed", request.url));
+    }
+
+    let method = request.method.as_deref().unwrap_or("GET").to_uppercase();
+    let mut builder = HTTP_CLIENT.request(reqwest::Method::from_bytes(method.as_bytes())?, url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = body {
+        builder = builder.body(body);
+    }
+    if let Some(timeout) = plugin.time_remaining() {
+        builder = builder.timeout(timeout);
+    }
+    let max_bytes = plugin
+        .manifest()
+        .memory
+        .max_http_response_bytes
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES);
+
+    let request = builder.build()?;
+    let config = match inject_auths {
+        true => current_session().and_then(session_config),
+        false => None,
+    };
+
+    let (status, content) = Handle::current().block_on(async {
+        let (client, request) = match &config {
+            Some(config) => add_auths(request, &config.auths).await?,
+            None => (HTTP_CLIENT.clone(), request),
+        };
+        let mut response = client
+            .execute(request)
+            .await
+            .map_err(|e| match e.is_timeout() {
+                true => anyhow!("timeout"),
+                false => anyhow!(e),
+            })?;
+        let status = response.status().as_u16();
+        let mut content = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            content.extend_from_slice(&chunk);
+            if content.len() as u64 > max_bytes {
+                return Err(anyhow!(
+                    "HTTP response exceeds the configured maximum number of bytes: {max_bytes}"
+                ));
+            }
+        }
+        Ok((status, content))
+    })?;
+
+    plugin
+        .vars_mut()
+        .insert(HTTP_STATUS_VAR.to_string(), status.to_le_bytes().to_vec());
+    let memory = plugin.memory_new(&content)?;
+    outputs[0] = Val::I64(memory.offset() as i64);
+    Ok(())
+}
+
+/// Adds the credentials `auths` holds for the request's URL, replacing any header of
+/// the same name the plugin set, and returns the client to send it with.
+async fn add_auths(
+    mut request: reqwest::Request,
+    auths: &Option<HashMap<Url, AuthConfig>>,
+) -> Result<(reqwest::Client, reqwest::Request)> {
+    let url = request.url().clone();
+    let credentials = HTTP_CLIENT
+        .get(url.clone())
+        .add_auth(auths, &url)
+        .await
+        .build()?;
+    for (name, value) in credentials.headers() {
+        request.headers_mut().insert(name, value.clone());
+    }
+    let auth = match find_auth(auths, &url) {
+        Some(auth) => Some(auth.resolve().await?),
+        None => None,
+    };
+    let client = match auth.as_deref() {
+        Some(AuthConfig::Mtls { cert, key }) => mtls_client(cert, key)?,
+        _ => HTTP_CLIENT.clone(),
+    };
+    Ok((client, request))
+}
+
+/// The client presenting a client certificate, built again only when the certificate
+/// or key file has changed since, like after a renewal.
+fn mtls_client(cert: &Path, key: &Path) -> Result<reqwest::Client> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let files = (cert.to_path_buf(), key.to_path_buf());
+    let times = (modified(cert), modified(key));
+    if let Some(cached) = MTLS_CLIENTS.get(&files)
+        && cached.0 == times
+    {
+        return Ok(cached.1.clone());
+    }
+    let client = reqwest::Client::builder()
+        .use_rustls_tls()
+        .identity(mtls_identity(cert, key)?)
+        .build()?;
+    MTLS_CLIENTS.insert(files, (times, client.clone()));
+    Ok(client)
+}
+
+fn http_status_code(
+    plugin: &mut CurrentPlugin,
+    _inputs: &[Val],
+    outputs: &mut [Val],
+    _context: UserData<HttpContext>,
+) -> Result<()> {
+    let status = plugin
+        .vars()
+        .get(HTTP_STATUS_VAR)
+        .and_then(|bytes| <[u8; 2]>::try_from(bytes.as_slice()).ok())
+        .map(u16::from_le_bytes)
+        .unwrap_or_default();
+    outputs[0] = Val::I32(status as i32);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_allowed() {
+        let allowed_hosts = vec!["api.example.com".to_string(), "*.github.com".to_string()];
+        assert!(host_allowed(&allowed_hosts, "api.example.com"));
+        assert!(host_allowed(&allowed_hosts, "raw.github.com"));
+        assert!(!host_allowed(&allowed_hosts, "example.com"));
+        assert!(host_allowed(&["*".to_string()], "anything.net"));
+        assert!(!host_allowed(&[], "example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_add_auths() {
+        let auths = Some(HashMap::from([
+            (
+                Url::parse("https://api.example.com/v1").unwrap(),
+                AuthConfig::Header {
+                    name: "X-Api-Key".to_string(),
+                    value: "s3cret".into(),
+                },
+            ),
+            (
+                Url::parse("https://gitlab.example.com").unwrap(),
+                AuthConfig::Token {
+                    token: "glpat".into(),
+                },
+            ),
+        ]));
+        let request = |url: &str| {
+            HTTP_CLIENT
+                .get(url)
+                .header("X-Api-Key", "from-plugin")
+                .header("Accept", "application/json")
+                .build()
+                .unwrap()
+        };
+
+        let (_, authed) = add_auths(request("https://api.example.com/v1/search"), &auths)
+            .await
+            .unwrap();
+        let keys: Vec<_> = authed.headers().get_all("X-Api-Key").iter().collect();
+        assert_eq!(keys, vec!["s3cret"]);
+        assert_eq!(authed.headers()["Accept"], "application/json");
+
+        let (_, authed) = add_auths(request("https://gitlab.example.com/api/v4"), &auths)
+            .await
+            .unwrap();
+        assert_eq!(authed.headers()["Authorization"], "Bearer glpat");
+        assert_eq!(authed.headers()["X-Api-Key"], "from-plugin");
+
+        let (_, authed) = add_auths(request("https://api.example.com/v2"), &auths)
+            .await
+            .unwrap();
+        assert_eq!(authed.headers()["X-Api-Key"], "from-plugin");
+        assert!(!authed.headers().contains_key("Authorization"));
+    }
+
+    #[tokio::test]
+    async fn test_add_auths_reuses_mtls_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("client.crt");
+        let key = dir.path().join("client.key");
+        std::fs::copy("tests/fixtures/client.crt", &cert).unwrap();
+        std::fs::copy("tests/fixtures/client.key", &key).unwrap();
+        let auths = Some(HashMap::from([(
+            Url::parse("https://internal.example.com").unwrap(),
+            AuthConfig::Mtls {
+                cert: cert.clone(),
+                key: key.clone(),
+            },
+        )]));
+        let files = (cert.clone(), key.clone());
+        let built_for = || MTLS_CLIENTS.get(&files).map(|cached| cached.0);
+
+        for _ in 0..2 {
+            let request = HTTP_CLIENT
+                .get("https://internal.example.com/api")
+                .build()
+                .unwrap();
+            add_auths(request, &auths).await.unwrap();
+        }
+        let first = built_for().unwrap();
+
+        // A renewed certificate gets a new client
+        std::fs::File::options()
+            .write(true)
+            .open(&cert)
+            .unwrap()
+            .set_modified(SystemTime::now() + std::time::Duration::from_secs(3600))
+            .unwrap();
+        let request = HTTP_CLIENT
+            .get("https://internal.example.com/api")
+            .build()
+            .unwrap();
+        add_auths(request, &auths).await.unwrap();
+        assert_ne!(built_for().unwrap(), first);
+    }
+
+    /// A module whose `fetch` export sends `request` through `http_request` and outputs
+    /// the response body.
+    fn fetching_module(request: &str) -> Vec<u8> {
+        wat::parse_str(format!(
+            r#"(module
+                (import "extism:host/env" "alloc" (func $alloc (param i64) (result i64)))
+                (import "extism:host/env" "store_u8" (func $store_u8 (param i64 i32)))
+                (import "extism:host/env" "length" (func $length (param i64) (result i64)))
+                (import "extism:host/env" "output_set" (func $output_set (param i64 i64)))
+                (import "extism:host/env" "http_request"
+                    (func $http_request (param i64 i64) (result i64)))
+                (memory 1)
+                (data (i32.const 0) "{data}")
+                (func (export "fetch") (result i32)
+                    (local $request i64) (local $i i32) (local $response i64)
+                    (local.set $request (call $alloc (i64.const {len})))
+                    (block $done
+                        (loop $copy
+                            (br_if $done (i32.ge_u (local.get $i) (i32.const {len})))
+                            (call $store_u8
+                                (i64.add (local.get $request) (i64.extend_i32_u (local.get $i)))
+                                (i32.load8_u (local.get $i)))
+                            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                            (br $copy)))
+                    (local.set $response (call $http_request (local.get $request) (i64.const 0)))
+                    (call $output_set (local.get $response) (call $length (local.get $response)))
+                    (i32.const 0)))"#,
+            data = request.replace('"', "\\\""),
+            len = request.len(),
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_http_request_from_module() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route("/hello", axum::routing::get(|| async { "hi" }));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let recorder = HostRecorder::open(&temp_dir.path().join("hosts.json")).unwrap();
+        let plugin_name = PluginName::try_from("fetcher").unwrap();
+        let wasm = fetching_module(&format!(
+            r#"{{"url":"http://{addr}/hello","headers":{{}}}}"#
+        ));
+        let call = |allowed_host: &'static str| {
+            let manifest = extism::Manifest::new([extism::Wasm::data(wasm.clone())])
+                .with_allowed_host(allowed_host);
+            let functions = http_functions(&plugin_name, Some(Arc::clone(&recorder)), false);
+            // Host functions block on the runtime, as they do in plugin calls
+            tokio::task::block_in_place(|| {
+                extism::PluginBuilder::new(&manifest)
+                    .with_functions(functions)
+                    .build()?
+                    .call::<&str, String>("fetch", "")
+            })
+        };
+
+        let recorded = || recorder.hosts.lock().unwrap()[&plugin_name]["127.0.0.1"];
+        assert_eq!(call("127.0.0.1").unwrap(), "hi");
+        assert_eq!(recorded(), HostAccess::Allowed);
+        let error = format!("{:?}", call("example.com").unwrap_err());
+        assert!(error.contains("is not allowed"), "{error}");
+        assert_eq!(recorded(), HostAccess::Denied);
+    }
+
+    #[test]
+    fn test_host_recorder() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("hosts.json");
+        std::fs::write(&path, r#"{"time": {"old.example.com": "allowed"}}"#).unwrap();
+        let plugin_name = PluginName::try_from("time").unwrap();
+
+        let recorder = HostRecorder::open(&path).unwrap();
+        recorder.record(&plugin_name, "api.example.com", HostAccess::Denied);
+        recorder.record(&plugin_name, "api.example.com", HostAccess::Allowed);
+        assert!(Arc::ptr_eq(&recorder, &HostRecorder::open(&path).unwrap()));
+
+        let recorded: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(
+            recorded,
+            serde_json::json!({
+                "time": {"api.example.com": "allowed", "old.example.com": "allowed"}
+            })
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::config::PluginName;
+use crate::{config::PluginName, metadata::PluginMetadata};
 use async_trait::async_trait;
 use rmcp::{
     ErrorData as McpError,
@@ -8,13 +8,387 @@ use rmcp::{
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::{Value, json};
 use std::{
+    cell::Cell,
+    collections::VecDeque,
     fmt::Debug,
-    ops::Deref,
-    sync::{Arc, Mutex},
+    ops::{Deref, DerefMut},
+    sync::{
+        Arc, Mutex, PoisonError, Weak,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
 };
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 
-type PluginHandle = Arc<Mutex<extism::Plugin>>;
+thread_local! {
+    static CURRENT_SESSION: Cell<Option<Uuid>> = const { Cell::new(None) };
+}
+
+/// Returns the id of the session whose plugin call is executing on this thread.
+///
+/// Host functions use this to find the session (peer, logging level, subscriptions)
+/// that triggered them, since compiled plugins are shared between sessions.
+pub fn current_session() -> Option<Uuid> {
+    CURRENT_SESSION.get()
+}
+
+struct SessionGuard(Option<Uuid>);
+
+impl SessionGuard {
+    fn enter(session_id: Uuid) -> Self {
+        Self(CURRENT_SESSION.replace(Some(session_id)))
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        CURRENT_SESSION.set(self.0);
+    }
+}
+
+/// A plugin module compiled once per process.
+///
+/// Instantiating a compiled plugin only links the host functions and creates a new
+/// store, so sessions can get their own instances without paying compile cost.
+pub struct CompiledPlugin {
+    compiled: extism::CompiledPlugin,
+    // Never called; only answers `function_exists` without creating an instance.
+    probe: Mutex<extism::Plugin>,
+}
+
+// SAFETY: `extism::CompiledPlugin` is only `!Send`/`!Sync` because the host function
+// user data is type-erased. It is never mutated after compilation and our user data
+// is `Send`, mirroring the `Send`/`Sync` impls extism provides for `extism::Plugin`.
+unsafe impl Send for CompiledPlugin {}
+unsafe impl Sync for CompiledPlugin {}
+
+impl Debug for CompiledPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CompiledPlugin")
+    }
+}
+
+impl CompiledPlugin {
+    pub fn new(builder: extism::PluginBuilder) -> Result<Self, extism::Error> {
+        let compiled = extism::CompiledPlugin::new(builder)?;
+        let probe = extism::Plugin::new_from_compiled(&compiled)?;
+        Ok(Self {
+            compiled,
+            probe: Mutex::new(probe),
+        })
+    }
+
+    pub fn function_exists(&self, name: &str) -> bool {
+        self.probe
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .function_exists(name)
+    }
+
+    pub fn instantiate(&self) -> Result<extism::Plugin, extism::Error> {
+        extism::Plugin::new_from_compiled(&self.compiled)
+    }
+}
+
+/// Number of crashes within [`CRASH_WINDOW`] after which a plugin is disabled.
+pub const MAX_CRASHES: usize = 5;
+/// Window over which crashes are counted by the circuit breaker.
+pub const CRASH_WINDOW: Duration = Duration::from_secs(60);
+
+type DisabledHook = Box<dyn Fn() + Send + Sync>;
+
+/// Restarts and recent crashes of a plugin, counted across every pool that shares it,
+/// and the circuit breaker they trip.
+///
+/// Sessions of the same compiled plugin share one, so a crash-looping plugin stays
+/// disabled for new sessions instead of getting [`MAX_CRASHES`] more crashes each.
+#[derive(Default)]
+pub struct CrashTracker {
+    restarts: AtomicU64,
+    crashes: Mutex<VecDeque<Instant>>,
+    disabled: AtomicBool,
+    // Hooks of the pools sharing the tracker, gone once their pool is dropped
+    on_disabled: Mutex<Vec<Weak<DisabledHook>>>,
+}
+
+impl Debug for CrashTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CrashTracker")
+            .field("restarts", &self.restarts())
+            .field("disabled", &self.is_disabled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CrashTracker {
+    /// Number of instances discarded and rebuilt since the tracker was created.
+    pub fn restarts(&self) -> u64 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    fn add_hook(&self, hook: &Arc<DisabledHook>) {
+        let mut hooks = self
+            .on_disabled
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        hooks.retain(|hook| hook.strong_count() > 0);
+        hooks.push(Arc::downgrade(hook));
+    }
+
+    /// Counts a crash, and disables the plugin once there were too many recently.
+    fn record_crash(&self) {
+        let now = Instant::now();
+        let crashes = {
+            let mut crashes = self.crashes.lock().unwrap_or_else(PoisonError::into_inner);
+            crashes.push_back(now);
+            while crashes
+                .front()
+                .is_some_and(|crash| now.duration_since(*crash) > CRASH_WINDOW)
+            {
+                crashes.pop_front();
+            }
+            crashes.len()
+        };
+        if crashes >= MAX_CRASHES && !self.disabled.swap(true, Ordering::Relaxed) {
+            tracing::error!(
+                "Disabling plugin after {crashes} crashes within {}s ({} restarts)",
+                CRASH_WINDOW.as_secs(),
+                self.restarts()
+            );
+            let hooks: Vec<_> = self
+                .on_disabled
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .drain(..)
+                .filter_map(|hook| hook.upgrade())
+                .collect();
+            for hook in hooks {
+                hook();
+            }
+        }
+    }
+}
+
+/// A session's instances of one compiled plugin.
+///
+/// A call checks an instance out for its duration, so up to `max_instances` calls to
+/// the same plugin run concurrently, in every pool sharing its permits. Instances are created on demand and returned to
+/// the pool afterwards, unless they were marked unhealthy. Pools are per session so
+/// plugin state is never shared between clients.
+///
+/// Unhealthy instances are replaced from the compiled plugin on the next checkout and
+/// counted as restarts. A plugin that crashes [`MAX_CRASHES`] times within
+/// [`CRASH_WINDOW`] is disabled, in every pool sharing its [`CrashTracker`].
+pub struct PluginPool {
+    compiled: Arc<CompiledPlugin>,
+    idle: Mutex<Vec<extism::Plugin>>,
+    permits: Arc<Semaphore>,
+    crashes: Arc<CrashTracker>,
+    on_disabled: Option<Arc<DisabledHook>>,
+}
+
+impl Debug for PluginPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginPool")
+            .field("compiled", &self.compiled)
+            .field("restarts", &self.restarts())
+            .field("disabled", &self.is_disabled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl PluginPool {
+    pub fn new(compiled: Arc<CompiledPlugin>, max_instances: usize) -> Self {
+        Self {
+            compiled,
+            idle: Mutex::new(Vec::new()),
+            permits: Arc::new(Semaphore::new(max_instances)),
+            crashes: Arc::default(),
+            on_disabled: None,
+        }
+    }
+
+    /// Counts crashes and restarts with `crashes`, shared with other pools, instead of
+    /// on this pool alone.
+    pub fn with_crash_tracker(mut self, crashes: Arc<CrashTracker>) -> Self {
+        if let Some(hook) = &self.on_disabled {
+            crashes.add_hook(hook);
+        }
+        self.crashes = crashes;
+        self
+    }
+
+    /// Takes a slot from `permits`, shared with other pools, for each call instead of
+    /// from this pool's own `max_instances`.
+    pub fn with_permits(mut self, permits: Arc<Semaphore>) -> Self {
+        self.permits = permits;
+        self
+    }
+
+    /// Sets a hook that runs once when the circuit breaker disables the plugin.
+    pub fn on_disabled(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        let hook: Arc<DisabledHook> = Arc::new(Box::new(hook));
+        self.crashes.add_hook(&hook);
+        self.on_disabled = Some(hook);
+        self
+    }
+
+    /// Waits for a free slot and hands out an idle instance, or a new one if none is idle.
+    pub async fn checkout(self: &Arc<Self>) -> Result<PooledInstance, extism::Error> {
+        if self.is_disabled() {
+            // Another session may have tripped the breaker, leaving these behind
+            self.idle
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clear();
+            return Err(extism::Error::msg(
+                "plugin is disabled after repeated crashes",
+            ));
+        }
+        let permit = Arc::clone(&self.permits).acquire_owned().await?;
+        let plugin = match self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+        {
+            Some(plugin) => plugin,
+            None => self.compiled.instantiate()?,
+        };
+        Ok(PooledInstance {
+            healthy: true,
+            plugin: Some(plugin),
+            pool: Arc::clone(self),
+            _permit: permit,
+        })
+    }
+
+    pub fn function_exists(&self, name: &str) -> bool {
+        self.compiled.function_exists(name)
+    }
+
+    /// Number of instances discarded and rebuilt, see [`CrashTracker::restarts`].
+    pub fn restarts(&self) -> u64 {
+        self.crashes.restarts()
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.crashes.is_disabled()
+    }
+}
+
+/// An instance checked out of a [`PluginPool`], returned to it on drop.
+pub struct PooledInstance {
+    healthy: bool,
+    plugin: Option<extism::Plugin>,
+    pool: Arc<PluginPool>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledInstance {
+    /// Drops the instance instead of returning it, e.g. after a call was interrupted by
+    /// a limit. The next checkout creates a fresh instance in its place.
+    pub fn discard(&mut self) {
+        self.healthy = false;
+    }
+
+    /// Discards the instance after a trap left it in an unknown state, counting the
+    /// crash towards the pool's circuit breaker.
+    pub fn mark_unhealthy(&mut self) {
+        if std::mem::replace(&mut self.healthy, false) {
+            self.pool.crashes.record_crash();
+        }
+    }
+}
+
+impl Deref for PooledInstance {
+    type Target = extism::Plugin;
+
+    fn deref(&self) -> &Self::Target {
+        self.plugin.as_ref().expect("instance already returned")
+    }
+}
+
+impl DerefMut for PooledInstance {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.plugin.as_mut().expect("instance already returned")
+    }
+}
+
+impl Drop for PooledInstance {
+    fn drop(&mut self) {
+        if let Some(plugin) = self.plugin.take() {
+            if self.healthy {
+                if !self.pool.is_disabled() {
+                    self.pool
+                        .idle
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push(plugin);
+                }
+            } else {
+                let restarts = self.pool.crashes.restarts.fetch_add(1, Ordering::Relaxed) + 1;
+                tracing::warn!(
+                    "Discarding unhealthy plugin instance {} ({restarts} restarts so far)",
+                    plugin.id
+                );
+            }
+        }
+    }
+}
+
+/// Whether a call error left the instance in an unknown state: a wasm trap (which is
+/// also how guest panics surface) or running out of memory under `memory_limit`.
+fn is_trap(e: &extism::Error) -> bool {
+    e.is::<wasmtime::Trap>() || e.root_cause().to_string() == "oom"
+}
+
+/// Error code returned to clients when a plugin call is stopped by one of the limits
+/// in its `RuntimeConfig`. The `data` of the error names the limit that tripped.
+pub const LIMIT_EXCEEDED: ErrorCode = ErrorCode(-32001);
+
+/// A per-call limit from `RuntimeConfig` that extism enforces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CallLimit {
+    Timeout,
+    Fuel,
+}
+
+impl CallLimit {
+    /// Which limit stopped a call, if any. Cancelling a call through its cancel handle
+    /// is reported the same way as a timeout, so callers must rule that out first.
+    fn of(e: &extism::Error) -> Option<Self> {
+        match e.root_cause().to_string().as_str() {
+            "timeout" => Some(CallLimit::Timeout),
+            "plugin ran out of fuel" => Some(CallLimit::Fuel),
+            _ => None,
+        }
+    }
+
+    fn config_key(self) -> &'static str {
+        match self {
+            CallLimit::Timeout => "call_timeout",
+            CallLimit::Fuel => "fuel_limit",
+        }
+    }
+
+    pub fn into_error(self, plugin_name: &str) -> McpError {
+        McpError::new(
+            LIMIT_EXCEEDED,
+            format!(
+                "Plugin {plugin_name} exceeded its {} and was stopped",
+                self.config_key()
+            ),
+            Some(json!({ "plugin": plugin_name, "limit": self.config_key() })),
+        )
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct PluginRequestContext {
@@ -101,6 +475,16 @@ pub trait Plugin: Send + Sync + Debug {
         context: RequestContext<RoleServer>,
     ) -> Result<ListToolsResult, McpError>;
 
+    /// Whether the plugin was disabled by the circuit breaker and should be hidden.
+    fn is_disabled(&self) -> bool {
+        false
+    }
+
+    /// What the plugin declares about itself, when it embeds metadata.
+    fn metadata(&self) -> Option<&PluginMetadata> {
+        None
+    }
+
     fn name(&self) -> &PluginName;
 
     async fn on_roots_list_changed(
@@ -110,8 +494,6 @@ pub trait Plugin: Send + Sync + Debug {
         Ok(())
     }
 
-    fn plugin(&self) -> &PluginHandle;
-
     async fn read_resource(
         &self,
         request: ReadResourceRequestParam,
@@ -122,7 +504,7 @@ pub trait Plugin: Send + Sync + Debug {
 }
 
 async fn call_plugin<R>(
-    plugin: &dyn Plugin,
+    plugin: &PluginBase,
     name: &str,
     payload: String,
     ct: CancellationToken,
@@ -130,23 +512,50 @@ async fn call_plugin<R>(
 where
     R: DeserializeOwned + Send + 'static,
 {
-    let plugin_name = plugin.name().to_string();
+    let plugin_name = plugin.name.to_string();
     if !function_exists_plugin(plugin, name) {
         return Err(McpError::invalid_request(
             format!("Method {name} not found for plugin {plugin_name}"),
             None,
         ));
     }
-    let plugin = Arc::clone(plugin.plugin());
-    let cancel_handle = {
-        let guard = plugin.lock().unwrap();
-        guard.cancel_handle()
+    if plugin.pool.is_disabled() {
+        return Err(disabled_error(plugin));
+    }
+    // Don't start a call whose request was already cancelled
+    if ct.is_cancelled() {
+        return Err(McpError::internal_error(
+            format!("Plugin {plugin_name} was cancelled"),
+            None,
+        ));
+    }
+    let session_id = plugin.session_id;
+    let mut instance = tokio::select! {
+        instance = plugin.pool.checkout() => instance.map_err(|e| McpError::internal_error(
+            format!("Failed to check out an instance of plugin {plugin_name}: {e}"),
+            None,
+        ))?,
+        _ = ct.cancelled() => {
+            return Err(McpError::internal_error(
+                format!("Plugin {plugin_name} was cancelled"),
+                None,
+            ));
+        }
     };
+    let cancel_handle = instance.cancel_handle();
 
     let name = name.to_string();
+    let call_ct = ct.clone();
+    let call_plugin_name = plugin_name.clone();
     let mut join = tokio::task::spawn_blocking(move || {
-        let mut plugin = plugin.lock().unwrap();
-        let result: Result<String, extism::Error> = plugin.call(&name, payload);
+        let _session = SessionGuard::enter(session_id);
+        let result: Result<String, extism::Error> = instance.call(&name, payload);
+        // The cancel handle may have stopped the call halfway through updating the
+        // plugin's state, so never hand that instance to another call
+        let cancelled = call_ct.is_cancelled();
+        if cancelled {
+            instance.discard();
+        }
         match result {
             Ok(res) => match serde_json::from_str::<R>(&res) {
                 Ok(parsed) => Ok(parsed),
@@ -155,10 +564,28 @@ where
                     None,
                 )),
             },
-            Err(e) => Err(McpError::internal_error(
-                format!("Failed to call plugin: {e}"),
-                None,
-            )),
+            Err(e) => {
+                let limit = CallLimit::of(&e).filter(|_| !cancelled);
+                // Being cancelled is not a crash
+                if is_trap(&e) && !cancelled {
+                    instance.mark_unhealthy();
+                } else if limit.is_some() {
+                    instance.discard();
+                }
+                match limit {
+                    Some(limit) => {
+                        tracing::warn!(
+                            "Plugin {call_plugin_name} exceeded its {}",
+                            limit.config_key()
+                        );
+                        Err(limit.into_error(&call_plugin_name))
+                    }
+                    None => Err(McpError::internal_error(
+                        format!("Failed to call plugin: {e}"),
+                        None,
+                    )),
+                }
+            }
         }
     });
 
@@ -206,25 +633,49 @@ where
     }
 }
 
-fn function_exists_plugin(plugin: &dyn Plugin, name: &str) -> bool {
-    let plugin = Arc::clone(plugin.plugin());
-    plugin.lock().unwrap().function_exists(name)
+fn disabled_error(plugin: &PluginBase) -> McpError {
+    McpError::internal_error(
+        format!(
+            "Plugin {} is disabled after crashing {MAX_CRASHES} times within {}s",
+            plugin.name,
+            CRASH_WINDOW.as_secs()
+        ),
+        Some(json!({ "plugin": plugin.name, "restarts": plugin.pool.restarts() })),
+    )
+}
+
+fn function_exists_plugin(plugin: &PluginBase, name: &str) -> bool {
+    plugin.pool.function_exists(name)
 }
 
-async fn notify_plugin(plugin: &dyn Plugin, name: &str, payload: String) -> Result<(), McpError> {
-    let plugin_name = plugin.name().to_string();
+async fn notify_plugin(plugin: &PluginBase, name: &str, payload: String) -> Result<(), McpError> {
+    let plugin_name = plugin.name.to_string();
     if !function_exists_plugin(plugin, name) {
         return Err(McpError::invalid_request(
             format!("Method {name} not found for plugin {plugin_name}"),
             None,
         ));
     }
-    let plugin = Arc::clone(plugin.plugin());
+    if plugin.pool.is_disabled() {
+        return Err(disabled_error(plugin));
+    }
+    let session_id = plugin.session_id;
+    let mut instance = plugin.pool.checkout().await.map_err(|e| {
+        McpError::internal_error(
+            format!("Failed to check out an instance of plugin {plugin_name}: {e}"),
+            None,
+        )
+    })?;
     let name = name.to_string();
     tokio::task::spawn_blocking(move || {
-        let mut plugin = plugin.lock().unwrap();
-        let result: Result<String, extism::Error> = plugin.call(&name, payload);
+        let _session = SessionGuard::enter(session_id);
+        let result: Result<String, extism::Error> = instance.call(&name, payload);
         if let Err(e) = result {
+            if is_trap(&e) {
+                instance.mark_unhealthy();
+            } else if CallLimit::of(&e).is_some() {
+                instance.discard();
+            }
             tracing::error!("Failed to notify plugin {plugin_name}: {e}");
         }
     });
@@ -234,7 +685,9 @@ async fn notify_plugin(plugin: &dyn Plugin, name: &str, payload: String) -> Resu
 #[derive(Debug)]
 pub struct PluginBase {
     pub name: PluginName,
-    pub plugin: PluginHandle,
+    pub pool: Arc<PluginPool>,
+    pub session_id: Uuid,
+    pub metadata: Option<Arc<PluginMetadata>>,
 }
 
 #[derive(Debug)]
@@ -275,18 +728,32 @@ impl Plugin for PluginV1 {
         call_plugin::<ListToolsResult>(self, "describe", "".to_string(), context.ct).await
     }
 
-    fn name(&self) -> &PluginName {
-        &self.name
+    fn is_disabled(&self) -> bool {
+        self.pool.is_disabled()
     }
 
-    fn plugin(&self) -> &PluginHandle {
-        &self.plugin
+    fn metadata(&self) -> Option<&PluginMetadata> {
+        self.0.metadata.as_deref()
+    }
+
+    fn name(&self) -> &PluginName {
+        &self.name
     }
 }
 
 impl PluginV1 {
-    pub fn new(name: PluginName, plugin: PluginHandle) -> Self {
-        Self(PluginBase { name, plugin })
+    pub fn new(
+        name: PluginName,
+        pool: Arc<PluginPool>,
+        session_id: Uuid,
+        metadata: Option<Arc<PluginMetadata>>,
+    ) -> Self {
+        Self(PluginBase {
+            name,
+            pool,
+            session_id,
+            metadata,
+        })
     }
 }
 
@@ -459,6 +926,14 @@ impl Plugin for PluginV2 {
         .await
     }
 
+    fn is_disabled(&self) -> bool {
+        self.pool.is_disabled()
+    }
+
+    fn metadata(&self) -> Option<&PluginMetadata> {
+        self.0.metadata.as_deref()
+    }
+
     fn name(&self) -> &PluginName {
         &self.name
     }
@@ -481,10 +956,6 @@ impl Plugin for PluginV2 {
         .await
     }
 
-    fn plugin(&self) -> &PluginHandle {
-        &self.plugin
-    }
-
     async fn read_resource(
         &self,
         request: ReadResourceRequestParam,
@@ -505,7 +976,55 @@ impl Plugin for PluginV2 {
 }
 
 impl PluginV2 {
-    pub fn new(name: PluginName, plugin: PluginHandle) -> Self {
-        Self(PluginBase { name, plugin })
+    pub fn new(
+        name: PluginName,
+        pool: Arc<PluginPool>,
+        session_id: Uuid,
+        metadata: Option<Arc<PluginMetadata>>,
+    ) -> Self {
+        Self(PluginBase {
+            name,
+            pool,
+            session_id,
+            metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_call_plugin_discards_cancelled_instance() {
+        let wasm = wat::parse_str(
+            r#"(module (func (export "call_tool") (result i32) (loop $spin (br $spin)) (i32.const 0)))"#,
+        )
+        .unwrap();
+        let builder = extism::PluginBuilder::new(extism::Manifest::new([extism::Wasm::data(wasm)]));
+        let plugin = PluginBase {
+            name: PluginName::try_from("spin").unwrap(),
+            pool: Arc::new(PluginPool::new(
+                Arc::new(CompiledPlugin::new(builder).unwrap()),
+                1,
+            )),
+            session_id: Uuid::new_v4(),
+            metadata: None,
+        };
+
+        let ct = CancellationToken::new();
+        let cancel = ct.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+        let result = call_plugin::<Value>(&plugin, "call_tool", String::new(), ct).await;
+        assert!(result.is_err(), "Cancelled call should fail: {result:?}");
+
+        // Once the interrupted call has given its instance up, the next call gets a new one
+        let instance = plugin.pool.checkout().await.unwrap();
+        assert_eq!(plugin.pool.restarts(), 1);
+        assert!(!plugin.pool.is_disabled(), "Cancellations are not crashes");
+        drop(instance);
     }
 }
@@ -0,0 +1,231 @@
+use crate::{
+    Cli,
+    config::{NetworkConfig, load_config},
+    metadata::{PluginMetadata, module_exports},
+    oci::{build_auth, push_plugin_image, sign_image},
+    service::config_path,
+};
+use anyhow::{Context, Result, anyhow};
+use clap::Args;
+use oci_client::{Client, Reference};
+use std::path::PathBuf;
+
+#[derive(Args, Clone, Debug)]
+pub struct PushArgs {
+    #[arg(value_name = "WASM", help = "Plugin module to publish")]
+    wasm: PathBuf,
+
+    #[arg(
+        value_name = "REFERENCE",
+        help = "Image to push to, such as ghcr.io/you/plugin:v1, with or without oci://"
+    )]
+    reference: String,
+
+    #[arg(
+        long = "name",
+        help = "Plugin name [default: the embedded metadata, or the module file name]"
+    )]
+    name: Option<String>,
+
+    #[arg(long = "version", help = "Plugin version")]
+    version: Option<String>,
+
+    #[arg(long = "description", help = "What the plugin does")]
+    description: Option<String>,
+
+    #[arg(
+        long = "export",
+        value_name = "FUNCTION",
+        help = "Plugin function the module implements; may be repeated [default: the embedded metadata, or every exported function]"
+    )]
+    exports: Vec<String>,
+
+    #[arg(
+        long = "sign-key",
+        value_name = "FILE",
+        help = "Cosign private key to sign the pushed image with; COSIGN_PASSWORD decrypts it",
+        env = "HYPER_MCP_SIGN_KEY"
+    )]
+    sign_key: Option<PathBuf>,
+}
+
+impl PushArgs {
+    /// Builds the metadata of the module from what it embeds, overridden by the command
+    /// line, and checks that the module exports every declared function.
+    fn metadata(&self, wasm: &[u8]) -> Result<PluginMetadata> {
+        let module_exports = module_exports(wasm)
+            .with_context(|| format!("{} is not a wasm module", self.wasm.display()))?;
+        let mut metadata = PluginMetadata::from_module(wasm)?.unwrap_or_default();
+
+        if !self.exports.is_empty() {
+            metadata.exports = self.exports.clone();
+        }
+        let missing: Vec<&str> = metadata
+            .exports
+            .iter()
+            .filter(|export| !module_exports.contains(export))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "{} does not export {}",
+                self.wasm.display(),
+                missing.join(", ")
+            ));
+        }
+        if metadata.exports.is_empty() {
+            metadata.exports = module_exports;
+        }
+
+        if let Some(name) = &self.name {
+            metadata.name = name.clone();
+        }
+        if metadata.name.is_empty() {
+            metadata.name = self
+                .wasm
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .ok_or_else(|| anyhow!("Pass --name to name the plugin"))?;
+        }
+        if self.version.is_some() {
+            metadata.version = self.version.clone();
+        }
+        if self.description.is_some() {
+            metadata.description = self.description.clone();
+        }
+        Ok(metadata)
+    }
+}
+
+/// Publishes a plugin module to an OCI registry, signing it when a key is given.
+pub async fn push(cli: &Cli, args: &PushArgs) -> Result<()> {
+    let wasm = tokio::fs::read(&args.wasm)
+        .await
+        .with_context(|| format!("Failed to read {}", args.wasm.display()))?;
+    let metadata = args.metadata(&wasm)?;
+
+    let image_reference = args
+        .reference
+        .strip_prefix("oci://")
+        .unwrap_or(&args.reference);
+    let reference = Reference::try_from(image_reference)
+        .with_context(|| format!("Invalid image reference {image_reference}"))?;
+    // Registry credentials and network settings come from the config when there is one
+    let path = config_path(cli);
+    let (auths, network) = match path.exists() {
+        true => {
+            let config = load_config(&path, cli.strict_env).await?;
+            (config.auths, config.network)
+        }
+        false => (None, None),
+    };
+    let network = NetworkConfig::from_cli(cli, network.as_ref());
+    let auth = build_auth(&reference, &auths, &network).await;
+
+    let client_config = network.registry_config()?;
+    let client = Client::new(client_config.clone().into());
+    let digest = push_plugin_image(&client, image_reference, &auth, wasm, &metadata)
+        .await
+        .map_err(|e| anyhow!("Failed to push {image_reference}: {e}"))?;
+    println!("Pushed {image_reference}@{digest}");
+
+    if let Some(sign_key) = &args.sign_key {
+        let private_key = tokio::fs::read(sign_key)
+            .await
+            .with_context(|| format!("Failed to read signing key {}", sign_key.display()))?;
+        let password = std::env::var("COSIGN_PASSWORD").unwrap_or_default();
+        let pinned_reference = format!(
+            "{}/{}@{digest}",
+            reference.registry(),
+            reference.repository()
+        );
+        sign_image(
+            client_config,
+            &pinned_reference,
+            &auth,
+            &private_key,
+            password.as_bytes(),
+        )
+        .await?;
+        println!("Signed {pinned_reference}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(flatten)]
+        push: PushArgs,
+    }
+
+    #[test]
+    fn test_push_metadata() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (func (export "call") (result i32) i32.const 0)
+                (func (export "describe") (result i32) i32.const 0)
+                (func (export "helper") (result i32) i32.const 0))"#,
+        )
+        .unwrap();
+
+        let args = TestCli::parse_from(["push", "out/time.wasm", "ghcr.io/me/time:v1"]).push;
+        let metadata = args.metadata(&wasm).unwrap();
+        assert_eq!(metadata.name, "time");
+        assert_eq!(metadata.exports, vec!["call", "describe", "helper"]);
+
+        let args = TestCli::parse_from([
+            "push",
+            "out/time.wasm",
+            "ghcr.io/me/time:v1",
+            "--name",
+            "clock",
+            "--version",
+            "1.2.0",
+            "--export",
+            "call",
+            "--export",
+            "describe",
+        ])
+        .push;
+        let metadata = args.metadata(&wasm).unwrap();
+        assert_eq!(metadata.name, "clock");
+        assert_eq!(metadata.version.as_deref(), Some("1.2.0"));
+        assert_eq!(metadata.exports, vec!["call", "describe"]);
+
+        let args = TestCli::parse_from([
+            "push",
+            "out/time.wasm",
+            "ghcr.io/me/time:v1",
+            "--export",
+            "call_tool",
+        ])
+        .push;
+        let error = args.metadata(&wasm).unwrap_err().to_string();
+        assert!(error.contains("does not export call_tool"), "{error}");
+        // Embedded metadata fills in what the command line leaves out
+        let wasm = wat::parse_str(
+            r#"(module
+                (@custom "hyper-mcp" "{\"name\":\"clock\",\"description\":\"Tells the time\",\"exports\":[\"call\"]}")
+                (func (export "call") (result i32) i32.const 0))"#,
+        )
+        .unwrap();
+        let args = TestCli::parse_from([
+            "push",
+            "out/time.wasm",
+            "ghcr.io/me/time:v1",
+            "--version",
+            "2.0.0",
+        ])
+        .push;
+        let metadata = args.metadata(&wasm).unwrap();
+        assert_eq!(metadata.name, "clock");
+        assert_eq!(metadata.version.as_deref(), Some("2.0.0"));
+        assert_eq!(metadata.description.as_deref(), Some("Tells the time"));
+        assert_eq!(metadata.exports, vec!["call"]);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{Cli, service};
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{runtime::Handle, sync::Notify};
+
+// How often the config file is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Reloads running sessions whenever the config file changes or the process gets SIGHUP.
+pub fn spawn_config_watcher(cli: Cli) {
+    let reload = Arc::new(Notify::new());
+    let path = service::config_path(&cli);
+
+    tokio::spawn({
+        let reload = Arc::clone(&reload);
+        async move {
+            let mut modified = modified_time(&path).await;
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                let current = modified_time(&path).await;
+                if current != modified {
+                    modified = current;
+                    tracing::info!("Config file {} changed", path.display());
+                    reload.notify_one();
+                }
+            }
+        }
+    });
+
+    #[cfg(unix)]
+    tokio::spawn({
+        let reload = Arc::clone(&reload);
+        async move {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    tracing::error!("Failed to listen for SIGHUP: {e}");
+                    return;
+                }
+            };
+            while hangup.recv().await.is_some() {
+                tracing::info!("Received SIGHUP");
+                reload.notify_one();
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        loop {
+            reload.notified().await;
+            tracing::info!("Reloading config");
+            // Pulling OCI plugins is not `Send`, so reload on a blocking thread the way
+            // sessions are created.
+            let cli = cli.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                Handle::current().block_on(service::reload_sessions(&cli))
+            })
+            .await;
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::error!("Failed to reload config: {e}"),
+                Err(e) => tracing::error!("Config reload task failed: {e}"),
+            }
+        }
+    });
+}
+
+async fn modified_time(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,155 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt, path::PathBuf};
+use tokio::process::Command;
+
+/// Where a secret is kept. Secrets are read each time they are needed, never when the
+/// config is loaded.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SecretSource {
+    // Entry of the system keyring
+    Keyring {
+        service: String,
+        user: String,
+    },
+    // File holding the secret, such as a Docker or Kubernetes secret
+    File {
+        path: PathBuf,
+    },
+    // Environment variable of the hyper-mcp process
+    Env {
+        name: String,
+    },
+    // Helper command that prints the secret, like a docker credential helper
+    Exec {
+        command: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        args: Vec<String>,
+    },
+}
+
+impl SecretSource {
+    /// Reads the secret. A trailing newline, as files and commands usually end with,
+    /// is not part of it.
+    ///
+    /// The keyring is read on the blocking thread pool, as its backends block, possibly
+    /// on an unlock prompt.
+    pub async fn read(&self) -> Result<String> {
+        let secret = match self {
+            SecretSource::Keyring { service, user } => {
+                let (service, user) = (service.clone(), user.clone());
+                tokio::task::spawn_blocking(move || {
+                    keyring::Entry::new(&service, &user)
+                        .and_then(|entry| entry.get_secret())
+                        .with_context(|| format!("Failed to read keyring entry {service}/{user}"))
+                        .and_then(|secret| {
+                            String::from_utf8(secret).with_context(|| {
+                                format!("Keyring entry {service}/{user} is not UTF-8")
+                            })
+                        })
+                })
+                .await??
+            }
+            SecretSource::File { path } => tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read secret file {}", path.display()))?,
+            SecretSource::Env { name } => std::env::var(name)
+                .with_context(|| format!("Environment variable {name} is not set"))?,
+            SecretSource::Exec { command, args } => {
+                let output = Command::new(command)
+                    .args(args)
+                    .kill_on_drop(true)
+                    .output()
+                    .await
+                    .with_context(|| format!("Failed to run secret helper {command}"))?;
+                if !output.status.success() {
+                    return Err(anyhow!(
+                        "Secret helper {command} failed with {}: {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                String::from_utf8(output.stdout)
+                    .with_context(|| format!("Secret helper {command} did not print UTF-8"))?
+            }
+        };
+        Ok(secret.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+/// A config value that is either written in the config or read from a [`SecretSource`].
+///
+/// Its `Debug` output never shows the value, so configs can be logged.
+#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Secret {
+    Plain(String),
+    Source(SecretSource),
+}
+
+impl Secret {
+    pub async fn resolve(&self) -> Result<Cow<'_, str>> {
+        match self {
+            Secret::Plain(value) => Ok(Cow::Borrowed(value)),
+            Secret::Source(source) => source.read().await.map(Cow::Owned),
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Secret::Plain(_) => write!(f, "\"<redacted>\""),
+            Secret::Source(source) => source.fmt(f),
+        }
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret::Plain(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_secret_sources() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("api_key");
+        std::fs::write(&path, "from-file\n").unwrap();
+        let secret: Secret =
+            serde_yaml::from_str(&format!("type: file\npath: {}", path.display())).unwrap();
+        assert_eq!(secret.resolve().await.unwrap(), "from-file");
+
+        // SAFETY: no other test reads this variable
+        unsafe { std::env::set_var("HYPER_MCP_TEST_SECRET", "from-env") };
+        let secret: Secret =
+            serde_json::from_str(r#"{"type": "env", "name": "HYPER_MCP_TEST_SECRET"}"#).unwrap();
+        assert_eq!(secret.resolve().await.unwrap(), "from-env");
+
+        let secret: Secret = serde_json::from_str(
+            r#"{"type": "exec", "command": "sh", "args": ["-c", "echo from-exec"]}"#,
+        )
+        .unwrap();
+        assert_eq!(secret.resolve().await.unwrap(), "from-exec");
+
+        let secret = SecretSource::Exec {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), "echo oops >&2; exit 3".to_string()],
+        };
+        let error = secret.read().await.unwrap_err().to_string();
+        assert!(error.contains("oops"), "{error}");
+
+        let secret: Secret = serde_json::from_str(r#""plain""#).unwrap();
+        assert_eq!(secret.resolve().await.unwrap(), "plain");
+        assert_eq!(format!("{secret:?}"), "\"<redacted>\"");
+
+        let result: Result<Secret, _> =
+            serde_json::from_str(r#"{"type": "env", "name": "X", "default": "y"}"#);
+        assert!(result.is_err());
+    }
+}
@@ -1,14 +1,23 @@
 use crate::{
     Cli,
-    config::{Config, PluginName, PluginNameParseError, load_config},
-    https_auth::Authenticator,
-    oci::pull_and_extract_oci_image,
-    plugin::{Plugin, PluginV1, PluginV2},
+    config::{
+        Config, NetworkConfig, PathMode, PluginConfig, PluginName, PluginNameParseError,
+        load_config,
+    },
+    fetch::{FetchedPlugin, PluginFetcher, sha256_digest},
+    lockfile::{Lockfile, lockfile_path},
+    metadata::PluginMetadata,
+    outbound::{HostRecorder, http_functions},
+    plugin::{
+        CompiledPlugin, CrashTracker, Plugin, PluginPool, PluginV1, PluginV2, current_session,
+    },
+    secret::Secret,
+    upstream::{UpstreamPlugin, is_upstream_url},
 };
-use anyhow::{Error, Result};
+use anyhow::{Context, Error, Result};
 use bytesize::ByteSize;
-use dashmap::{DashMap, DashSet, Entry};
-use extism::{EXTISM_USER_MODULE, Function, Manifest, UserData, Wasm, host_fn};
+use dashmap::{DashMap, DashSet};
+use extism::{EXTISM_USER_MODULE, Function, Manifest, PluginBuilder, UserData, Wasm, host_fn};
 use extism_convert::Json;
 use rmcp::{
     ErrorData as McpError, ServerHandler,
@@ -18,18 +27,18 @@ use rmcp::{
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use serde_with::{DurationSeconds, serde_as};
-use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{self, Debug},
     ops::Deref,
+    path::PathBuf,
     str::FromStr,
-    sync::{Arc, LazyLock, Mutex, RwLock, Weak},
+    sync::{Arc, LazyLock, Mutex, PoisonError, RwLock, Weak},
     time::Duration,
 };
 use tokio::{
     runtime::Handle,
-    sync::{OnceCell, SetOnce},
+    sync::{Mutex as AsyncMutex, OnceCell, Semaphore, SetOnce},
 };
 use url::Url;
 use uuid::Uuid;
@@ -86,35 +95,60 @@ fn parse_namespaced_uri(namespaced_uri: String) -> Result<(PluginName, String)>
     Ok((plugin_name, uri.to_string()))
 }
 
-/// Check if a value contains an environment variable reference in the format ${ENVVARKEY}
-/// and replace it with the actual environment variable value if it exists.
-/// If the environment variable doesn't exist, returns the original value.
-fn check_env_reference(value: &str) -> String {
-    // Check if the value matches the pattern ${ENVVARKEY}
-    if let Some(stripped) = value.strip_prefix("${").and_then(|s| s.strip_suffix("}")) {
-        // Try to get the environment variable
-        match std::env::var(stripped) {
-            Ok(env_value) => {
-                tracing::debug!(
-                    "Resolved environment variable reference ${{{stripped}}} to actual value"
-                );
-                env_value
-            }
-            Err(_) => {
-                tracing::warn!(
-                    "Environment variable {stripped} not found, keeping original value {value}"
-                );
-                value.to_string()
+static PLUGIN_SERVICE_INNER_REGISTRY: LazyLock<DashMap<Uuid, Weak<PluginServiceInner>>> =
+    LazyLock::new(DashMap::new);
+// Content of pinned plugins by their pin, see `content_key`
+static WASM_CONTENT_CACHE: LazyLock<DashMap<String, FetchedPlugin>> = LazyLock::new(DashMap::new);
+static PLUGIN_REGISTRY: LazyLock<Mutex<HashMap<PluginName, Arc<RegistrySlot>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The registry entry of a plugin name, compiled by the first session that needs it
+/// while the others wait for that compilation instead of starting their own.
+///
+/// The config it is compiled from is kept so that a session loading a different
+/// config for the same plugin name gets a fresh slot instead of a stale plugin.
+#[derive(Debug)]
+struct RegistrySlot {
+    config: PluginConfig,
+    plugin: OnceCell<Arc<RegisteredPlugin>>,
+}
+
+impl RegistrySlot {
+    /// The slot of `config`, replacing the registered one when the config changed.
+    fn get(plugin_name: &PluginName, config: &PluginConfig) -> Arc<RegistrySlot> {
+        let mut registry = PLUGIN_REGISTRY
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match registry.get(plugin_name) {
+            Some(slot) if slot.config == *config => Arc::clone(slot),
+            _ => {
+                let slot = Arc::new(RegistrySlot {
+                    config: config.clone(),
+                    plugin: OnceCell::new(),
+                });
+                registry.insert(plugin_name.clone(), Arc::clone(&slot));
+                slot
             }
         }
-    } else {
-        value.to_string()
     }
 }
 
-static PLUGIN_SERVICE_INNER_REGISTRY: LazyLock<DashMap<Uuid, Weak<PluginServiceInner>>> =
-    LazyLock::new(DashMap::new);
-static WASM_CONTENT_CACHE: LazyLock<DashMap<PluginName, Vec<u8>>> = LazyLock::new(DashMap::new);
+/// A plugin compiled once for the whole process and shared by every session.
+#[derive(Debug)]
+struct RegisteredPlugin {
+    // What the plugin resolved to when it was fetched, see `FetchedPlugin::digest`
+    digest: String,
+    metadata: Option<Arc<PluginMetadata>>,
+    compiled: Arc<CompiledPlugin>,
+    // Digest of the `env_vars` values it was compiled with, to tell when a secret among
+    // them was rotated
+    env_digest: String,
+    // Shared by the pools of every session, so the circuit breaker trips for all
+    crashes: Arc<CrashTracker>,
+    // Shared by the pools of every session, so `max_instances` bounds the calls
+    // running at once across sessions
+    permits: Arc<Semaphore>,
+}
 
 #[allow(dead_code)]
 #[serde_as]
@@ -173,18 +207,19 @@ impl<'de> Deserialize<'de> for CreateElicitationRequestParamWithTimeout {
 
 #[derive(Clone, Debug)]
 struct PluginServiceContext {
-    handle: Handle,
-    plugin_service_id: Uuid,
     plugin_name: String,
 }
 
+type PluginMap = HashMap<PluginName, Arc<dyn Plugin>>;
+
 pub struct PluginServiceInner {
-    config: Config,
+    config: RwLock<Arc<Config>>,
     id: Uuid,
     logging_level: RwLock<LoggingLevel>,
-    names: SetOnce<HashMap<Uuid, PluginName>>,
     peer: SetOnce<Peer<RoleServer>>,
-    plugins: SetOnce<HashMap<PluginName, Box<dyn Plugin>>>,
+    plugins: RwLock<Arc<PluginMap>>,
+    // Serializes config reloads of this session.
+    reload_lock: AsyncMutex<()>,
     subscriptions: DashSet<String>,
 }
 
@@ -210,36 +245,267 @@ impl Deref for PluginService {
     }
 }
 
-impl PluginService {
-    pub async fn new(cli: &Cli) -> Result<Self> {
-        // Get default config path in the user's config directory
-        let default_config_path = dirs::config_dir()
+/// Returns the config file given on the command line, or the default one in the user's
+/// config directory.
+pub fn config_path(cli: &Cli) -> PathBuf {
+    cli.config_file.clone().unwrap_or_else(|| {
+        dirs::config_dir()
             .map(|mut path| {
                 path.push("hyper-mcp");
                 path.push("config.json");
                 path
             })
-            .unwrap();
+            .unwrap()
+    })
+}
+
+/// The key of a plugin's content in `WASM_CONTENT_CACHE`, for plugins whose content
+/// cannot change: those pinned by `sha256`, which the fetcher checked it against.
+///
+/// Other plugins are fetched again whenever their config changes, so that a reload
+/// picks up new content. OCI plugins are left out as they have their own cache on disk,
+/// by manifest digest.
+fn content_key(plugin_cfg: &PluginConfig) -> Option<String> {
+    let pin = plugin_cfg.sha256.as_ref()?;
+    if plugin_cfg.url.scheme() == "oci" {
+        return None;
+    }
+    let pin = pin.strip_prefix("sha256:").unwrap_or(pin);
+    Some(format!("sha256:{}", pin.to_ascii_lowercase()))
+}
+
+/// Returns the client peer of a live session, if it has one.
+pub fn session_peer(id: Uuid) -> Option<Peer<RoleServer>> {
+    PluginService::get(id).and_then(|service| service.peer.get().cloned())
+}
+
+/// Returns the config a live session currently runs with.
+pub fn session_config(id: Uuid) -> Option<Arc<Config>> {
+    PluginService::get(id).map(|service| service.config())
+}
+
+/// Reloads the config file and applies it to every live session.
+///
+/// Sessions are reloaded independently, so a session that fails to load the new
+/// config keeps running with its previous one.
+pub async fn reload_sessions(cli: &Cli) -> Result<()> {
+    let config = load_config(&config_path(cli), cli.strict_env).await?;
+    let sessions: Vec<PluginService> = PLUGIN_SERVICE_INNER_REGISTRY
+        .iter()
+        .filter_map(|entry| entry.value().upgrade())
+        .map(PluginService)
+        .collect();
+    let rotated = forget_plugins_with_rotated_secrets().await;
+    for service in sessions {
+        if let Err(e) = service.reload(cli, config.clone(), &rotated).await {
+            tracing::error!("Failed to reload config for session {}: {e}", service.id);
+        }
+    }
+
+    // Forget plugins that are no longer configured; sessions still using them hold
+    // their own references.
+    PLUGIN_REGISTRY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|name, _| config.plugins.contains_key(name));
+    WASM_CONTENT_CACHE.retain(|key, _| {
+        config
+            .plugins
+            .values()
+            .any(|cfg| content_key(cfg).as_ref() == Some(key))
+    });
+    Ok(())
+}
+
+/// Forgets the compiled plugins whose `env_vars` read secrets that changed since they
+/// were compiled, and returns their names.
+///
+/// Secrets are only read when a plugin is compiled, so the next load compiles it again
+/// with the rotated ones. Plugins whose secrets cannot be read keep running.
+async fn forget_plugins_with_rotated_secrets() -> HashSet<PluginName> {
+    let slots: Vec<(PluginName, Arc<RegistrySlot>)> = PLUGIN_REGISTRY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter(|(_, slot)| reads_secrets(&slot.config))
+        .map(|(name, slot)| (name.clone(), Arc::clone(slot)))
+        .collect();
+
+    let mut rotated = HashSet::new();
+    for (plugin_name, slot) in slots {
+        let Some(registered) = slot.plugin.get() else {
+            continue;
+        };
+        let digest = resolve_env_vars(&plugin_name, &slot.config)
+            .await
+            .and_then(|env_vars| env_digest(&env_vars));
+        match digest {
+            Ok(digest) if digest == registered.env_digest => {}
+            Ok(_) => {
+                tracing::info!("Secrets of plugin {plugin_name} changed, compiling it again");
+                let mut registry = PLUGIN_REGISTRY
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                // Unless a session already replaced it meanwhile
+                if registry
+                    .get(&plugin_name)
+                    .is_some_and(|current| Arc::ptr_eq(current, &slot))
+                {
+                    registry.remove(&plugin_name);
+                }
+                rotated.insert(plugin_name);
+            }
+            Err(e) => tracing::warn!("Keeping plugin {plugin_name} as it was: {e}"),
+        }
+    }
+    rotated
+}
+
+/// See `RegisteredPlugin::env_digest`.
+fn env_digest(env_vars: &BTreeMap<String, String>) -> Result<String> {
+    Ok(sha256_digest(&serde_json::to_vec(env_vars)?))
+}
+
+/// The values of the plugin's `env_vars`, reading those kept in secret sources.
+async fn resolve_env_vars(
+    plugin_name: &PluginName,
+    plugin_cfg: &PluginConfig,
+) -> Result<BTreeMap<String, String>> {
+    let mut resolved = BTreeMap::new();
+    let env_vars = plugin_cfg
+        .runtime_config
+        .as_ref()
+        .and_then(|runtime_cfg| runtime_cfg.env_vars.as_ref());
+    for (key, value) in env_vars.into_iter().flatten() {
+        let value = value
+            .resolve()
+            .await
+            .with_context(|| format!("Failed to read env_vars {key} of plugin {plugin_name}"))?;
+        resolved.insert(key.clone(), value.into_owned());
+    }
+    Ok(resolved)
+}
+
+/// How many calls to the plugin may run at once, see `RuntimeConfig::max_instances`.
+fn max_instances(plugin_cfg: &PluginConfig) -> usize {
+    plugin_cfg
+        .runtime_config
+        .as_ref()
+        .and_then(|rc| rc.max_instances)
+        .map_or(1, |n| n.get())
+}
+
+/// Whether any of the plugin's `env_vars` is read from a secret source.
+fn reads_secrets(plugin_cfg: &PluginConfig) -> bool {
+    plugin_cfg
+        .runtime_config
+        .as_ref()
+        .and_then(|runtime_cfg| runtime_cfg.env_vars.as_ref())
+        .is_some_and(|env_vars| {
+            env_vars
+                .values()
+                .any(|value| matches!(value, Secret::Source(_)))
+        })
+}
 
-        let config_path = cli.config_file.as_ref().unwrap_or(&default_config_path);
+impl PluginService {
+    pub async fn new(cli: &Cli) -> Result<Self> {
+        let config_path = config_path(cli);
         tracing::info!("Using config file at {}", config_path.display());
 
+        let config = Arc::new(load_config(&config_path, cli.strict_env).await?);
         let inner = Arc::new(PluginServiceInner {
-            config: load_config(config_path).await?,
+            config: RwLock::new(Arc::clone(&config)),
             id: Uuid::new_v4(),
             logging_level: RwLock::new(LoggingLevel::Error),
-            names: SetOnce::new(),
             peer: SetOnce::new(),
-            plugins: SetOnce::new(),
+            plugins: RwLock::new(Arc::default()),
+            reload_lock: AsyncMutex::new(()),
             subscriptions: DashSet::new(),
         });
         PLUGIN_SERVICE_INNER_REGISTRY.insert(inner.id, Arc::downgrade(&inner));
         let service = Self(inner);
 
-        service.load_plugins(cli).await?;
+        let plugins = service
+            .load_plugins(cli, &config, config.plugins.keys())
+            .await?;
+        *service
+            .plugins
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(plugins);
         Ok(service)
     }
 
+    fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn plugins(&self) -> Arc<PluginMap> {
+        Arc::clone(&self.plugins.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Applies a new config to this session.
+    ///
+    /// Only plugins whose `PluginConfig` was added or changed, or whose secrets were
+    /// `rotated`, are loaded again; calls already running keep the instance they started
+    /// on. When any plugin changed, the client is told that its tool, prompt and resource
+    /// lists may have changed.
+    pub async fn reload(
+        &self,
+        cli: &Cli,
+        config: Config,
+        rotated: &HashSet<PluginName>,
+    ) -> Result<()> {
+        let _reload = self.reload_lock.lock().await;
+        let old_config = self.config();
+        let changed: Vec<&PluginName> = config
+            .plugins
+            .iter()
+            .filter(|(name, cfg)| {
+                old_config.plugins.get(*name) != Some(*cfg) || rotated.contains(*name)
+            })
+            .map(|(name, _)| name)
+            .collect();
+        let removed: Vec<&PluginName> = old_config
+            .plugins
+            .keys()
+            .filter(|name| !config.plugins.contains_key(*name))
+            .collect();
+
+        let mut plugins = PluginMap::clone(&self.plugins());
+        plugins.retain(|name, _| config.plugins.contains_key(name));
+        plugins.extend(
+            self.load_plugins(cli, &config, changed.iter().copied())
+                .await?,
+        );
+
+        let unchanged = changed.is_empty() && removed.is_empty();
+        if !unchanged {
+            tracing::info!(
+                "Reloaded config for session {}: changed {changed:?}, removed {removed:?}",
+                self.id
+            );
+        }
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+        *self.plugins.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(plugins);
+        if unchanged {
+            return Ok(());
+        }
+
+        if let Some(peer) = self.peer.get() {
+            if let Err(e) = peer.notify_tool_list_changed().await {
+                tracing::error!("Failed to notify tool list changed: {e}");
+            }
+            if let Err(e) = peer.notify_prompt_list_changed().await {
+                tracing::error!("Failed to notify prompt list changed: {e}");
+            }
+            if let Err(e) = peer.notify_resource_list_changed().await {
+                tracing::error!("Failed to notify resource list changed: {e}");
+            }
+        }
+        Ok(())
+    }
+
     fn get(id: Uuid) -> Option<PluginService> {
         if let Some(weak_inner) = PLUGIN_SERVICE_INNER_REGISTRY.get(&id)
             && let Some(inner) = weak_inner.upgrade()
@@ -250,471 +516,502 @@ impl PluginService {
         None
     }
 
-    async fn load_plugins(&self, cli: &Cli) -> Result<()> {
-        let reqwest_client: OnceCell<reqwest::Client> = OnceCell::new();
-        let oci_client: OnceCell<oci_client::Client> = OnceCell::new();
-        let s3_client: OnceCell<aws_sdk_s3::Client> = OnceCell::new();
+    /// Returns the session whose plugin call is executing on the current thread.
+    fn current() -> Result<PluginService> {
+        let id = current_session()
+            .ok_or_else(|| anyhow::anyhow!("No session bound to the current plugin call"))?;
+        PluginService::get(id)
+            .ok_or_else(|| anyhow::anyhow!("PluginService with ID {id:?} not found"))
+    }
 
-        let mut names = HashMap::new();
-        let mut plugins: HashMap<PluginName, Box<dyn Plugin>> = HashMap::new();
+    /// Loads the named plugins from `config` into new pools for this session.
+    async fn load_plugins<'a>(
+        &self,
+        cli: &Cli,
+        config: &Config,
+        names: impl IntoIterator<Item = &'a PluginName>,
+    ) -> Result<PluginMap> {
+        let fetcher = PluginFetcher::default();
+        let network = NetworkConfig::from_cli(cli, config.network.as_ref());
+        let lockfile = match cli.locked {
+            true => Some(Lockfile::load(&lockfile_path(&config_path(cli))).await?),
+            false => None,
+        };
 
-        host_fn!(create_elicitation(ctx: PluginServiceContext; elicitation_msg: Json<CreateElicitationRequestParamWithTimeout>) -> Json<CreateElicitationResult> {
-            let elicitation_msg = elicitation_msg.into_inner();
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
-            match plugin_service.peer.get() {
-                Some(peer) => {
-                    if peer.supports_elicitation() {
-                        if let Some(timeout) = elicitation_msg.timeout {
-                            tracing::info!("Creating elicitation from {} with timeout {:?}", ctx.plugin_name, timeout);
-                            ctx.handle.block_on(peer.create_elicitation_with_timeout(elicitation_msg.inner, Some(timeout))).map(Json).map_err(Error::from)
-                        } else {
-                            tracing::info!("Creating elicitation from {}", ctx.plugin_name);
-                            ctx.handle.block_on(peer.create_elicitation(elicitation_msg.inner)).map(Json).map_err(Error::from)
+        let recorder = cli
+            .record_hosts
+            .as_deref()
+            .map(HostRecorder::open)
+            .transpose()?;
+        let mut plugins = PluginMap::new();
+
+        for plugin_name in names {
+            let plugin_cfg = config
+                .plugins
+                .get(plugin_name)
+                .ok_or_else(|| anyhow::anyhow!("Plugin {plugin_name} is not configured"))?;
+            if is_upstream_url(&plugin_cfg.url) {
+                let plugin = UpstreamPlugin::connect(
+                    plugin_name.clone(),
+                    plugin_cfg,
+                    &config.auths,
+                    &network,
+                    self.id,
+                )
+                .await?;
+                tracing::info!("Connected to upstream server of plugin {plugin_name}");
+                plugins.insert(plugin_name.clone(), Arc::new(plugin));
+                continue;
+            }
+            // Concurrent sessions wait for the same compilation, without holding up
+            // sessions loading other plugins
+            let slot = RegistrySlot::get(plugin_name, plugin_cfg);
+            if slot.plugin.initialized() {
+                tracing::debug!("Reusing compiled plugin {plugin_name}");
+            }
+            let registered = slot
+                .plugin
+                .get_or_try_init(|| async {
+                    let key = content_key(plugin_cfg);
+                    let fetched = match key.as_ref().and_then(|key| WASM_CONTENT_CACHE.get(key)) {
+                        Some(fetched) => fetched.clone(),
+                        None => {
+                            let fetched =
+                                fetcher.fetch(cli, config, plugin_name, plugin_cfg).await?;
+                            if let Some(key) = key {
+                                WASM_CONTENT_CACHE.insert(key, fetched.clone());
+                            }
+                            fetched
                         }
-                    } else {
-                        tracing::info!("Peer does not support elicitation, declining from {}", ctx.plugin_name);
-                        Ok(Json(CreateElicitationResult {
-                            action: ElicitationAction::Decline,
-                            content: None,
-                        }))
-                    }
-                },
-                None => Err(anyhow::anyhow!("No peer available")),
+                    };
+                    let metadata = PluginMetadata::from_module(&fetched.content)
+                        .with_context(|| format!("Plugin {plugin_name} has invalid metadata"))?
+                        .map(Arc::new);
+                    let env_vars = resolve_env_vars(plugin_name, plugin_cfg).await?;
+                    let registered = Arc::new(RegisteredPlugin {
+                        digest: fetched.digest,
+                        metadata,
+                        env_digest: env_digest(&env_vars)?,
+                        compiled: Arc::new(compile_plugin(
+                            plugin_name,
+                            plugin_cfg,
+                            fetched.content,
+                            env_vars,
+                            recorder.clone(),
+                        )?),
+                        crashes: Arc::default(),
+                        permits: Arc::new(Semaphore::new(max_instances(plugin_cfg))),
+                    });
+                    tracing::info!("Compiled plugin {plugin_name}");
+                    Ok::<_, Error>(registered)
+                })
+                .await?;
+            if let Some(lockfile) = &lockfile
+                && let Err(e) = lockfile.check(plugin_name, &plugin_cfg.url, &registered.digest)
+            {
+                tracing::error!("Refusing to load plugin {plugin_name}: {e}");
+                return Err(anyhow::anyhow!(
+                    "Refusing to load plugin {plugin_name}: {e}"
+                ));
+            }
+            if let Some(metadata) = &registered.metadata {
+                metadata.check(
+                    plugin_name,
+                    plugin_cfg.runtime_config.as_ref(),
+                    cli.strict_capabilities,
+                )?;
             }
-        });
 
-        host_fn!(create_message(ctx: PluginServiceContext; sampling_msg: Json<CreateMessageRequestParam>) -> Json<CreateMessageResult> {
-            let sampling_msg = sampling_msg.into_inner();
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
-            match plugin_service.peer.get() {
-                Some(peer) => {
-                    if let Some(peer_info) = peer.peer_info() && peer_info.capabilities.sampling.is_some() {
-                        tracing::info!("Creating sampling message from {}", ctx.plugin_name);
-                        ctx.handle.block_on(peer.create_message(sampling_msg)).map(Json).map_err(Error::from)
-                    } else {
-                        Err(anyhow::anyhow!("Peer does not support sampling"))
-                    }
-                },
-                None => Err(anyhow::anyhow!("No peer available")),
+            let session_id = self.id;
+            let disabled_name = plugin_name.clone();
+            let crashes = Arc::clone(&registered.crashes);
+            let pool = Arc::new(
+                PluginPool::new(Arc::clone(&registered.compiled), max_instances(plugin_cfg))
+                    .with_crash_tracker(Arc::clone(&registered.crashes))
+                    .with_permits(Arc::clone(&registered.permits))
+                    .on_disabled(move || {
+                        tracing::error!(
+                            "Plugin {disabled_name} disabled after repeated crashes ({} restarts), hiding it from session {session_id}",
+                            crashes.restarts()
+                        );
+                        // Its tools are no longer listed, so let the client refresh
+                        if let Some(peer) = session_peer(session_id)
+                            && let Ok(handle) = Handle::try_current()
+                        {
+                            handle.spawn(async move {
+                                if let Err(e) = peer.notify_tool_list_changed().await {
+                                    tracing::error!("Failed to notify tool list changed: {e}");
+                                }
+                            });
+                        }
+                    }),
+            );
+            // Plugins that do not declare their ABI are told apart by their exports
+            let metadata = registered.metadata.clone();
+            let abi_version = match metadata.as_ref().and_then(|m| m.abi_version) {
+                Some(abi_version) => abi_version,
+                None if pool.function_exists("call") && pool.function_exists("describe") => 1,
+                None => 2,
+            };
+            let plugin: Arc<dyn Plugin> = match abi_version {
+                1 => Arc::new(PluginV1::new(plugin_name.clone(), pool, self.id, metadata)),
+                _ => Arc::new(PluginV2::new(plugin_name.clone(), pool, self.id, metadata)),
+            };
+
+            tracing::info!("Loaded plugin {}", plugin.name());
+            plugins.insert(plugin.name().clone(), plugin);
+        }
+        Ok(plugins)
+    }
+
+    pub fn logging_level(&self) -> LoggingLevel {
+        *self
+            .logging_level
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn set_logging_level(&self, level: LoggingLevel) {
+        *self
+            .logging_level
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = level;
+    }
+}
+
+/// Describes the plugins that declare a version or description, so clients know what
+/// the tools of each plugin are for.
+fn plugin_instructions(plugins: &PluginMap) -> Option<String> {
+    let mut lines: Vec<String> = plugins
+        .iter()
+        .filter_map(|(plugin_name, plugin)| {
+            let metadata = plugin.metadata()?;
+            let mut line = format!("- {plugin_name}");
+            if let Some(version) = &metadata.version {
+                line.push_str(&format!(" {version}"));
             }
-        });
+            if let Some(description) = &metadata.description {
+                line.push_str(&format!(": {description}"));
+            }
+            (metadata.version.is_some() || metadata.description.is_some()).then_some(line)
+        })
+        .collect();
+    lines.sort();
+    match lines.is_empty() {
+        true => None,
+        false => Some(format!(
+            "Tools, prompts and resources are prefixed with the name of the plugin that provides them. Plugins:\n{}",
+            lines.join("\n")
+        )),
+    }
+}
 
-        // Declares a host function `list_roots` that plugins can call
-        host_fn!(list_roots(ctx: PluginServiceContext;) -> Json<ListRootsResult> {
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
-            match plugin_service.peer.get() {
-                Some(peer) => {
-                    if let Some(peer_info) = peer.peer_info() && peer_info.capabilities.roots.is_some() {
-                        tracing::info!("Listing roots from {}", ctx.plugin_name);
-                        ctx.handle.block_on(peer.list_roots()).map(Json).map_err(Error::from)
+/// Compiles a plugin's wasm module together with the host functions it can import.
+///
+/// Host functions resolve the calling session at call time through
+/// [`current_session`], so the compiled plugin can be shared by every session. Only
+/// when `recorder` is set or the plugin has `inject_auths` do HTTP requests go through
+/// [`http_functions`] instead of extism's own `http_request`, to be recorded or to get
+/// the session's `auths` added.
+fn compile_plugin(
+    plugin_name: &PluginName,
+    plugin_cfg: &PluginConfig,
+    wasm_content: Vec<u8>,
+    env_vars: BTreeMap<String, String>,
+    recorder: Option<Arc<HostRecorder>>,
+) -> Result<CompiledPlugin> {
+    host_fn!(create_elicitation(ctx: PluginServiceContext; elicitation_msg: Json<CreateElicitationRequestParamWithTimeout>) -> Json<CreateElicitationResult> {
+        let elicitation_msg = elicitation_msg.into_inner();
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
+        match plugin_service.peer.get() {
+            Some(peer) => {
+                if peer.supports_elicitation() {
+                    if let Some(timeout) = elicitation_msg.timeout {
+                        tracing::info!("Creating elicitation from {} with timeout {:?}", ctx.plugin_name, timeout);
+                        Handle::current().block_on(peer.create_elicitation_with_timeout(elicitation_msg.inner, Some(timeout))).map(Json).map_err(Error::from)
                     } else {
-                        Ok(Json(ListRootsResult::default()))
+                        tracing::info!("Creating elicitation from {}", ctx.plugin_name);
+                        Handle::current().block_on(peer.create_elicitation(elicitation_msg.inner)).map(Json).map_err(Error::from)
                     }
-                },
-                None => Err(anyhow::anyhow!("No peer available")),
-            }
-        });
-
-        // Declares a host function `notify_logging_message` that plugins can call
-        host_fn!(notify_logging_message(ctx: PluginServiceContext; log_msg: Json<LoggingMessageNotificationParam>) {
-            let log_msg = log_msg.into_inner();
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
-            if (plugin_service.logging_level() as u8) <= (log_msg.level as u8) && let Some(peer) = plugin_service.peer.get() {
-                tracing::debug!("Logging message from {}", ctx.plugin_name);
-                return ctx.handle.block_on(peer.notify_logging_message(log_msg)).map_err(Error::from);
-            }
-            Ok(())
-        });
+                } else {
+                    tracing::info!("Peer does not support elicitation, declining from {}", ctx.plugin_name);
+                    Ok(Json(CreateElicitationResult {
+                        action: ElicitationAction::Decline,
+                        content: None,
+                    }))
+                }
+            },
+            None => Err(anyhow::anyhow!("No peer available")),
+        }
+    });
+
+    host_fn!(create_message(ctx: PluginServiceContext; sampling_msg: Json<CreateMessageRequestParam>) -> Json<CreateMessageResult> {
+        let sampling_msg = sampling_msg.into_inner();
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
+        match plugin_service.peer.get() {
+            Some(peer) => {
+                if let Some(peer_info) = peer.peer_info() && peer_info.capabilities.sampling.is_some() {
+                    tracing::info!("Creating sampling message from {}", ctx.plugin_name);
+                    Handle::current().block_on(peer.create_message(sampling_msg)).map(Json).map_err(Error::from)
+                } else {
+                    Err(anyhow::anyhow!("Peer does not support sampling"))
+                }
+            },
+            None => Err(anyhow::anyhow!("No peer available")),
+        }
+    });
+
+    // Declares a host function `list_roots` that plugins can call
+    host_fn!(list_roots(ctx: PluginServiceContext;) -> Json<ListRootsResult> {
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
+        match plugin_service.peer.get() {
+            Some(peer) => {
+                if let Some(peer_info) = peer.peer_info() && peer_info.capabilities.roots.is_some() {
+                    tracing::info!("Listing roots from {}", ctx.plugin_name);
+                    Handle::current().block_on(peer.list_roots()).map(Json).map_err(Error::from)
+                } else {
+                    Ok(Json(ListRootsResult::default()))
+                }
+            },
+            None => Err(anyhow::anyhow!("No peer available")),
+        }
+    });
+
+    // Declares a host function `notify_logging_message` that plugins can call
+    host_fn!(notify_logging_message(ctx: PluginServiceContext; log_msg: Json<LoggingMessageNotificationParam>) {
+        let log_msg = log_msg.into_inner();
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
+        if (plugin_service.logging_level() as u8) <= (log_msg.level as u8) && let Some(peer) = plugin_service.peer.get() {
+            tracing::debug!("Logging message from {}", ctx.plugin_name);
+            return Handle::current().block_on(peer.notify_logging_message(log_msg)).map_err(Error::from);
+        }
+        Ok(())
+    });
+
+    // Declares a host function `notify_progress` that plugins can call
+    host_fn!(notify_progress(ctx: PluginServiceContext; progress_msg: Json<ProgressNotificationParam>) {
+        let progress_msg = progress_msg.into_inner();
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
+        match plugin_service.peer.get() {
+            Some(peer) => {
+                tracing::debug!("Progress notification from {}", ctx.plugin_name);
+                Handle::current().block_on(peer.notify_progress(progress_msg)).map_err(Error::from)
+            },
+            None => Ok(()),
+        }
+    });
 
-        // Declares a host function `notify_progress` that plugins can call
-        host_fn!(notify_progress(ctx: PluginServiceContext; progress_msg: Json<ProgressNotificationParam>) {
-            let progress_msg = progress_msg.into_inner();
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
-            match plugin_service.peer.get() {
-                Some(peer) => {
-                    tracing::debug!("Progress notification from {}", ctx.plugin_name);
-                    ctx.handle.block_on(peer.notify_progress(progress_msg)).map_err(Error::from)
-                },
-                None => Ok(()),
-            }
-        });
+    host_fn!(notify_prompt_list_changed(ctx: PluginServiceContext;) {
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
 
-        host_fn!(notify_prompt_list_changed(ctx: PluginServiceContext;) {
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
+        match plugin_service.peer.get() {
+            Some(peer) => {
+                tracing::info!("Notifying tool list changed from {}", ctx.plugin_name);
+                Handle::current().block_on(peer.notify_prompt_list_changed()).map_err(Error::from)
+            },
+            None => Ok(()),
+        }
+    });
 
-            match plugin_service.peer.get() {
-                Some(peer) => {
-                    tracing::info!("Notifying tool list changed from {}", ctx.plugin_name);
-                    ctx.handle.block_on(peer.notify_prompt_list_changed()).map_err(Error::from)
-                },
-                None => Ok(()),
-            }
-        });
+    host_fn!(notify_resource_list_changed(ctx: PluginServiceContext;) {
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
 
-        host_fn!(notify_resource_list_changed(ctx: PluginServiceContext;) {
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
+        match plugin_service.peer.get() {
+            Some(peer) => {
+                tracing::info!("Notifying tool list changed from {}", ctx.plugin_name);
+                Handle::current().block_on(peer.notify_resource_list_changed()).map_err(Error::from)
+            },
+            None => Ok(()),
+        }
+    });
 
+    host_fn!(notify_resource_updated(ctx: PluginServiceContext; update_msg: Json<ResourceUpdatedNotificationParam>) {
+        let update_msg = update_msg.into_inner();
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
+        if plugin_service.subscriptions.contains(&update_msg.uri) {
             match plugin_service.peer.get() {
                 Some(peer) => {
-                    tracing::info!("Notifying tool list changed from {}", ctx.plugin_name);
-                    ctx.handle.block_on(peer.notify_resource_list_changed()).map_err(Error::from)
+                    tracing::info!("Notifying resource {} updated from {}", update_msg.uri, ctx.plugin_name);
+                    Handle::current().block_on(peer.notify_resource_updated(update_msg)).map_err(Error::from)
                 },
                 None => Ok(()),
             }
-        });
-
-        host_fn!(notify_resource_updated(ctx: PluginServiceContext; update_msg: Json<ResourceUpdatedNotificationParam>) {
-            let update_msg = update_msg.into_inner();
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
-            if plugin_service.subscriptions.contains(&update_msg.uri) {
-                match plugin_service.peer.get() {
-                    Some(peer) => {
-                        tracing::info!("Notifying resource {} updated from {}", update_msg.uri, ctx.plugin_name);
-                        ctx.handle.block_on(peer.notify_resource_updated(update_msg)).map_err(Error::from)
-                    },
-                    None => Ok(()),
-                }
-            }
-            else {
-                Ok(())
-            }
-        });
+        }
+        else {
+            Ok(())
+        }
+    });
 
-        // Declares a host function `notify_tool_list_changed` that plugins can call
-        host_fn!(notify_tool_list_changed(ctx: PluginServiceContext;) {
-            let ctx = ctx.get()?.lock().unwrap().clone();
-            let plugin_service = PluginService::get(ctx.plugin_service_id).ok_or_else(|| {
-                anyhow::anyhow!("PluginService with ID {:?} not found", ctx.plugin_service_id)
-            })?;
+    // Declares a host function `notify_tool_list_changed` that plugins can call
+    host_fn!(notify_tool_list_changed(ctx: PluginServiceContext;) {
+        let ctx = ctx.get()?.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let plugin_service = PluginService::current()?;
 
-            match plugin_service.peer.get() {
-                Some(peer) => {
-                    tracing::info!("Notifying tool list changed from {}", ctx.plugin_name);
-                    ctx.handle.block_on(peer.notify_tool_list_changed()).map_err(Error::from)
-                },
-                None => Ok(()),
+        match plugin_service.peer.get() {
+            Some(peer) => {
+                tracing::info!("Notifying tool list changed from {}", ctx.plugin_name);
+                Handle::current().block_on(peer.notify_tool_list_changed()).map_err(Error::from)
+            },
+            None => Ok(()),
+        }
+    });
+
+    let runtime_cfg = plugin_cfg.runtime_config.as_ref();
+    let mut manifest = Manifest::new([Wasm::data(wasm_content)]);
+    if let Some(runtime_cfg) = runtime_cfg {
+        tracing::info!("runtime_cfg: {runtime_cfg:?}");
+        if let Some(hosts) = &runtime_cfg.allowed_hosts {
+            for host in hosts {
+                manifest = manifest.with_allowed_host(host);
             }
-        });
+        }
+        if let Some(paths) = &runtime_cfg.allowed_paths {
+            for path in paths {
+                // extism mounts host paths prefixed with `ro:` read-only
+                let host = match path.mode {
+                    PathMode::Ro => format!("ro:{}", path.host),
+                    PathMode::Rw => path.host.clone(),
+                };
+                manifest = manifest.with_allowed_path(host, path.guest());
+            }
+        }
 
-        for (plugin_name, plugin_cfg) in &self.config.plugins {
-            let wasm_content = match WASM_CONTENT_CACHE.entry(plugin_name.clone()) {
-                Entry::Occupied(entry) => entry.get().clone(),
-                Entry::Vacant(entry) => {
-                    let content = match plugin_cfg.url.scheme() {
-                        "file" => tokio::fs::read(plugin_cfg.url.path()).await?,
-                        "http" => reqwest_client
-                            .get_or_init(|| async { reqwest::Client::new() })
-                            .await
-                            .get(plugin_cfg.url.as_str())
-                            .send()
-                            .await?
-                            .bytes()
-                            .await?
-                            .to_vec(),
-                        "https" => reqwest_client
-                            .get_or_init(|| async { reqwest::Client::new() })
-                            .await
-                            .get(plugin_cfg.url.as_str())
-                            .add_auth(&self.config.auths, &plugin_cfg.url)
-                            .send()
-                            .await?
-                            .bytes()
-                            .await?
-                            .to_vec(),
-                        "oci" => {
-                            let image_reference =
-                                plugin_cfg.url.as_str().strip_prefix("oci://").unwrap();
-                            let target_file_path = "/plugin.wasm";
-                            let mut hasher = Sha256::new();
-                            hasher.update(image_reference);
-                            let hash = hasher.finalize();
-                            let short_hash = &hex::encode(hash)[..7];
-                            let cache_dir = dirs::cache_dir()
-                                .map(|mut path| {
-                                    path.push("hyper-mcp");
-                                    path
-                                })
-                                .unwrap();
-                            std::fs::create_dir_all(&cache_dir)?;
-
-                            let local_output_path =
-                                cache_dir.join(format!("{plugin_name}-{short_hash}.wasm"));
-                            let local_output_path = local_output_path.to_str().unwrap();
-
-                            if let Err(e) = pull_and_extract_oci_image(
-                                cli,
-                                oci_client
-                                    .get_or_init(|| async {
-                                        oci_client::Client::new(
-                                            oci_client::client::ClientConfig::default(),
-                                        )
-                                    })
-                                    .await,
-                                image_reference,
-                                target_file_path,
-                                local_output_path,
-                            )
-                            .await
-                            {
-                                tracing::error!("Error pulling oci plugin: {e}");
-                                return Err(anyhow::anyhow!("Failed to pull OCI plugin: {e}"));
-                            }
-                            tracing::info!("cache plugin `{plugin_name}` to : {local_output_path}");
-                            tokio::fs::read(local_output_path).await?
-                        }
-                        "s3" => {
-                            let bucket = plugin_cfg.url.host_str().ok_or_else(|| {
-                                anyhow::anyhow!("S3 URL must have a valid bucket name in the host")
-                            })?;
-                            let key = plugin_cfg.url.path().trim_start_matches('/');
-                            match s3_client
-                                .get_or_init(|| async {
-                                    aws_sdk_s3::Client::new(&aws_config::load_from_env().await)
-                                })
-                                .await
-                                .get_object()
-                                .bucket(bucket)
-                                .key(key)
-                                .send()
-                                .await
-                            {
-                                Ok(response) => match response.body.collect().await {
-                                    Ok(body) => body.to_vec(),
-                                    Err(e) => {
-                                        tracing::error!("Failed to collect S3 object body: {e}");
-                                        return Err(anyhow::anyhow!(
-                                            "Failed to collect S3 object body: {e}"
-                                        ));
-                                    }
-                                },
-                                Err(e) => {
-                                    tracing::error!("Failed to get object from S3: {e}");
-                                    return Err(anyhow::anyhow!(
-                                        "Failed to get object from S3: {e}"
-                                    ));
-                                }
-                            }
-                        }
-                        unsupported => {
-                            tracing::error!("Unsupported plugin URL scheme: {unsupported}");
-                            return Err(anyhow::anyhow!(
-                                "Unsupported plugin URL scheme: {unsupported}"
-                            ));
-                        }
-                    };
-                    entry.insert(content.clone());
-                    content
-                }
-            };
-            let mut manifest = Manifest::new([Wasm::data(wasm_content)]);
-            if let Some(runtime_cfg) = &plugin_cfg.runtime_config {
-                tracing::info!("runtime_cfg: {runtime_cfg:?}");
-                if let Some(hosts) = &runtime_cfg.allowed_hosts {
-                    for host in hosts {
-                        manifest = manifest.with_allowed_host(host);
-                    }
-                }
-                if let Some(paths) = &runtime_cfg.allowed_paths {
-                    for path in paths {
-                        // path will be available in the plugin with exact same path
-                        manifest = manifest.with_allowed_path(path.clone(), path.clone());
-                    }
-                }
+        // Add plugin configurations if present
+        for (key, value) in env_vars {
+            manifest = manifest.with_config_key(key, value);
+        }
 
-                // Add plugin configurations if present
-                if let Some(env_vars) = &runtime_cfg.env_vars {
-                    for (key, value) in env_vars {
-                        let resolved_value = check_env_reference(value);
-                        manifest = manifest.with_config_key(key, &resolved_value);
-                    }
+        if let Some(memory_limit) = &runtime_cfg.memory_limit {
+            match ByteSize::from_str(memory_limit) {
+                Ok(b) => {
+                    // Wasm page size 64KiB, convert to number of pages
+                    let num_pages = b.as_u64() / (64 * 1024);
+                    manifest = manifest.with_memory_max(num_pages as u32);
                 }
-
-                if let Some(memory_limit) = &runtime_cfg.memory_limit {
-                    match ByteSize::from_str(memory_limit) {
-                        Ok(b) => {
-                            // Wasm page size 64KiB, convert to number of pages
-                            let num_pages = b.as_u64() / (64 * 1024);
-                            manifest = manifest.with_memory_max(num_pages as u32);
-                        }
-                        Err(e) => {
-                            tracing::error!(
-                                "Failed to parse memory_limit '{memory_limit}': {e}. Using default memory limit."
-                            );
-                        }
-                    }
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to parse memory_limit '{memory_limit}': {e}. Using default memory limit."
+                    );
                 }
             }
-            let extism_plugin = extism::Plugin::new(
-                &manifest,
-                [
-                    Function::new(
-                        "create_elicitation",
-                        [extism::PTR],
-                        [extism::PTR],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        create_elicitation,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "create_message",
-                        [extism::PTR],
-                        [extism::PTR],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        create_message,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "list_roots",
-                        [],
-                        [extism::PTR],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        list_roots,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "notify_logging_message",
-                        [extism::PTR],
-                        [],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        notify_logging_message,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "notify_progress",
-                        [extism::PTR],
-                        [],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        notify_progress,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "notify_prompt_list_changed",
-                        [],
-                        [],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        notify_prompt_list_changed,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "notify_resource_list_changed",
-                        [],
-                        [],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        notify_resource_list_changed,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "notify_resource_updated",
-                        [extism::PTR],
-                        [],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        notify_resource_updated,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                    Function::new(
-                        "notify_tool_list_changed",
-                        [],
-                        [],
-                        UserData::new(PluginServiceContext {
-                            plugin_service_id: self.id,
-                            handle: Handle::current(),
-                            plugin_name: plugin_name.to_string(),
-                        }),
-                        notify_tool_list_changed,
-                    )
-                    .with_namespace(EXTISM_USER_MODULE),
-                ],
-                true,
-            )
-            .unwrap();
-
-            let plugin_id = extism_plugin.id;
-            let plugin: Box<dyn Plugin> = if extism_plugin.function_exists("call")
-                && extism_plugin.function_exists("describe")
-            {
-                Box::new(PluginV1::new(
-                    plugin_name.clone(),
-                    Arc::new(Mutex::new(extism_plugin)),
-                ))
-            } else {
-                Box::new(PluginV2::new(
-                    plugin_name.clone(),
-                    Arc::new(Mutex::new(extism_plugin)),
-                ))
-            };
+        }
 
-            names.insert(plugin_id, plugin_name.clone());
-            plugins.insert(plugin_name.clone(), plugin);
-            tracing::info!("Loaded plugin {plugin_name}");
+        if let Some(call_timeout) = runtime_cfg.call_timeout {
+            manifest = manifest.with_timeout(call_timeout);
         }
-        self.names.set(names).expect("Names already set");
-        self.plugins.set(plugins).expect("Plugins already set");
-        Ok(())
     }
-
-    pub fn logging_level(&self) -> LoggingLevel {
-        *self.logging_level.read().unwrap()
+    let mut builder = PluginBuilder::new(&manifest)
+        .with_wasi(true)
+        .with_functions([
+            Function::new(
+                "create_elicitation",
+                [extism::PTR],
+                [extism::PTR],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                create_elicitation,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "create_message",
+                [extism::PTR],
+                [extism::PTR],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                create_message,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "list_roots",
+                [],
+                [extism::PTR],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                list_roots,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "notify_logging_message",
+                [extism::PTR],
+                [],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                notify_logging_message,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "notify_progress",
+                [extism::PTR],
+                [],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                notify_progress,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "notify_prompt_list_changed",
+                [],
+                [],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                notify_prompt_list_changed,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "notify_resource_list_changed",
+                [],
+                [],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                notify_resource_list_changed,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "notify_resource_updated",
+                [extism::PTR],
+                [],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                notify_resource_updated,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+            Function::new(
+                "notify_tool_list_changed",
+                [],
+                [],
+                UserData::new(PluginServiceContext {
+                    plugin_name: plugin_name.to_string(),
+                }),
+                notify_tool_list_changed,
+            )
+            .with_namespace(EXTISM_USER_MODULE),
+        ]);
+    let inject_auths = runtime_cfg
+        .and_then(|cfg| cfg.inject_auths)
+        .unwrap_or_default();
+    if inject_auths || recorder.is_some() {
+        builder = builder.with_functions(http_functions(plugin_name, recorder, inject_auths));
     }
-
-    pub fn set_logging_level(&self, level: LoggingLevel) {
-        *self.logging_level.write().unwrap() = level;
+    if let Some(fuel_limit) = runtime_cfg.and_then(|cfg| cfg.fuel_limit) {
+        builder = builder.with_fuel_limit(fuel_limit);
     }
+    CompiledPlugin::new(builder)
 }
 
 impl ServerHandler for PluginService {
@@ -724,6 +1021,7 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, McpError> {
         tracing::info!("got tools/call request {:?}", request);
+        let config = self.config();
         let (plugin_name, tool_name) = match parse_namespaced_name(request.name.to_string()) {
             Ok((plugin_name, tool_name)) => (plugin_name, tool_name),
             Err(e) => {
@@ -733,7 +1031,7 @@ impl ServerHandler for PluginService {
                 ));
             }
         };
-        let plugin_config = match self.config.plugins.get(&plugin_name) {
+        let plugin_config = match config.plugins.get(&plugin_name) {
             Some(config) => config,
             None => {
                 return Err(McpError::method_not_found::<CallToolRequestMethod>());
@@ -754,12 +1052,7 @@ impl ServerHandler for PluginService {
             arguments: request.arguments,
         };
 
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let plugins = self.plugins();
 
         let Some(plugin) = plugins.get(&plugin_name) else {
             return Err(McpError::method_not_found::<CallToolRequestMethod>());
@@ -773,6 +1066,7 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<CompleteResult, McpError> {
         tracing::info!("got completion/complete request {:?}", request);
+        let config = self.config();
         let (plugin_name, request) = match request.r#ref {
             Reference::Prompt(PromptReference { name, title }) => {
                 let (plugin_name, prompt_name) = match parse_namespaced_name(name.to_string()) {
@@ -784,7 +1078,7 @@ impl ServerHandler for PluginService {
                         ));
                     }
                 };
-                let plugin_config = match self.config.plugins.get(&plugin_name) {
+                let plugin_config = match config.plugins.get(&plugin_name) {
                     Some(config) => config,
                     None => {
                         return Err(McpError::method_not_found::<CompleteRequestMethod>());
@@ -821,7 +1115,7 @@ impl ServerHandler for PluginService {
                         ));
                     }
                 };
-                let plugin_config = match self.config.plugins.get(&plugin_name) {
+                let plugin_config = match config.plugins.get(&plugin_name) {
                     Some(config) => config,
                     None => {
                         return Err(McpError::method_not_found::<CompleteRequestMethod>());
@@ -847,12 +1141,7 @@ impl ServerHandler for PluginService {
             }
         };
 
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let plugins = self.plugins();
 
         let Some(plugin) = plugins.get(&plugin_name) else {
             return Err(McpError::method_not_found::<CallToolRequestMethod>());
@@ -881,6 +1170,7 @@ impl ServerHandler for PluginService {
                 .enable_tools()
                 .enable_tool_list_changed()
                 .build(),
+            instructions: plugin_instructions(&self.plugins()),
 
             ..Default::default()
         }
@@ -892,6 +1182,7 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<GetPromptResult, McpError> {
         tracing::info!("got prompts/get request {:?}", request);
+        let config = self.config();
         let (plugin_name, prompt_name) = match parse_namespaced_name(request.name.to_string()) {
             Ok((plugin_name, prompt_name)) => (plugin_name, prompt_name),
             Err(e) => {
@@ -901,7 +1192,7 @@ impl ServerHandler for PluginService {
                 ));
             }
         };
-        let plugin_config = match self.config.plugins.get(&plugin_name) {
+        let plugin_config = match config.plugins.get(&plugin_name) {
             Some(config) => config,
             None => {
                 return Err(McpError::method_not_found::<GetPromptRequestMethod>());
@@ -922,12 +1213,7 @@ impl ServerHandler for PluginService {
             arguments: request.arguments,
         };
 
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let plugins = self.plugins();
 
         let Some(plugin) = plugins.get(&plugin_name) else {
             return Err(McpError::method_not_found::<GetPromptRequestMethod>());
@@ -941,20 +1227,16 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<ListPromptsResult, McpError> {
         tracing::info!("got prompts/list request {:?}", request);
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let config = self.config();
+        let plugins = self.plugins();
 
         let mut list_prompts_result = ListPromptsResult::default();
 
-        for (plugin_name, plugin) in plugins.iter() {
+        for (plugin_name, plugin) in plugins.iter().filter(|(_, plugin)| !plugin.is_disabled()) {
             let plugin_prompts = plugin
                 .list_prompts(request.clone(), context.clone())
                 .await?;
-            let plugin_cfg = self.config.plugins.get(plugin_name).ok_or_else(|| {
+            let plugin_cfg = config.plugins.get(plugin_name).ok_or_else(|| {
                 McpError::internal_error(
                     format!("Plugin configuration not found for {plugin_name}"),
                     None,
@@ -989,20 +1271,16 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<ListResourcesResult, McpError> {
         tracing::info!("got resources/list request {:?}", request);
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let config = self.config();
+        let plugins = self.plugins();
 
         let mut list_resources_result = ListResourcesResult::default();
 
-        for (plugin_name, plugin) in plugins.iter() {
+        for (plugin_name, plugin) in plugins.iter().filter(|(_, plugin)| !plugin.is_disabled()) {
             let plugin_resources = plugin
                 .list_resources(request.clone(), context.clone())
                 .await?;
-            let plugin_cfg = self.config.plugins.get(plugin_name).ok_or_else(|| {
+            let plugin_cfg = config.plugins.get(plugin_name).ok_or_else(|| {
                 McpError::internal_error(
                     format!("Plugin configuration not found for {plugin_name}"),
                     None,
@@ -1040,20 +1318,16 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<ListResourceTemplatesResult, McpError> {
         tracing::info!("got resources/templates/list request {:?}", request);
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let config = self.config();
+        let plugins = self.plugins();
 
         let mut list_resource_templates_result = ListResourceTemplatesResult::default();
 
-        for (plugin_name, plugin) in plugins.iter() {
+        for (plugin_name, plugin) in plugins.iter().filter(|(_, plugin)| !plugin.is_disabled()) {
             let plugin_resource_templates = plugin
                 .list_resource_templates(request.clone(), context.clone())
                 .await?;
-            let plugin_cfg = self.config.plugins.get(plugin_name).ok_or_else(|| {
+            let plugin_cfg = config.plugins.get(plugin_name).ok_or_else(|| {
                 McpError::internal_error(
                     format!("Plugin configuration not found for {plugin_name}"),
                     None,
@@ -1094,18 +1368,14 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<ListToolsResult, McpError> {
         tracing::info!("got tools/list request {:?}", request);
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let config = self.config();
+        let plugins = self.plugins();
 
         let mut list_tools_result = ListToolsResult::default();
 
-        for (plugin_name, plugin) in plugins.iter() {
+        for (plugin_name, plugin) in plugins.iter().filter(|(_, plugin)| !plugin.is_disabled()) {
             let plugin_tools = plugin.list_tools(request.clone(), context.clone()).await?;
-            let plugin_cfg = self.config.plugins.get(plugin_name).ok_or_else(|| {
+            let plugin_cfg = config.plugins.get(plugin_name).ok_or_else(|| {
                 McpError::internal_error(
                     format!("Plugin configuration not found for {plugin_name}"),
                     None,
@@ -1143,11 +1413,8 @@ impl ServerHandler for PluginService {
 
     async fn on_roots_list_changed(&self, context: NotificationContext<RoleServer>) -> () {
         tracing::info!("got roots/list_changed notification");
-        let Some(plugins) = self.plugins.get() else {
-            tracing::error!("Plugins not initialized");
-            return;
-        };
-        for (plugin_name, plugin) in plugins.iter() {
+        let plugins = self.plugins();
+        for (plugin_name, plugin) in plugins.iter().filter(|(_, plugin)| !plugin.is_disabled()) {
             if let Err(e) = plugin.on_roots_list_changed(context.clone()).await {
                 tracing::error!("Failed to notify plugin {plugin_name} of roots list change: {e}");
             }
@@ -1160,6 +1427,7 @@ impl ServerHandler for PluginService {
         context: RequestContext<RoleServer>,
     ) -> Result<ReadResourceResult, McpError> {
         tracing::info!("got resources/read request {:?}", request);
+        let config = self.config();
         let (plugin_name, resource_uri) = match parse_namespaced_uri(request.uri.to_string()) {
             Ok((plugin_name, resource_uri)) => (plugin_name, resource_uri),
             Err(e) => {
@@ -1169,7 +1437,7 @@ impl ServerHandler for PluginService {
                 ));
             }
         };
-        let plugin_config = match self.config.plugins.get(&plugin_name) {
+        let plugin_config = match config.plugins.get(&plugin_name) {
             Some(config) => config,
             None => {
                 return Err(McpError::method_not_found::<ReadResourceRequestMethod>());
@@ -1189,12 +1457,7 @@ impl ServerHandler for PluginService {
             uri: resource_uri.clone(),
         };
 
-        let Some(plugins) = self.plugins.get() else {
-            return Err(McpError::internal_error(
-                "Plugins not initialized".to_string(),
-                None,
-            ));
-        };
+        let plugins = self.plugins();
 
         let Some(plugin) = plugins.get(&plugin_name) else {
             return Err(McpError::method_not_found::<GetPromptRequestMethod>());
@@ -1239,6 +1502,7 @@ mod tests {
         model::ClientInfo,
         service::{RoleClient, RunningService, Service, serve_client, serve_server},
     };
+    use sha2::{Digest, Sha256};
     use std::{
         path::PathBuf,
         sync::atomic::{AtomicUsize, Ordering},
@@ -1299,6 +1563,7 @@ mod tests {
 
     fn create_test_cli() -> Cli {
         crate::Cli {
+            command: None,
             config_file: None,
             transport: "stdio".to_string(),
             bind_address: "127.0.0.1:3001".to_string(),
@@ -1309,6 +1574,17 @@ mod tests {
             cert_issuer: None,
             cert_email: None,
             cert_url: None,
+            cosign_key: None,
+            locked: false,
+            offline: false,
+            strict_capabilities: false,
+            strict_env: false,
+            ca_certs: vec![],
+            https_proxy: None,
+            http_proxy: None,
+            no_proxy: None,
+            insecure_registries: vec![],
+            record_hosts: None,
         }
     }
 
@@ -1326,24 +1602,84 @@ mod tests {
 
     fn create_test_service(config: Config) -> PluginService {
         PluginService(Arc::new(PluginServiceInner {
-            config,
+            config: RwLock::new(Arc::new(config)),
             id: Uuid::new_v4(),
             logging_level: RwLock::new(LoggingLevel::Info),
-            names: SetOnce::new(),
             peer: SetOnce::new(),
-            plugins: SetOnce::new(),
+            plugins: RwLock::new(Arc::default()),
+            reload_lock: AsyncMutex::new(()),
             subscriptions: DashSet::new(),
         }))
     }
 
-    async fn create_test_pair<S, C>(
-        service: S,
-        client: C,
-    ) -> (RunningService<RoleServer, S>, RunningService<RoleClient, C>)
-    where
-        S: Service<RoleServer>,
-        C: Service<RoleClient>,
-    {
+    /// A small MCP server standing in for an upstream server.
+    #[derive(Clone)]
+    struct StandInServer;
+
+    impl ServerHandler for StandInServer {
+        async fn call_tool(
+            &self,
+            request: CallToolRequestParam,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<CallToolResult, McpError> {
+            let text = request
+                .arguments
+                .and_then(|args| args.get("text").cloned())
+                .unwrap_or_default();
+            Ok(CallToolResult::success(vec![Content::text(
+                text.as_str().unwrap_or_default(),
+            )]))
+        }
+
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo {
+                capabilities: ServerCapabilities::builder().enable_tools().build(),
+                ..Default::default()
+            }
+        }
+
+        async fn list_tools(
+            &self,
+            _request: Option<PaginatedRequestParam>,
+            _context: RequestContext<RoleServer>,
+        ) -> Result<ListToolsResult, McpError> {
+            let schema = Arc::new(serde_json::Map::new());
+            Ok(ListToolsResult {
+                tools: vec![
+                    Tool::new("echo", "Echoes its text argument", Arc::clone(&schema)),
+                    Tool::new("hidden", "Skipped by config", schema),
+                ],
+                next_cursor: None,
+            })
+        }
+    }
+
+    /// Serves [`StandInServer`] over streamable HTTP and returns its address.
+    async fn start_stand_in_server() -> std::net::SocketAddr {
+        use rmcp::transport::streamable_http_server::{
+            StreamableHttpService, session::local::LocalSessionManager,
+        };
+
+        let service = StreamableHttpService::new(
+            || Ok(StandInServer),
+            LocalSessionManager::default().into(),
+            Default::default(),
+        );
+        let router = axum::Router::new().nest_service("/mcp", service);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    async fn create_test_pair<S, C>(
+        service: S,
+        client: C,
+    ) -> (RunningService<RoleServer, S>, RunningService<RoleClient, C>)
+    where
+        S: Service<RoleServer>,
+        C: Service<RoleClient>,
+    {
         let (srv_io, cli_io) = duplex(64 * 1024);
         tokio::try_join!(
             async {
@@ -1360,6 +1696,13 @@ mod tests {
         .expect("Failed to create test pair")
     }
 
+    fn registered_plugin(plugin_name: &PluginName) -> Arc<RegisteredPlugin> {
+        let registry = PLUGIN_REGISTRY
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(registry[plugin_name].plugin.get().unwrap())
+    }
+
     fn get_test_wasm_path() -> PathBuf {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         path.push("examples");
@@ -1476,6 +1819,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_unwrap)]
     fn test_parse_namespaced_tool_name_empty_parts() {
         let tool_name = "-tool".to_string();
         let result = parse_namespaced_name(tool_name);
@@ -1628,80 +1972,706 @@ mod tests {
     // PluginService creation tests
 
     #[tokio::test]
-    async fn test_plugin_service_creation_empty_config() {
+    async fn test_plugin_service_creation_empty_config() {
+        let config_content = r#"
+plugins: {}
+"#;
+        let (_temp_dir, config_path) = create_temp_config_file(config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+
+        let result = PluginService::new(&cli).await;
+        assert!(
+            result.is_ok(),
+            "Should create service with empty plugin config"
+        );
+
+        let service = result.unwrap();
+        let plugins = service.plugins();
+        assert!(plugins.is_empty(), "Should have no plugins loaded");
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_creation_with_file_plugin() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let config_content = format!(
+            r#"
+plugins:
+  time_plugin:
+    url: "file://{}"
+    runtime_config:
+      memory_limit: "1MB"
+      env_vars:
+        TEST_MODE: "true"
+"#,
+            wasm_path.display()
+        );
+
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+
+        let result = PluginService::new(&cli).await;
+        assert!(
+            result.is_ok(),
+            "Should create service with valid WASM plugin"
+        );
+
+        let service = result.unwrap();
+        let plugins = service.plugins();
+        assert_eq!(plugins.len(), 1, "Should have one plugin loaded");
+        assert!(plugins.contains_key(&PluginName::from_str("time_plugin").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_sessions_share_compiled_plugin() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let config_content = format!(
+            r#"
+plugins:
+  shared_time_plugin:
+    url: "file://{}"
+"#,
+            wasm_path.display()
+        );
+
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+        let plugin_name = PluginName::from_str("shared_time_plugin").unwrap();
+
+        let _first = PluginService::new(&cli).await.unwrap();
+        let compiled = registered_plugin(&plugin_name);
+        let _second = PluginService::new(&cli).await.unwrap();
+        assert!(
+            Arc::ptr_eq(&compiled, &registered_plugin(&plugin_name)),
+            "Second session should reuse the compiled plugin"
+        );
+
+        // A different config for the same plugin name is compiled again
+        let config_content = format!(
+            r#"
+plugins:
+  shared_time_plugin:
+    url: "file://{}"
+    runtime_config:
+      memory_limit: "2MB"
+"#,
+            wasm_path.display()
+        );
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        cli.config_file = Some(config_path);
+        let _third = PluginService::new(&cli).await.unwrap();
+        assert!(
+            !Arc::ptr_eq(&compiled, &registered_plugin(&plugin_name)),
+            "Changed config should trigger a new compilation"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_reload_rereads_env_var_secrets() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let (temp_dir, config_path) = create_temp_config_file("").await.unwrap();
+        let secret_path = temp_dir.path().join("api_key");
+        std::fs::write(&secret_path, "before").unwrap();
+        let config_content = format!(
+            r#"
+plugins:
+  secret_reading_plugin:
+    url: "file://{0}"
+    runtime_config:
+      env_vars:
+        API_KEY:
+          type: file
+          path: "{1}"
+  secret_free_plugin:
+    url: "file://{0}"
+"#,
+            wasm_path.display(),
+            secret_path.display()
+        );
+        tokio::fs::write(&config_path, config_content)
+            .await
+            .unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path.clone());
+
+        let service = PluginService::new(&cli).await.unwrap();
+        let reading = PluginName::try_from("secret_reading_plugin").unwrap();
+        let free = PluginName::try_from("secret_free_plugin").unwrap();
+        let before = (registered_plugin(&reading), registered_plugin(&free));
+
+        let config = load_config(&config_path, false).await.unwrap();
+        let rotated = forget_plugins_with_rotated_secrets().await;
+        assert!(!rotated.contains(&reading), "{rotated:?}");
+        service.reload(&cli, config, &rotated).await.unwrap();
+        assert!(
+            Arc::ptr_eq(&before.0, &registered_plugin(&reading)),
+            "Plugins whose secrets did not change should stay compiled"
+        );
+
+        std::fs::write(&secret_path, "after").unwrap();
+        let config = load_config(&config_path, false).await.unwrap();
+        let rotated = forget_plugins_with_rotated_secrets().await;
+        assert!(rotated.contains(&reading), "{rotated:?}");
+        service.reload(&cli, config, &rotated).await.unwrap();
+
+        assert!(
+            !Arc::ptr_eq(&before.0, &registered_plugin(&reading)),
+            "Plugins reading secrets should be compiled again"
+        );
+        assert!(
+            Arc::ptr_eq(&before.1, &registered_plugin(&free)),
+            "Other plugins should stay compiled"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_reload_only_changed_plugins() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let config_content = format!(
+            r#"
+plugins:
+  reload_kept_plugin:
+    url: "file://{0}"
+  reload_changed_plugin:
+    url: "file://{0}"
+"#,
+            wasm_path.display()
+        );
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path.clone());
+
+        let (server, client) =
+            create_test_pair(PluginService::new(&cli).await.unwrap(), TestClient::new()).await;
+        let kept = PluginName::try_from("reload_kept_plugin").unwrap();
+        let changed = PluginName::try_from("reload_changed_plugin").unwrap();
+        let added = PluginName::try_from("reload_added_plugin").unwrap();
+        let before = server.service().plugins();
+
+        let config_content = format!(
+            r#"
+plugins:
+  reload_kept_plugin:
+    url: "file://{0}"
+  reload_changed_plugin:
+    url: "file://{0}"
+    runtime_config:
+      skip_tools: ["time"]
+  reload_added_plugin:
+    url: "file://{0}"
+"#,
+            wasm_path.display()
+        );
+        tokio::fs::write(&config_path, config_content)
+            .await
+            .unwrap();
+        let config = load_config(&config_path, false).await.unwrap();
+        server
+            .service()
+            .reload(&cli, config, &HashSet::new())
+            .await
+            .unwrap();
+
+        let after = server.service().plugins();
+        assert!(
+            Arc::ptr_eq(&before[&kept], &after[&kept]),
+            "Unchanged plugin should not be reloaded"
+        );
+        assert!(
+            !Arc::ptr_eq(&before[&changed], &after[&changed]),
+            "Changed plugin should be reloaded"
+        );
+        assert!(after.contains_key(&added), "Added plugin should be loaded");
+
+        let ctx = create_test_ctx(&server);
+        let tools = server.service().list_tools(None, ctx).await.unwrap();
+        let names: Vec<&str> = tools.tools.iter().map(|tool| tool.name.as_ref()).collect();
+        assert!(names.contains(&"reload_kept_plugin-time"));
+        assert!(names.contains(&"reload_added_plugin-time"));
+        assert!(
+            !names.contains(&"reload_changed_plugin-time"),
+            "New skip_tools should apply"
+        );
+
+        let notified = tokio::time::timeout(Duration::from_secs(5), async {
+            while client.service().get_tool_list_changed_count() == 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        assert!(
+            notified.is_ok(),
+            "Client should be told the tool list changed"
+        );
+
+        // Removing plugins drops them from the session
+        let config_content = format!(
+            r#"
+plugins:
+  reload_kept_plugin:
+    url: "file://{0}"
+"#,
+            wasm_path.display()
+        );
+        tokio::fs::write(&config_path, config_content)
+            .await
+            .unwrap();
+        let config = load_config(&config_path, false).await.unwrap();
+        server
+            .service()
+            .reload(&cli, config, &HashSet::new())
+            .await
+            .unwrap();
+        let after = server.service().plugins();
+        assert_eq!(after.len(), 1);
+        assert!(Arc::ptr_eq(&before[&kept], &after[&kept]));
+
+        assert_ok!(server.cancel().await);
+        assert_ok!(client.cancel().await);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_pool_checkout_and_return() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let config_content = format!(
+            r#"
+plugins:
+  pooled_time_plugin:
+    url: "file://{}"
+    runtime_config:
+      max_instances: 2
+"#,
+            wasm_path.display()
+        );
+
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+        let plugin_name = PluginName::from_str("pooled_time_plugin").unwrap();
+
+        let _service = PluginService::new(&cli).await.unwrap();
+        let compiled = Arc::clone(&registered_plugin(&plugin_name).compiled);
+        let pool = Arc::new(PluginPool::new(compiled, 2));
+
+        let first = pool.checkout().await.unwrap();
+        let second = tokio::time::timeout(Duration::from_secs(5), pool.checkout())
+            .await
+            .expect("Second checkout should not wait with max_instances: 2")
+            .unwrap();
+        assert_ne!(
+            first.id, second.id,
+            "Concurrent checkouts get distinct instances"
+        );
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), pool.checkout())
+                .await
+                .is_err(),
+            "Third checkout should wait for an instance to be returned"
+        );
+
+        // Returned instances are reused
+        let first_id = first.id;
+        drop(first);
+        let mut third = pool.checkout().await.unwrap();
+        assert_eq!(third.id, first_id);
+
+        // Unhealthy instances are discarded and replaced
+        third.mark_unhealthy();
+        drop(third);
+        let fourth = pool.checkout().await.unwrap();
+        assert_ne!(fourth.id, first_id);
+        assert_eq!(pool.restarts(), 1);
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_pools_share_max_instances() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let config_content = format!(
+            "plugins:\n  shared_pool_plugin:\n    url: \"file://{}\"\n",
+            wasm_path.display()
+        );
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+        let plugin_name = PluginName::from_str("shared_pool_plugin").unwrap();
+
+        let _service = PluginService::new(&cli).await.unwrap();
+        let registered = registered_plugin(&plugin_name);
+        let pool = |registered: &RegisteredPlugin| {
+            Arc::new(
+                PluginPool::new(Arc::clone(&registered.compiled), 1)
+                    .with_permits(Arc::clone(&registered.permits)),
+            )
+        };
+        let (first_session, other_session) = (pool(&registered), pool(&registered));
+
+        let first = first_session.checkout().await.unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), other_session.checkout())
+                .await
+                .is_err(),
+            "Other sessions should wait while the only instance is in use"
+        );
+        drop(first);
+        let other = tokio::time::timeout(Duration::from_secs(5), other_session.checkout())
+            .await
+            .expect("Other sessions get an instance once it is returned")
+            .unwrap();
+        drop(other);
+    }
+
+    // A module whose only export, `call_tool`, hits `unreachable`.
+    const TRAPPING_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type 0: () -> i32
+        0x03, 0x02, 0x01, 0x00, // function 0 has type 0
+        0x07, 0x0d, 0x01, 0x09, b'c', b'a', b'l', b'l', b'_', b't', b'o', b'o', b'l', 0x00,
+        0x00, // export function 0 as call_tool
+        0x0a, 0x05, 0x01, 0x03, 0x00, 0x00, 0x0b, // body: unreachable
+    ];
+
+    #[tokio::test]
+    async fn test_plugin_service_disables_crash_looping_plugin() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wasm_path = temp_dir.path().join("trap.wasm");
+        tokio::fs::write(&wasm_path, TRAPPING_WASM).await.unwrap();
+        let config_content = format!(
+            r#"
+plugins:
+  trap_plugin:
+    url: "file://{}"
+"#,
+            wasm_path.display()
+        );
+
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+
+        let (server, client) = create_test_pair(
+            PluginService::new(&cli).await.unwrap(),
+            ClientInfo::default(),
+        )
+        .await;
+
+        let request = CallToolRequestParam {
+            name: std::borrow::Cow::Borrowed("trap_plugin-crash"),
+            arguments: None,
+        };
+        for _ in 0..crate::plugin::MAX_CRASHES {
+            let ctx = create_test_ctx(&server);
+            let result = server.service().call_tool(request.clone(), ctx).await;
+            let Err(error) = result else {
+                panic!("Trapping call should fail: {result:?}");
+            };
+            assert!(
+                error.message.starts_with("Failed to call plugin"),
+                "{}",
+                error.message
+            );
+        }
+
+        // The circuit breaker has tripped, so the plugin is no longer called or listed
+        let ctx = create_test_ctx(&server);
+        let result = server.service().call_tool(request.clone(), ctx).await;
+        let Err(error) = result else {
+            panic!("Disabled plugin should not be called: {result:?}");
+        };
+        assert!(error.message.contains("disabled"), "{}", error.message);
+        assert_eq!(
+            error.data,
+            Some(serde_json::json!({ "plugin": "trap_plugin", "restarts": 5 }))
+        );
+
+        let ctx = create_test_ctx(&server);
+        let result = server.service().list_tools(None, ctx).await;
+        assert!(
+            result.is_ok_and(|tools| tools.tools.is_empty()),
+            "Disabled plugin should not be listed"
+        );
+
+        // New sessions share the circuit breaker instead of getting fresh crashes
+        let (other_server, other_client) = create_test_pair(
+            PluginService::new(&cli).await.unwrap(),
+            ClientInfo::default(),
+        )
+        .await;
+        let ctx = create_test_ctx(&other_server);
+        let result = other_server.service().call_tool(request, ctx).await;
+        let Err(error) = result else {
+            panic!("Plugin disabled by another session should not be called: {result:?}");
+        };
+        assert!(error.message.contains("disabled"), "{}", error.message);
+
+        assert_ok!(other_server.cancel().await);
+        assert_ok!(other_client.cancel().await);
+        assert_ok!(server.cancel().await);
+        assert_ok!(client.cancel().await);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_fetches_changed_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wasm_path = temp_dir.path().join("changing.wasm");
+        let config = |runtime_config: &str| {
+            format!(
+                "plugins:\n  changing_plugin:\n    url: \"file://{}\"\n{runtime_config}",
+                wasm_path.display()
+            )
+        };
+        let plugin_name = PluginName::from_str("changing_plugin").unwrap();
+        let mut cli = create_test_cli();
+
+        tokio::fs::write(&wasm_path, TRAPPING_WASM).await.unwrap();
+        let (_first_dir, config_path) = create_temp_config_file(&config("")).await.unwrap();
+        cli.config_file = Some(config_path);
+        let _first = PluginService::new(&cli).await.unwrap();
+        assert_eq!(
+            registered_plugin(&plugin_name).digest,
+            crate::fetch::sha256_digest(TRAPPING_WASM)
+        );
+
+        // Same URL, new content and a changed config, as when the config is reloaded
+        let module = wat::parse_str("(module)").unwrap();
+        tokio::fs::write(&wasm_path, &module).await.unwrap();
+        let (_second_dir, config_path) =
+            create_temp_config_file(&config("    runtime_config:\n      max_instances: 2\n"))
+                .await
+                .unwrap();
+        cli.config_file = Some(config_path);
+        let _second = PluginService::new(&cli).await.unwrap();
+        assert_eq!(
+            registered_plugin(&plugin_name).digest,
+            crate::fetch::sha256_digest(&module),
+            "Unpinned plugins should be fetched again"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_creation_with_nonexistent_file() {
         let config_content = r#"
-plugins: {}
+plugins:
+  missing_plugin:
+    url: "file:///nonexistent/path/plugin.wasm"
 "#;
+
         let (_temp_dir, config_path) = create_temp_config_file(config_content).await.unwrap();
         let mut cli = create_test_cli();
         cli.config_file = Some(config_path);
 
         let result = PluginService::new(&cli).await;
-        assert!(
-            result.is_ok(),
-            "Should create service with empty plugin config"
-        );
-
-        let service = result.unwrap();
-        let Some(plugins) = service.plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
-        assert!(plugins.is_empty(), "Should have no plugins loaded");
+        assert!(result.is_err(), "Should fail with nonexistent plugin file");
     }
 
     #[tokio::test]
-    async fn test_plugin_service_creation_with_file_plugin() {
+    async fn test_plugin_service_creation_with_pinned_digest() {
         let wasm_path = get_test_wasm_path();
         if !test_wasm_exists() {
             println!("Skipping test - WASM file not found at {wasm_path:?}");
             return;
         }
+        let digest = hex::encode(Sha256::digest(std::fs::read(&wasm_path).unwrap()));
 
         let config_content = format!(
             r#"
 plugins:
-  time_plugin:
+  pinned_time_plugin:
     url: "file://{}"
-    runtime_config:
-      memory_limit: "1MB"
-      env_vars:
-        TEST_MODE: "true"
+    sha256: "sha256:{digest}"
 "#,
             wasm_path.display()
         );
-
         let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
         let mut cli = create_test_cli();
         cli.config_file = Some(config_path);
-
-        let result = PluginService::new(&cli).await;
+        let service = PluginService::new(&cli).await;
         assert!(
-            result.is_ok(),
-            "Should create service with valid WASM plugin"
+            service.is_ok(),
+            "Matching digest should load: {:?}",
+            service.err()
         );
 
-        let service = result.unwrap();
-        let Some(plugins) = service.plugins.get() else {
-            panic!("Plugins should be initialized");
+        let config_content = format!(
+            r#"
+plugins:
+  mispinned_time_plugin:
+    url: "file://{}"
+    digest: "{}"
+"#,
+            wasm_path.display(),
+            "0".repeat(64)
+        );
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        cli.config_file = Some(config_path);
+        let Err(e) = PluginService::new(&cli).await else {
+            panic!("Mismatched digest should refuse to load");
         };
-        assert_eq!(plugins.len(), 1, "Should have one plugin loaded");
-        assert!(plugins.contains_key(&PluginName::from_str("time_plugin").unwrap()));
+        assert!(
+            e.to_string().contains("sha256 digest mismatch"),
+            "Error should explain the mismatch: {e}"
+        );
     }
 
     #[tokio::test]
-    async fn test_plugin_service_creation_with_nonexistent_file() {
+    async fn test_plugin_service_creation_offline() {
         let config_content = r#"
 plugins:
-  missing_plugin:
-    url: "file:///nonexistent/path/plugin.wasm"
+  remote_plugin:
+    url: "https://example.com/plugin.wasm"
 "#;
-
         let (_temp_dir, config_path) = create_temp_config_file(config_content).await.unwrap();
         let mut cli = create_test_cli();
         cli.config_file = Some(config_path);
+        cli.offline = true;
 
-        let result = PluginService::new(&cli).await;
-        assert!(result.is_err(), "Should fail with nonexistent plugin file");
+        let Err(e) = PluginService::new(&cli).await else {
+            panic!("Offline mode should refuse to download plugins");
+        };
+        assert!(
+            e.to_string().contains("running offline"),
+            "Unexpected error: {e}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_creation_checks_metadata() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (@custom "hyper-mcp" "{\"version\":\"1.0.0\",\"description\":\"Says hi\",\"abi_version\":2,\"required_env_vars\":[\"API_KEY\"]}")
+                (memory (export "memory") 1))"#,
+        )
+        .unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let wasm_path = temp_dir.path().join("greeter.wasm");
+        std::fs::write(&wasm_path, wasm).unwrap();
+        let url = Url::from_file_path(&wasm_path).unwrap();
+
+        let config_content = format!(
+            r#"
+plugins:
+  metadata_plugin:
+    url: "{url}"
+"#
+        );
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+        let Err(e) = PluginService::new(&cli).await else {
+            panic!("A plugin missing a required env var should not load");
+        };
+        assert!(
+            e.to_string()
+                .contains("Plugin metadata_plugin requires env_vars API_KEY"),
+            "Unexpected error: {e}"
+        );
+
+        let config_content = format!(
+            r#"
+plugins:
+  metadata_plugin:
+    url: "{url}"
+    runtime_config:
+      env_vars:
+        API_KEY: "secret"
+"#
+        );
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        cli.config_file = Some(config_path);
+        let service = PluginService::new(&cli).await.unwrap();
+        let instructions = ServerHandler::get_info(&service).instructions.unwrap();
+        assert!(
+            instructions.contains("- metadata_plugin 1.0.0: Says hi"),
+            "{instructions}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plugin_service_creation_locked() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+        let url = Url::from_file_path(&wasm_path).unwrap();
+        let config_content = format!(
+            r#"
+plugins:
+  locked_time_plugin:
+    url: "{url}"
+"#
+        );
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path.clone());
+        cli.locked = true;
+
+        let Err(e) = PluginService::new(&cli).await else {
+            panic!("Locked mode should refuse to run without a lockfile");
+        };
+        assert!(e.to_string().contains("lockfile"), "Unexpected error: {e}");
+
+        let lock_path = lockfile_path(&config_path);
+        let name = PluginName::try_from("locked_time_plugin").unwrap();
+        let mut lockfile = Lockfile::default();
+        lockfile.plugins.insert(
+            name.clone(),
+            crate::lockfile::LockedPlugin {
+                url: url.clone(),
+                digest: crate::fetch::sha256_digest(&std::fs::read(&wasm_path).unwrap()),
+                fetched_at: chrono::Utc::now(),
+            },
+        );
+        lockfile.save(&lock_path).await.unwrap();
+        let service = PluginService::new(&cli).await;
+        assert!(
+            service.is_ok(),
+            "Matching lockfile should load: {:?}",
+            service.err()
+        );
+
+        // The compiled plugin is reused now, but the lockfile is still enforced
+        lockfile.plugins.get_mut(&name).unwrap().digest = format!("sha256:{}", "0".repeat(64));
+        lockfile.save(&lock_path).await.unwrap();
+        let Err(e) = PluginService::new(&cli).await else {
+            panic!("Mismatched lockfile should refuse to load");
+        };
+        assert!(
+            e.to_string().contains("but is locked to"),
+            "Error should explain the mismatch: {e}"
+        );
     }
 
     #[tokio::test]
@@ -1742,6 +2712,8 @@ plugins:
         let config = Config {
             plugins: HashMap::new(),
             auths: Some(HashMap::new()),
+            network: None,
+            s3: None,
         };
         let service = create_test_service(config);
 
@@ -1753,6 +2725,7 @@ plugins:
     }
 
     #[tokio::test]
+    #[allow(clippy::collapsible_if)]
     async fn test_plugin_service_list_tools_with_plugin() {
         let wasm_path = get_test_wasm_path();
         if !test_wasm_exists() {
@@ -1779,9 +2752,7 @@ plugins:
         )
         .await;
         // Verify the service was created successfully
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded plugin");
 
         // Test the list_tools function
@@ -1868,6 +2839,7 @@ plugins:
     }
 
     #[tokio::test]
+    #[allow(clippy::needless_borrow)]
     async fn test_plugin_service_list_tools_with_skip_tools() {
         let wasm_path = get_test_wasm_path();
         if !test_wasm_exists() {
@@ -1896,9 +2868,7 @@ plugins:
             ClientInfo::default(),
         )
         .await;
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded plugin");
 
         // Test the list_tools function with skip_tools configuration
@@ -1943,7 +2913,13 @@ plugins:
 
         // Verify the plugin configuration includes skip_tools
         let plugin_name: PluginName = "time_plugin".parse().unwrap();
-        let plugin_config = server.service().config.plugins.get(&plugin_name).unwrap();
+        let plugin_config = server
+            .service()
+            .config()
+            .plugins
+            .get(&plugin_name)
+            .cloned()
+            .unwrap();
         let skip_tools = plugin_config
             .runtime_config
             .as_ref()
@@ -1971,6 +2947,8 @@ plugins:
         let config = Config {
             plugins: HashMap::new(),
             auths: Some(HashMap::new()),
+            network: None,
+            s3: None,
         };
         let (server, client) =
             create_test_pair(create_test_service(config), ClientInfo::default()).await;
@@ -2011,6 +2989,8 @@ plugins:
         let config = Config {
             plugins: HashMap::new(),
             auths: Some(HashMap::new()),
+            network: None,
+            s3: None,
         };
         let (server, client) =
             create_test_pair(create_test_service(config), ClientInfo::default()).await;
@@ -2063,9 +3043,7 @@ plugins:
             ClientInfo::default(),
         )
         .await;
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded plugin");
 
         // Test calling the time tool with get_time_utc operation
@@ -2130,6 +3108,63 @@ plugins:
         assert_ok!(client.cancel().await);
     }
 
+    #[tokio::test]
+    async fn test_plugin_service_call_tool_exceeding_fuel_limit() {
+        let wasm_path = get_test_wasm_path();
+        if !test_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let config_content = format!(
+            r#"
+plugins:
+  fuel_time_plugin:
+    url: "file://{}"
+    runtime_config:
+      fuel_limit: 1000
+"#,
+            wasm_path.display()
+        );
+
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+
+        let (server, client) = create_test_pair(
+            PluginService::new(&cli).await.unwrap(),
+            ClientInfo::default(),
+        )
+        .await;
+
+        let request = CallToolRequestParam {
+            name: std::borrow::Cow::Borrowed("fuel_time_plugin-time"),
+            arguments: Some({
+                let mut map = serde_json::Map::new();
+                map.insert(
+                    "name".to_string(),
+                    serde_json::Value::String("get_time_utc".to_string()),
+                );
+                map
+            }),
+        };
+
+        let ctx = create_test_ctx(&server);
+        let result = server.service().call_tool(request, ctx).await;
+        let Err(error) = result else {
+            panic!("Call should run out of fuel: {result:?}");
+        };
+        assert_eq!(error.code, crate::plugin::LIMIT_EXCEEDED);
+        assert!(error.message.contains("fuel_limit"), "{}", error.message);
+        assert_eq!(
+            error.data,
+            Some(serde_json::json!({ "plugin": "fuel_time_plugin", "limit": "fuel_limit" }))
+        );
+
+        assert_ok!(server.cancel().await);
+        assert_ok!(client.cancel().await);
+    }
+
     #[tokio::test]
     async fn test_plugin_service_call_tool_with_skipped_tool() {
         let wasm_path = get_test_wasm_path();
@@ -2159,9 +3194,7 @@ plugins:
             ClientInfo::default(),
         )
         .await;
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded plugin");
 
         // Test calling the skipped time tool
@@ -2198,6 +3231,8 @@ plugins:
         let config = Config {
             plugins: HashMap::new(),
             auths: Some(HashMap::new()),
+            network: None,
+            s3: None,
         };
         let service = create_test_service(config);
 
@@ -2213,6 +3248,8 @@ plugins:
         let config = Config {
             plugins: HashMap::new(),
             auths: Some(HashMap::new()),
+            network: None,
+            s3: None,
         };
         let service = create_test_service(config);
 
@@ -2227,6 +3264,8 @@ plugins:
         let config = Config {
             plugins: HashMap::new(),
             auths: Some(HashMap::new()),
+            network: None,
+            s3: None,
         };
         let service = create_test_service(config);
 
@@ -2236,6 +3275,69 @@ plugins:
         assert!(info.capabilities.tools.is_some());
     }
 
+    #[tokio::test]
+    async fn test_plugin_service_proxies_upstream_server() {
+        let addr = start_stand_in_server().await;
+        let config_content = format!(
+            r#"
+plugins:
+  upstream_plugin:
+    url: "mcp+http://{addr}/mcp"
+    runtime_config:
+      skip_tools: ["hidden"]
+"#
+        );
+
+        let (_temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+
+        let (server, client) = create_test_pair(
+            PluginService::new(&cli).await.unwrap(),
+            ClientInfo::default(),
+        )
+        .await;
+
+        let ctx = create_test_ctx(&server);
+        let tools = server.service().list_tools(None, ctx).await.unwrap();
+        let names: Vec<&str> = tools.tools.iter().map(|tool| tool.name.as_ref()).collect();
+        assert_eq!(names, vec!["upstream_plugin-echo"]);
+
+        // The stand-in has no prompts, which is not an error
+        let ctx = create_test_ctx(&server);
+        let prompts = server.service().list_prompts(None, ctx).await.unwrap();
+        assert!(prompts.prompts.is_empty());
+
+        let request = CallToolRequestParam {
+            name: std::borrow::Cow::Borrowed("upstream_plugin-echo"),
+            arguments: Some({
+                let mut map = serde_json::Map::new();
+                map.insert("text".to_string(), serde_json::Value::from("hello"));
+                map
+            }),
+        };
+        let ctx = create_test_ctx(&server);
+        let result = server.service().call_tool(request, ctx).await.unwrap();
+        assert_eq!(
+            result.content[0].as_text().map(|text| text.text.as_str()),
+            Some("hello")
+        );
+
+        let request = CallToolRequestParam {
+            name: std::borrow::Cow::Borrowed("upstream_plugin-hidden"),
+            arguments: None,
+        };
+        let ctx = create_test_ctx(&server);
+        let result = server.service().call_tool(request, ctx).await;
+        assert!(
+            result.is_err(),
+            "Skipped upstream tool should not be callable"
+        );
+
+        assert_ok!(server.cancel().await);
+        assert_ok!(client.cancel().await);
+    }
+
     #[tokio::test]
     async fn test_plugin_service_multiple_plugins() {
         let wasm_path = get_test_wasm_path();
@@ -2265,9 +3367,7 @@ plugins:
         cli.config_file = Some(config_path);
 
         let service = PluginService::new(&cli).await.unwrap();
-        let Some(plugins) = service.plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = service.plugins();
 
         assert_eq!(plugins.len(), 2, "Should have loaded two plugins");
         assert!(plugins.contains_key(&PluginName::from_str("time_plugin_1").unwrap()));
@@ -3003,24 +4103,24 @@ plugins:
             .collect();
 
         // Verify tool descriptions exist and are meaningful
-        if let Some(add_tool) = tool_map.get("tool_list_changed_plugin-add_tool") {
-            if let Some(desc) = &add_tool.description {
-                assert!(!desc.is_empty(), "add_tool should have a description");
-                assert!(
-                    desc.to_lowercase().contains("add"),
-                    "add_tool description should mention 'add'"
-                );
-            }
+        if let Some(add_tool) = tool_map.get("tool_list_changed_plugin-add_tool")
+            && let Some(desc) = &add_tool.description
+        {
+            assert!(!desc.is_empty(), "add_tool should have a description");
+            assert!(
+                desc.to_lowercase().contains("add"),
+                "add_tool description should mention 'add'"
+            );
         }
 
-        if let Some(tool_1) = tool_map.get("tool_list_changed_plugin-tool_1") {
-            if let Some(desc) = &tool_1.description {
-                assert!(!desc.is_empty(), "tool_1 should have a description");
-                assert!(
-                    desc.to_lowercase().contains("tool"),
-                    "tool_1 description should mention 'tool'"
-                );
-            }
+        if let Some(tool_1) = tool_map.get("tool_list_changed_plugin-tool_1")
+            && let Some(desc) = &tool_1.description
+        {
+            assert!(!desc.is_empty(), "tool_1 should have a description");
+            assert!(
+                desc.to_lowercase().contains("tool"),
+                "tool_1 description should mention 'tool'"
+            );
         }
 
         assert_ok!(server.cancel().await);
@@ -3059,9 +4159,7 @@ plugins:
         )
         .await;
 
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded rstime plugin");
 
         let request = None;
@@ -3132,9 +4230,7 @@ plugins:
         )
         .await;
 
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded rstime plugin");
 
         let request = None;
@@ -3206,9 +4302,7 @@ plugins:
         )
         .await;
 
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded rstime plugin");
 
         let request = None;
@@ -3287,9 +4381,7 @@ plugins:
         )
         .await;
 
-        let Some(plugins) = server.service().plugins.get() else {
-            panic!("Plugins should be initialized");
-        };
+        let plugins = server.service().plugins();
         assert!(!plugins.is_empty(), "Should have loaded rstime plugin");
 
         let request = None;
@@ -3749,6 +4841,57 @@ plugins:
         assert_ok!(client.cancel().await);
     }
 
+    #[tokio::test]
+    async fn test_rstime_read_resource_records_denied_host() {
+        let wasm_path = get_rstime_wasm_path();
+        if !test_rstime_wasm_exists() {
+            println!("Skipping test - WASM file not found at {wasm_path:?}");
+            return;
+        }
+
+        let config_content = format!(
+            r#"
+plugins:
+  rstime:
+    url: "file://{}"
+    runtime_config:
+      allowed_hosts:
+        - "example.com"
+"#,
+            wasm_path.display()
+        );
+
+        let (temp_dir, config_path) = create_temp_config_file(&config_content).await.unwrap();
+        let record_path = temp_dir.path().join("hosts.json");
+        let mut cli = create_test_cli();
+        cli.config_file = Some(config_path);
+        cli.record_hosts = Some(record_path.clone());
+
+        let (server, client) = create_test_pair(
+            PluginService::new(&cli).await.unwrap(),
+            ClientInfo::default(),
+        )
+        .await;
+
+        let request = ReadResourceRequestParam {
+            uri: "https://www.timezoneconverter.com/rstime/cgi-bin/zoneinfo?tz=America/New_York"
+                .to_string(),
+        };
+        let ctx = create_test_ctx(&server);
+        let result = server.service().read_resource(request, ctx).await;
+        assert!(result.is_err(), "The host is not in allowed_hosts");
+
+        let recorded: Value =
+            serde_json::from_slice(&std::fs::read(&record_path).unwrap()).unwrap();
+        assert_eq!(
+            recorded,
+            serde_json::json!({"rstime": {"www.timezoneconverter.com": "denied"}})
+        );
+
+        assert_ok!(server.cancel().await);
+        assert_ok!(client.cancel().await);
+    }
+
     #[tokio::test]
     async fn test_rstime_complete_prompt_timezone() {
         let wasm_path = get_rstime_wasm_path();
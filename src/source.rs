@@ -0,0 +1,228 @@
+use crate::{
+    Cli,
+    cache::PluginCache,
+    config::{Config, NetworkConfig, PluginConfig, PluginName},
+    fetch::{FetchedPlugin, sha256_digest},
+    https_auth::client_identity,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::OnceCell;
+use url::Url;
+
+mod azure;
+mod file;
+mod gcs;
+mod git;
+mod http;
+mod oci;
+mod s3;
+
+pub use azure::AzureBlobSource;
+pub use file::FileSource;
+pub use gcs::GcsSource;
+pub use git::GitSource;
+pub use http::HttpSource;
+pub use oci::OciSource;
+pub use s3::S3Source;
+
+/// What a [`PluginSource`] is asked to download.
+pub struct FetchRequest<'a> {
+    pub cli: &'a Cli,
+    pub config: &'a Config,
+    pub network: &'a NetworkConfig,
+    pub plugin_name: &'a PluginName,
+    pub plugin_cfg: &'a PluginConfig,
+    // Where downloads are kept, absent when the cache directory could not be opened
+    pub cache: Option<&'a PluginCache>,
+    // Whether plugins that are not pulled when loading are fetched anyway, to fill the
+    // cache for them
+    pub prefetch: bool,
+}
+
+impl FetchRequest<'_> {
+    pub fn url(&self) -> &Url {
+        &self.plugin_cfg.url
+    }
+
+    pub fn cache(&self) -> Result<&PluginCache> {
+        self.cache
+            .ok_or_else(|| anyhow::anyhow!("The plugin cache directory is unavailable"))
+    }
+}
+
+/// The plugin URL as the lockfile, the plugin cache and their output show it, without
+/// the SAS token an `az://` URL can carry in its query.
+///
+/// The token is a credential, and leaving it out also keeps a locked or cached plugin
+/// the same when the token is rotated.
+pub fn stored_url(url: &Url) -> Url {
+    let mut url = url.clone();
+    if url.scheme() == "az" {
+        url.set_query(None);
+    }
+    url
+}
+
+/// Downloads the wasm modules of plugins for some URL schemes.
+///
+/// Most sources only implement `fetch`, and the provided `load` computes the digest of
+/// what they return, keeps it in the plugin cache and loads it from there when running
+/// offline. [`PluginFetcher`](crate::fetch::PluginFetcher) checks `sha256` pins on
+/// whatever `load` returns.
+///
+/// Their futures are not `Send`, as verifying OCI signatures holds sigstore values that
+/// are not.
+#[async_trait(?Send)]
+pub trait PluginSource: Send + Sync {
+    /// URL schemes this source downloads from.
+    fn schemes(&self) -> &'static [&'static str];
+
+    /// Whether downloads are kept in the plugin cache and loaded from it offline.
+    fn cached(&self) -> bool {
+        true
+    }
+
+    async fn fetch(&self, request: &FetchRequest<'_>) -> Result<Vec<u8>>;
+
+    /// Returns the plugin with its digest, downloading it or loading it from the cache.
+    async fn load(&self, request: &FetchRequest<'_>) -> Result<FetchedPlugin> {
+        let (plugin_name, url) = (request.plugin_name, request.url());
+        if !self.cached() {
+            let content = self.fetch(request).await?;
+            return Ok(FetchedPlugin {
+                digest: sha256_digest(&content),
+                content,
+            });
+        }
+        if request.cli.offline {
+            let cache = request.cache()?;
+            let Some(entry) = cache.latest(plugin_name, url).await else {
+                return Err(anyhow::anyhow!(
+                    "Plugin {plugin_name} is not cached and hyper-mcp is running offline"
+                ));
+            };
+            tracing::info!("Loading plugin {plugin_name} from the cache while offline");
+            return Ok(FetchedPlugin {
+                digest: entry.digest.clone(),
+                content: cache.load(entry).await?,
+            });
+        }
+
+        let content = self.fetch(request).await?;
+        let digest = sha256_digest(&content);
+        // Keep a copy to fall back on when running offline
+        if let Err(e) = async {
+            request
+                .cache()?
+                .insert(plugin_name, url, &digest, &content)
+                .await
+        }
+        .await
+        {
+            tracing::warn!("Failed to cache plugin {plugin_name}: {e}");
+        }
+        Ok(FetchedPlugin { content, digest })
+    }
+}
+
+/// The sources of every scheme hyper-mcp knows.
+pub fn default_sources() -> Vec<Box<dyn PluginSource>> {
+    vec![
+        Box::new(OciSource::default()),
+        Box::new(FileSource),
+        Box::new(HttpSource::default()),
+        Box::new(S3Source::default()),
+        Box::new(GcsSource::from_env()),
+        Box::new(AzureBlobSource::from_env()),
+        Box::new(GitSource),
+    ]
+}
+
+/// HTTP clients for the sources that download over HTTP, reusing one client unless a
+/// URL needs a client certificate or an insecure host skips certificate checks.
+#[derive(Default)]
+struct HttpClients {
+    client: OnceCell<reqwest::Client>,
+}
+
+impl HttpClients {
+    /// The client to download `url` with, `auths` being matched against `auth_url`.
+    async fn get(
+        &self,
+        request: &FetchRequest<'_>,
+        url: &Url,
+        auth_url: &Url,
+    ) -> Result<reqwest::Client> {
+        let identity = client_identity(&request.config.auths, auth_url).await?;
+        let insecure = request.network.is_insecure(url);
+        Ok(match (identity, insecure) {
+            (None, false) => self
+                .client
+                .get_or_try_init(|| async {
+                    Ok::<_, anyhow::Error>(request.network.reqwest_builder()?.build()?)
+                })
+                .await?
+                .clone(),
+            (identity, insecure) => {
+                let mut builder = request
+                    .network
+                    .reqwest_builder()?
+                    .danger_accept_invalid_certs(insecure);
+                if let Some(identity) = identity {
+                    builder = builder.use_rustls_tls().identity(identity);
+                }
+                builder.build()?
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_util {
+    use super::*;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, Uri},
+    };
+    use clap::Parser;
+    use std::sync::{Arc, Mutex};
+
+    // URI and headers of every request the stand-in received
+    pub type Requests = Arc<Mutex<Vec<(Uri, HeaderMap)>>>;
+
+    /// Starts an HTTP stand-in for a storage service that answers every request with
+    /// `content`, and returns its URL.
+    pub async fn serve(content: &'static [u8]) -> (Url, Requests) {
+        let requests = Requests::default();
+        let app = axum::Router::new()
+            .fallback(
+                move |State(requests): State<Requests>, uri: Uri, headers: HeaderMap| async move {
+                    requests.lock().unwrap().push((uri, headers));
+                    content
+                },
+            )
+            .with_state(Arc::clone(&requests));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (Url::parse(&format!("http://{addr}")).unwrap(), requests)
+    }
+
+    /// Fetches the only plugin of a config from `source`.
+    pub async fn fetch(source: &dyn PluginSource, config_yaml: &str) -> Result<Vec<u8>> {
+        let config: Config = serde_yaml::from_str(config_yaml).unwrap();
+        let (plugin_name, plugin_cfg) = config.plugins.iter().next().unwrap();
+        source
+            .fetch(&FetchRequest {
+                cli: &Cli::parse_from(["hyper-mcp"]),
+                config: &config,
+                network: &NetworkConfig::default(),
+                plugin_name,
+                plugin_cfg,
+                cache: None,
+                prefetch: false,
+            })
+            .await
+    }
+}
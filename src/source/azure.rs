@@ -0,0 +1,154 @@
+use super::{FetchRequest, HttpClients, PluginSource};
+use crate::https_auth::Authenticator;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+// Blob service version that accepts OAuth bearer tokens
+const AZURE_STORAGE_VERSION: &str = "2023-11-03";
+
+/// `az://account/container/blob` plugins from Azure Blob Storage.
+///
+/// Credentials are the `auths` entry matching the `az://` URL, typically an `oauth2`
+/// auth with the `https://storage.azure.com/.default` scope. A SAS token can instead be
+/// given as the query of the plugin URL, and public containers need neither.
+pub struct AzureBlobSource {
+    // Blob endpoint taking the account as first path segment, as Azurite does, instead
+    // of `https://{account}.blob.core.windows.net`
+    endpoint: Option<Url>,
+    clients: HttpClients,
+}
+
+impl AzureBlobSource {
+    pub fn new(endpoint: Option<Url>) -> Self {
+        Self {
+            endpoint,
+            clients: HttpClients::default(),
+        }
+    }
+
+    /// Talks to the endpoint in `HYPER_MCP_AZURE_BLOB_ENDPOINT` when it is set, such as
+    /// `http://127.0.0.1:10000` for Azurite.
+    pub fn from_env() -> Self {
+        let endpoint = std::env::var("HYPER_MCP_AZURE_BLOB_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.is_empty())
+            .and_then(|endpoint| match Url::parse(&endpoint) {
+                Ok(endpoint) => Some(endpoint),
+                Err(e) => {
+                    tracing::warn!(
+                        "Ignoring invalid HYPER_MCP_AZURE_BLOB_ENDPOINT {endpoint}: {e}"
+                    );
+                    None
+                }
+            });
+        Self::new(endpoint)
+    }
+
+    fn blob_url(&self, url: &Url) -> Result<Url> {
+        let account = url
+            .host_str()
+            .ok_or_else(|| anyhow!("Azure URL must have a storage account in the host"))?;
+        let mut blob_url = match &self.endpoint {
+            Some(endpoint) => {
+                let mut blob_url = endpoint.clone();
+                blob_url
+                    .path_segments_mut()
+                    .map_err(|_| anyhow!("Invalid Azure blob endpoint {endpoint}"))?
+                    .pop_if_empty()
+                    .push(account);
+                blob_url
+            }
+            None => Url::parse(&format!("https://{account}.blob.core.windows.net"))?,
+        };
+        // Encoded again as the segments are pushed
+        let path = percent_decode_str(url.path().trim_start_matches('/')).decode_utf8()?;
+        if !path.contains('/') {
+            return Err(anyhow!("Azure URL {url} must name a container and a blob"));
+        }
+        blob_url
+            .path_segments_mut()
+            .map_err(|_| anyhow!("Invalid Azure blob URL for {url}"))?
+            .pop_if_empty()
+            .extend(path.split('/'));
+        blob_url.set_query(url.query());
+        Ok(blob_url)
+    }
+}
+
+#[async_trait(?Send)]
+impl PluginSource for AzureBlobSource {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["az"]
+    }
+
+    async fn fetch(&self, request: &FetchRequest<'_>) -> Result<Vec<u8>> {
+        let url = request.url();
+        let blob_url = self.blob_url(url)?;
+        let mut auth_url = url.clone();
+        auth_url.set_query(None);
+        let response = self
+            .clients
+            .get(request, &blob_url, &auth_url)
+            .await?
+            .get(blob_url)
+            .header("x-ms-version", AZURE_STORAGE_VERSION)
+            .add_auth(&request.config.auths, &auth_url)
+            .await
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("Failed to get blob {auth_url} from Azure: {e}"))?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::test_util::{fetch, serve};
+
+    #[test]
+    fn test_blob_url() {
+        let url = Url::parse("az://team/plugins/v1/time.wasm?sv=2024&sig=abc").unwrap();
+        assert_eq!(
+            AzureBlobSource::new(None).blob_url(&url).unwrap().as_str(),
+            "https://team.blob.core.windows.net/plugins/v1/time.wasm?sv=2024&sig=abc"
+        );
+        let url = Url::parse("az://team/plugins/time v2.wasm").unwrap();
+        assert_eq!(
+            AzureBlobSource::new(None).blob_url(&url).unwrap().as_str(),
+            "https://team.blob.core.windows.net/plugins/time%20v2.wasm"
+        );
+        let url = Url::parse("az://team/time.wasm").unwrap();
+        assert!(AzureBlobSource::new(None).blob_url(&url).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_azure_blob_source() {
+        let (endpoint, requests) = serve(b"wasm").await;
+        let source = AzureBlobSource::new(Some(endpoint));
+        let content = fetch(
+            &source,
+            r#"
+auths:
+  "az://devstoreaccount1/plugins/":
+    type: token
+    token: eyJ0eXAi
+plugins:
+  time:
+    url: az://devstoreaccount1/plugins/team/time.wasm
+"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(content, b"wasm");
+
+        let requests = requests.lock().unwrap();
+        let (uri, headers) = &requests[0];
+        assert_eq!(uri.path(), "/devstoreaccount1/plugins/team/time.wasm");
+        assert_eq!(headers["authorization"], "Bearer eyJ0eXAi");
+        assert_eq!(headers["x-ms-version"], AZURE_STORAGE_VERSION);
+    }
+}
@@ -0,0 +1,24 @@
+use super::{FetchRequest, PluginSource};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+/// `file://` plugins, read from disk every time they load.
+pub struct FileSource;
+
+#[async_trait(?Send)]
+impl PluginSource for FileSource {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["file"]
+    }
+
+    fn cached(&self) -> bool {
+        false
+    }
+
+    async fn fetch(&self, request: &FetchRequest<'_>) -> Result<Vec<u8>> {
+        let path = request.url().path();
+        tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read plugin {path}"))
+    }
+}
@@ -0,0 +1,347 @@
+use super::{FetchRequest, PluginSource};
+use crate::{
+    config::AuthConfig,
+    https_auth::{Authenticator, find_auth},
+};
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use std::{ffi::OsStr, path::Path};
+use tokio::process::Command;
+use url::Url;
+use uuid::Uuid;
+
+/// `git+https://host/repo.git#ref:path/to/plugin.wasm` plugins, read from a commit of a
+/// git repository with the `git` command. The ref is a branch, a tag or a commit the
+/// server lets clients fetch, and only that commit is downloaded.
+///
+/// Credentials are the `auths` entry matching the `https://` URL of the repository,
+/// sent as HTTP headers. `git+ssh://` repositories use the usual ssh keys and agent.
+pub struct GitSource;
+
+/// The repository URL, ref and path of a plugin URL.
+fn parse_git_url(url: &Url) -> Result<(Url, &str, &str)> {
+    let (reference, path) = url
+        .fragment()
+        .and_then(|fragment| fragment.split_once(':'))
+        .filter(|(reference, path)| !reference.is_empty() && !path.is_empty())
+        .ok_or_else(|| anyhow!("Git plugin URL {url} must end with #<ref>:<path>"))?;
+    // git would take such a ref for an option, like --upload-pack=<command>
+    if reference.starts_with('-') {
+        return Err(anyhow!(
+            "Git plugin URL {url} has an invalid ref {reference}"
+        ));
+    }
+    let repository = url
+        .as_str()
+        .strip_prefix("git+")
+        .and_then(|repository| repository.split('#').next())
+        .ok_or_else(|| anyhow!("Git plugin URL {url} must start with git+"))?;
+    Ok((Url::parse(repository)?, reference, path))
+}
+
+/// Runs `git` and returns what it printed, or an error with what it complained about.
+async fn git<I, S>(envs: &[(String, String)], args: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = Command::new("git")
+        .args(args)
+        .envs(envs.iter().map(|(key, value)| (key, value)))
+        // Fail instead of waiting for a password nobody will type
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Environment for `git` to reach the repository with the credentials and network
+/// settings of hyper-mcp. Settings go through `GIT_CONFIG_*` rather than the command
+/// line so that credentials do not show up in process listings.
+///
+/// git takes a single file of CA certificates, so those of `ca_certs` are written
+/// together into `dir`.
+async fn git_env(
+    request: &FetchRequest<'_>,
+    repository: &Url,
+    dir: &Path,
+) -> Result<Vec<(String, String)>> {
+    let mut settings = Vec::new();
+    if matches!(repository.scheme(), "http" | "https") {
+        let credentials = request
+            .network
+            .reqwest_builder()?
+            .build()?
+            .get(repository.as_str())
+            .add_auth(&request.config.auths, repository)
+            .await
+            .build()?;
+        for (name, value) in credentials.headers() {
+            let value = value.to_str().context("Invalid credentials header")?;
+            settings.push(("http.extraHeader".to_string(), format!("{name}: {value}")));
+        }
+        if request.network.is_insecure(repository) {
+            settings.push(("http.sslVerify".to_string(), "false".to_string()));
+        }
+        let certificates = request.network.certificates()?;
+        if !certificates.is_empty() {
+            let ca_info = dir.join("ca-certs.pem");
+            tokio::fs::write(&ca_info, certificates.concat()).await?;
+            settings.push((
+                "http.sslCAInfo".to_string(),
+                ca_info.to_string_lossy().into_owned(),
+            ));
+        }
+        let auth = match find_auth(&request.config.auths, repository) {
+            Some(auth) => Some(auth.resolve().await?),
+            None => None,
+        };
+        if let Some(AuthConfig::Mtls { cert, key }) = auth.as_deref() {
+            settings.push((
+                "http.sslCert".to_string(),
+                cert.to_string_lossy().into_owned(),
+            ));
+            settings.push((
+                "http.sslKey".to_string(),
+                key.to_string_lossy().into_owned(),
+            ));
+        }
+    }
+    let mut envs = vec![("GIT_CONFIG_COUNT".to_string(), settings.len().to_string())];
+    for (i, (key, value)) in settings.into_iter().enumerate() {
+        envs.push((format!("GIT_CONFIG_KEY_{i}"), key));
+        envs.push((format!("GIT_CONFIG_VALUE_{i}"), value));
+    }
+    let network = request.network;
+    for (name, value) in [
+        ("https_proxy", &network.https_proxy),
+        ("http_proxy", &network.http_proxy),
+        ("no_proxy", &network.no_proxy),
+    ] {
+        if let Some(value) = value {
+            envs.push((name.to_string(), value.clone()));
+        }
+    }
+    Ok(envs)
+}
+
+async fn fetch_file(
+    dir: &Path,
+    envs: &[(String, String)],
+    repository: &Url,
+    reference: &str,
+    path: &str,
+) -> Result<Vec<u8>> {
+    git(
+        envs,
+        [
+            OsStr::new("init"),
+            "--quiet".as_ref(),
+            "--bare".as_ref(),
+            dir.as_os_str(),
+        ],
+    )
+    .await?;
+    git(
+        envs,
+        [
+            OsStr::new("-C"),
+            dir.as_os_str(),
+            "fetch".as_ref(),
+            "--quiet".as_ref(),
+            "--depth=1".as_ref(),
+            "--".as_ref(),
+            repository.as_str().as_ref(),
+            reference.as_ref(),
+        ],
+    )
+    .await
+    .map_err(|e| anyhow!("Failed to fetch {reference} from {repository}: {e}"))?;
+    git(
+        envs,
+        [
+            OsStr::new("-C"),
+            dir.as_os_str(),
+            "show".as_ref(),
+            format!("FETCH_HEAD:{path}").as_ref(),
+        ],
+    )
+    .await
+    .map_err(|e| anyhow!("Failed to read {path} at {reference} of {repository}: {e}"))
+}
+
+#[async_trait(?Send)]
+impl PluginSource for GitSource {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["git+https", "git+http", "git+ssh", "git+file"]
+    }
+
+    async fn fetch(&self, request: &FetchRequest<'_>) -> Result<Vec<u8>> {
+        let (repository, reference, path) = parse_git_url(request.url())?;
+        let dir = std::env::temp_dir().join(format!("hyper-mcp-git-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await?;
+        let content = async {
+            let envs = git_env(request, &repository, &dir).await?;
+            fetch_file(&dir.join("repository"), &envs, &repository, reference, path).await
+        }
+        .await;
+        if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+            tracing::warn!("Failed to remove {}: {e}", dir.display());
+        }
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::test_util::fetch;
+
+    #[test]
+    fn test_parse_git_url() {
+        let url =
+            Url::parse("git+https://github.com/team/plugins.git#v1.2.0:time/plugin.wasm").unwrap();
+        let (repository, reference, path) = parse_git_url(&url).unwrap();
+        assert_eq!(repository.as_str(), "https://github.com/team/plugins.git");
+        assert_eq!(reference, "v1.2.0");
+        assert_eq!(path, "time/plugin.wasm");
+
+        let url = Url::parse("git+https://github.com/team/plugins.git#main").unwrap();
+        assert!(parse_git_url(&url).is_err());
+
+        let url = Url::parse(
+            "git+https://github.com/team/plugins.git#--upload-pack=touch%20/tmp/x:plugin.wasm",
+        )
+        .unwrap();
+        assert!(parse_git_url(&url).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_git_env_tls_settings() {
+        use crate::{
+            Cli,
+            config::{Config, NetworkConfig},
+        };
+        use clap::Parser;
+
+        let config: Config = serde_yaml::from_str(
+            r#"
+auths:
+  "https://git.example.com/":
+    type: mtls
+    cert: tests/fixtures/client.crt
+    key: tests/fixtures/client.key
+plugins:
+  time:
+    url: git+https://git.example.com/team/plugins.git#v1:time.wasm
+"#,
+        )
+        .unwrap();
+        let (plugin_name, plugin_cfg) = config.plugins.iter().next().unwrap();
+        let network = NetworkConfig {
+            ca_certs: vec!["tests/fixtures/client.crt".into()],
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let request = FetchRequest {
+            cli: &Cli::parse_from(["hyper-mcp"]),
+            config: &config,
+            network: &network,
+            plugin_name,
+            plugin_cfg,
+            cache: None,
+            prefetch: false,
+        };
+        let (repository, _, _) = parse_git_url(request.url()).unwrap();
+        let envs = git_env(&request, &repository, dir.path()).await.unwrap();
+
+        let settings: Vec<(&str, &str)> = (0..envs[0].1.parse().unwrap())
+            .map(|i| {
+                let value = |name: String| {
+                    envs.iter()
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| value.as_str())
+                        .unwrap()
+                };
+                (
+                    value(format!("GIT_CONFIG_KEY_{i}")),
+                    value(format!("GIT_CONFIG_VALUE_{i}")),
+                )
+            })
+            .collect();
+        let ca_info = dir.path().join("ca-certs.pem");
+        assert_eq!(
+            settings,
+            [
+                ("http.sslCAInfo", ca_info.to_str().unwrap()),
+                ("http.sslCert", "tests/fixtures/client.crt"),
+                ("http.sslKey", "tests/fixtures/client.key"),
+            ]
+        );
+        assert!(
+            std::fs::read_to_string(&ca_info)
+                .unwrap()
+                .starts_with("-----BEGIN CERTIFICATE-----")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_git_source() {
+        let repository = tempfile::tempdir().unwrap();
+        let dir = repository.path();
+        std::fs::create_dir(dir.join("plugins")).unwrap();
+        std::fs::write(dir.join("plugins/time.wasm"), b"wasm").unwrap();
+        let no_envs = [];
+        for args in [
+            vec!["init", "--quiet"],
+            vec!["add", "."],
+            vec![
+                "-c",
+                "user.name=hyper-mcp",
+                "-c",
+                "user.email=hyper-mcp@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                "Add time plugin",
+            ],
+            vec!["tag", "v1"],
+        ] {
+            git(
+                &no_envs,
+                ["-C", dir.to_str().unwrap()].into_iter().chain(args),
+            )
+            .await
+            .unwrap();
+        }
+
+        let url = Url::from_directory_path(dir).unwrap();
+        let content = fetch(
+            &GitSource,
+            &format!("plugins:\n  time:\n    url: git+{url}#v1:plugins/time.wasm\n"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(content, b"wasm");
+
+        let error = fetch(
+            &GitSource,
+            &format!("plugins:\n  time:\n    url: git+{url}#v1:plugins/missing.wasm\n"),
+        )
+        .await
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains("Failed to read plugins/missing.wasm"),
+            "{error}"
+        );
+    }
+}
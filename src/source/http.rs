@@ -0,0 +1,66 @@
+use super::{FetchRequest, HttpClients, PluginSource};
+use crate::https_auth::Authenticator;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+/// `http://` and `https://` plugins. Credentials from `auths` are only sent over https.
+#[derive(Default)]
+pub struct HttpSource {
+    clients: HttpClients,
+}
+
+#[async_trait(?Send)]
+impl PluginSource for HttpSource {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["http", "https"]
+    }
+
+    async fn fetch(&self, request: &FetchRequest<'_>) -> Result<Vec<u8>> {
+        let url = request.url();
+        let client = self.clients.get(request, url, url).await?;
+        let mut builder = client.get(url.as_str());
+        if url.scheme() == "https" {
+            builder = builder.add_auth(&request.config.auths, url).await;
+        }
+        let response = builder
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(|e| anyhow!("Failed to download plugin from {url}: {e}"))?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cli, cache::PluginCache, config::Config, fetch::PluginFetcher};
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn test_http_source_error_status() {
+        // Answers 404 to everything
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, axum::Router::new()).await });
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "plugins:\n  missing_http_plugin:\n    url: http://{addr}/time.wasm\n"
+        ))
+        .unwrap();
+        let (plugin_name, plugin_cfg) = config.plugins.iter().next().unwrap();
+        let cli = Cli::parse_from(["hyper-mcp"]);
+        let dir = tempfile::tempdir().unwrap();
+        let cache = PluginCache::at(dir.path().to_path_buf()).unwrap();
+        let error = PluginFetcher::default()
+            .with_cache(cache.clone())
+            .fetch(&cli, &config, plugin_name, plugin_cfg)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("404"), "{error}");
+        assert!(
+            cache.latest(plugin_name, &plugin_cfg.url).await.is_none(),
+            "Error pages must not be cached"
+        );
+    }
+}
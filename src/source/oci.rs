@@ -0,0 +1,241 @@
+use super::{FetchRequest, PluginSource};
+use crate::{
+    config::PullPolicy,
+    fetch::FetchedPlugin,
+    oci::{
+        build_auth, check_image_signature, policy_fingerprint, pull_and_extract_oci_image,
+        resolve_manifest_digest, signature_policy,
+    },
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use oci_client::Reference;
+use tokio::sync::OnceCell;
+
+/// `oci://` plugins, whose images are resolved to a manifest digest and cached by it
+/// according to their pull policy, instead of being downloaded every time.
+#[derive(Default)]
+pub struct OciSource {
+    client: OnceCell<oci_client::Client>,
+}
+
+#[async_trait(?Send)]
+impl PluginSource for OciSource {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["oci"]
+    }
+
+    async fn fetch(&self, request: &FetchRequest<'_>) -> Result<Vec<u8>> {
+        Ok(self.load(request).await?.content)
+    }
+
+    /// Resolves the image to a manifest digest according to the plugin's pull policy and
+    /// pulls that exact manifest unless it is already cached on disk.
+    async fn load(&self, request: &FetchRequest<'_>) -> Result<FetchedPlugin> {
+        let FetchRequest {
+            cli,
+            config,
+            network,
+            plugin_name,
+            plugin_cfg,
+            cache: _,
+            prefetch,
+        } = *request;
+        let registry_config = network.registry_config()?;
+        let client = self
+            .client
+            .get_or_init(|| async { oci_client::Client::new(registry_config.clone().into()) })
+            .await;
+        let image_reference = plugin_cfg.url.as_str().strip_prefix("oci://").unwrap();
+        let reference = Reference::try_from(image_reference)?;
+        // Only look up credentials when contacting the registry, credential helpers may
+        // be slow or unavailable offline
+        let auth = || build_auth(&reference, &config.auths, network);
+        let policy = match (cli.offline, plugin_cfg.pull_policy) {
+            (true, _) => PullPolicy::Never,
+            // Prefetching exists to fill the cache for plugins that will not pull later
+            (false, Some(PullPolicy::Never)) if prefetch => PullPolicy::IfNotPresent,
+            (false, policy) => policy.unwrap_or_else(|| default_pull_policy(&reference)),
+        };
+
+        let signature = signature_policy(cli, plugin_cfg.signature.as_ref());
+        let fingerprint = signature.as_ref().map(policy_fingerprint).transpose()?;
+
+        let cache = request.cache()?;
+        let cached = match reference.digest() {
+            Some(digest) => cache.get(plugin_name, digest).await,
+            None => cache.latest(plugin_name, &plugin_cfg.url).await,
+        };
+        let digest = match (policy, &cached) {
+            (PullPolicy::Never | PullPolicy::IfNotPresent, Some(entry)) => entry.digest.clone(),
+            (PullPolicy::Never, None) => {
+                return Err(anyhow::anyhow!(
+                    "Plugin {plugin_name} is not cached and {}",
+                    match cli.offline {
+                        true => "hyper-mcp is running offline",
+                        false => "its pull_policy is never",
+                    }
+                ));
+            }
+            _ => resolve_manifest_digest(client, image_reference, &auth().await)
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!("Failed to resolve OCI plugin {image_reference}: {e}")
+                })?,
+        };
+
+        let pinned_reference = reference.clone_with_digest(digest.clone()).whole();
+        let mut entry = match cache.get(plugin_name, &digest).await {
+            Some(entry) if entry.source == plugin_cfg.url => {
+                tracing::info!("Plugin {image_reference} is cached as {digest}");
+                entry
+            }
+            // Another reference of the plugin resolved to the same manifest before
+            Some(_) => cache.record(plugin_name, &plugin_cfg.url, &digest).await?,
+            None => {
+                let local_output_path = cache.module_path(plugin_name, &digest);
+                let local_output_path = local_output_path.to_str().unwrap();
+                if let Err(e) = pull_and_extract_oci_image(
+                    cli,
+                    &registry_config,
+                    &pinned_reference,
+                    &auth().await,
+                    plugin_cfg.signature.as_ref(),
+                    "/plugin.wasm",
+                    local_output_path,
+                )
+                .await
+                {
+                    tracing::error!("Error pulling oci plugin: {e}");
+                    return Err(anyhow::anyhow!("Failed to pull OCI plugin: {e}"));
+                }
+                tracing::info!("cache plugin `{plugin_name}` to : {local_output_path}");
+                let entry = cache.record(plugin_name, &plugin_cfg.url, &digest).await?;
+                cache
+                    .set_verified_policy(entry, fingerprint.clone())
+                    .await?
+            }
+        };
+
+        // A cached image may have been pulled without verification or under another
+        // policy, so check it again whenever the policy has changed since
+        if let Some(signature) = &signature
+            && entry.verified_policy != fingerprint
+        {
+            if cli.offline {
+                return Err(anyhow::anyhow!(
+                    "Plugin {plugin_name} is cached as {digest} but was not verified against its current signature policy, which needs the registry while hyper-mcp is running offline"
+                ));
+            }
+            check_image_signature(
+                cli,
+                &registry_config,
+                &pinned_reference,
+                signature,
+                &auth().await,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Refusing to load cached plugin {plugin_name}: {e}"))?;
+            entry = cache
+                .set_verified_policy(entry, fingerprint.clone())
+                .await?;
+        }
+
+        Ok(FetchedPlugin {
+            content: cache.load(entry).await?,
+            digest,
+        })
+    }
+}
+
+/// Images referenced by digest or by a specific tag are not expected to change, while
+/// `latest` (or no tag at all) is checked against the registry every time.
+fn default_pull_policy(reference: &Reference) -> PullPolicy {
+    match (reference.digest(), reference.tag()) {
+        (Some(_), _) => PullPolicy::IfNotPresent,
+        (None, None) | (None, Some("latest")) => PullPolicy::Always,
+        (None, Some(_)) => PullPolicy::IfNotPresent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Cli,
+        cache::PluginCache,
+        config::{Config, NetworkConfig},
+        fetch::sha256_digest,
+    };
+    use clap::Parser;
+
+    #[test]
+    fn test_default_pull_policy() {
+        let policy = |image: &str| default_pull_policy(&Reference::try_from(image).unwrap());
+        assert_eq!(policy("ghcr.io/tuananh/time-plugin"), PullPolicy::Always);
+        assert_eq!(
+            policy("ghcr.io/tuananh/time-plugin:latest"),
+            PullPolicy::Always
+        );
+        assert_eq!(
+            policy("ghcr.io/tuananh/time-plugin:v1.2.0"),
+            PullPolicy::IfNotPresent
+        );
+        assert_eq!(
+            policy(&format!(
+                "ghcr.io/tuananh/time-plugin@sha256:{}",
+                "0".repeat(64)
+            )),
+            PullPolicy::IfNotPresent
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oci_source_reverifies_cached_image() {
+        let content = b"\0asm\x01\0\0\0unverified".to_vec();
+        let digest = sha256_digest(&content);
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("cosign.pub");
+        std::fs::write(&key, "not checked offline").unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            "plugins:\n  unverified_oci_plugin:\n    url: oci://127.0.0.1:1/test/plugin@{digest}\n    signature:\n      key: {}\n",
+            key.display()
+        ))
+        .unwrap();
+        let (plugin_name, plugin_cfg) = config.plugins.iter().next().unwrap();
+        // As left behind by a pull with --insecure-skip-signature
+        let cache = PluginCache::at(dir.path().join("cache")).unwrap();
+        cache
+            .insert(plugin_name, &plugin_cfg.url, &digest, &content)
+            .await
+            .unwrap();
+
+        let load = |cli: Cli| {
+            let (config, cache) = (&config, &cache);
+            async move {
+                OciSource::default()
+                    .load(&FetchRequest {
+                        cli: &cli,
+                        config,
+                        network: &NetworkConfig::default(),
+                        plugin_name,
+                        plugin_cfg,
+                        cache: Some(cache),
+                        prefetch: false,
+                    })
+                    .await
+            }
+        };
+        let skipped = load(Cli::parse_from([
+            "hyper-mcp",
+            "--offline",
+            "--insecure-skip-signature",
+        ]))
+        .await;
+        let unverified = load(Cli::parse_from(["hyper-mcp", "--offline"])).await;
+
+        assert_eq!(skipped.unwrap().content, content);
+        let error = unverified.err().unwrap().to_string();
+        assert!(error.contains("signature policy"), "{error}");
+    }
+}
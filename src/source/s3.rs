@@ -0,0 +1,187 @@
+use super::{FetchRequest, PluginSource};
+use crate::config::{Config, NetworkConfig, PluginConfig, S3Config};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+/// `s3://bucket/key` plugins from Amazon S3 or an S3 compatible store.
+#[derive(Default)]
+pub struct S3Source {
+    // By the S3 settings they were made with
+    clients: Mutex<HashMap<S3Config, aws_sdk_s3::Client>>,
+}
+
+#[async_trait(?Send)]
+impl PluginSource for S3Source {
+    fn schemes(&self) -> &'static [&'static str] {
+        &["s3"]
+    }
+
+    async fn fetch(&self, request: &FetchRequest<'_>) -> Result<Vec<u8>> {
+        let url = request.url();
+        let bucket = url
+            .host_str()
+            .ok_or_else(|| anyhow!("S3 URL must have a valid bucket name in the host"))?;
+        let key = url.path().trim_start_matches('/');
+        let s3 = s3_config(request.config, request.plugin_cfg)
+            .cloned()
+            .unwrap_or_default();
+        match self
+            .client(&s3, request.network)
+            .await?
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(response) => match response.body.collect().await {
+                Ok(body) => Ok(body.to_vec()),
+                Err(e) => {
+                    tracing::error!("Failed to collect S3 object body: {e}");
+                    Err(anyhow!("Failed to collect S3 object body: {e}"))
+                }
+            },
+            Err(e) => {
+                tracing::error!("Failed to get object from S3: {e}");
+                Err(anyhow!("Failed to get object from S3: {e}"))
+            }
+        }
+    }
+}
+
+impl S3Source {
+    async fn client(&self, s3: &S3Config, network: &NetworkConfig) -> Result<aws_sdk_s3::Client> {
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(s3) {
+            return Ok(client.clone());
+        }
+        let mut loader = aws_config::from_env();
+        if let Some(profile) = &s3.profile {
+            loader = loader.profile_name(profile);
+        }
+        if let Some(region) = &s3.region {
+            loader = loader.region(aws_config::Region::new(region.clone()));
+        }
+        if let Some(http_client) = network.s3_http_client()? {
+            loader = loader.http_client(http_client);
+        }
+        let mut builder = aws_sdk_s3::config::Builder::from(&loader.load().await);
+        if let Some(endpoint_url) = &s3.endpoint_url {
+            builder = builder.endpoint_url(endpoint_url.as_str());
+        }
+        if let Some(force_path_style) = s3.force_path_style {
+            builder = builder.force_path_style(force_path_style);
+        }
+        let client = aws_sdk_s3::Client::from_conf(builder.build());
+        clients.insert(s3.clone(), client.clone());
+        Ok(client)
+    }
+}
+
+/// The S3 settings of a plugin: its own, or else those of the longest `s3` prefix of its
+/// URL in the config.
+fn s3_config<'a>(config: &'a Config, plugin_cfg: &'a PluginConfig) -> Option<&'a S3Config> {
+    if let Some(s3) = &plugin_cfg.s3 {
+        return Some(s3);
+    }
+    config
+        .s3
+        .iter()
+        .flatten()
+        .filter(|(prefix, _)| plugin_cfg.url.as_str().starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.as_str().len())
+        .map(|(_, s3)| s3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PluginName;
+    use url::Url;
+
+    #[test]
+    fn test_s3_config() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+s3:
+  "s3://lab-":
+    region: eu-west-1
+  "s3://lab-minio/":
+    endpoint_url: http://localhost:9000
+    force_path_style: true
+plugins:
+  time:
+    url: s3://lab-minio/plugins/time.wasm
+  fetch:
+    url: s3://lab-eu/fetch.wasm
+  own:
+    url: s3://lab-minio/own.wasm
+    s3:
+      region: us-west-2
+  other:
+    url: s3://other/plugin.wasm
+"#,
+        )
+        .unwrap();
+        let s3 = |name: &str| {
+            let plugin_cfg = &config.plugins[&PluginName::try_from(name).unwrap()];
+            s3_config(&config, plugin_cfg).cloned()
+        };
+
+        let minio = s3("time").unwrap();
+        assert_eq!(
+            minio.endpoint_url.unwrap().as_str(),
+            "http://localhost:9000/"
+        );
+        assert_eq!(minio.force_path_style, Some(true));
+        assert_eq!(minio.region, None);
+        assert_eq!(s3("fetch").unwrap().region.as_deref(), Some("eu-west-1"));
+        assert_eq!(s3("own").unwrap().region.as_deref(), Some("us-west-2"));
+        assert_eq!(s3("other"), None);
+    }
+
+    #[tokio::test]
+    async fn test_s3_client_path_style_endpoint() {
+        use axum::{extract::State, http::Uri};
+        use std::sync::{Arc, Mutex as StdMutex};
+
+        // An S3 stand-in that serves every object and records what was asked for
+        let requested = Arc::new(StdMutex::new(Vec::new()));
+        let app = axum::Router::new()
+            .fallback(
+                |State(requested): State<Arc<StdMutex<Vec<String>>>>, uri: Uri| async move {
+                    requested.lock().unwrap().push(uri.path().to_string());
+                    b"wasm".to_vec()
+                },
+            )
+            .with_state(Arc::clone(&requested));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // SAFETY: no other test reads these variables
+        unsafe {
+            std::env::set_var("AWS_ACCESS_KEY_ID", "minioadmin");
+            std::env::set_var("AWS_SECRET_ACCESS_KEY", "minioadmin");
+        }
+        let s3 = S3Config {
+            endpoint_url: Some(Url::parse(&format!("http://{addr}")).unwrap()),
+            region: Some("us-east-1".to_string()),
+            force_path_style: Some(true),
+            ..Default::default()
+        };
+        let source = S3Source::default();
+        let client = source.client(&s3, &NetworkConfig::default()).await.unwrap();
+        let object = client
+            .get_object()
+            .bucket("plugins")
+            .key("time.wasm")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(object.body.collect().await.unwrap().to_vec(), b"wasm");
+        assert_eq!(*requested.lock().unwrap(), vec!["/plugins/time.wasm"]);
+    }
+}
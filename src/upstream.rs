@@ -0,0 +1,607 @@
+use crate::{
+    config::{AuthConfig, NetworkConfig, PluginConfig, PluginName},
+    https_auth::{Authenticator, client_identity},
+    plugin::{CallLimit, Plugin},
+    service::session_peer,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use reqwest::{Identity, header::HeaderMap};
+use rmcp::{
+    ClientHandler, ErrorData as McpError, RoleClient, ServiceError, ServiceExt,
+    model::*,
+    service::{
+        NotificationContext, PeerRequestOptions, RequestContext, RoleServer, RunningService,
+    },
+    transport::{
+        StreamableHttpClientTransport, TokioChildProcess,
+        streamable_http_client::{
+            SseError, StreamableHttpClient, StreamableHttpClientTransportConfig,
+            StreamableHttpError, StreamableHttpPostResponse,
+        },
+    },
+};
+use sse_stream::Sse;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+use uuid::Uuid;
+
+/// Whether a plugin URL points at an upstream MCP server rather than a wasm module.
+pub fn is_upstream_url(url: &Url) -> bool {
+    matches!(url.scheme(), "stdio" | "mcp+http" | "mcp+https")
+}
+
+/// Receives notifications from an upstream server and passes list changes on to the
+/// client of the session that owns the connection.
+#[derive(Clone, Debug)]
+struct UpstreamClient {
+    plugin_name: PluginName,
+    session_id: Uuid,
+}
+
+impl ClientHandler for UpstreamClient {
+    async fn on_prompt_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        if let Some(peer) = session_peer(self.session_id) {
+            tracing::info!("Notifying prompt list changed from {}", self.plugin_name);
+            if let Err(e) = peer.notify_prompt_list_changed().await {
+                tracing::error!("Failed to notify prompt list changed: {e}");
+            }
+        }
+    }
+
+    async fn on_resource_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        if let Some(peer) = session_peer(self.session_id) {
+            tracing::info!("Notifying resource list changed from {}", self.plugin_name);
+            if let Err(e) = peer.notify_resource_list_changed().await {
+                tracing::error!("Failed to notify resource list changed: {e}");
+            }
+        }
+    }
+
+    async fn on_tool_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        if let Some(peer) = session_peer(self.session_id) {
+            tracing::info!("Notifying tool list changed from {}", self.plugin_name);
+            if let Err(e) = peer.notify_tool_list_changed().await {
+                tracing::error!("Failed to notify tool list changed: {e}");
+            }
+        }
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        ClientInfo {
+            client_info: Implementation {
+                name: "hyper-mcp".to_string(),
+                title: Some("Hyper MCP".to_string()),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// The HTTP client of an upstream server, sending with every request the same
+/// credentials `auths` would add to a download from its URL.
+///
+/// Credentials are looked up for each request, so OAuth2 tokens are refreshed and
+/// secrets read again as they change. The underlying client, and its connections, are
+/// only replaced when they do. Both go through the `network` settings, as do OAuth2
+/// token requests.
+#[derive(Clone)]
+struct AuthenticatedClient {
+    url: Url,
+    auths: Option<HashMap<Url, AuthConfig>>,
+    network: NetworkConfig,
+    identity: Option<Identity>,
+    // Client without credentials, to look them up with
+    auth_client: reqwest::Client,
+    // Last credentials sent and the client sending them as default headers
+    client: Arc<Mutex<(HeaderMap, reqwest::Client)>>,
+}
+
+impl AuthenticatedClient {
+    async fn new(
+        url: Url,
+        auths: Option<HashMap<Url, AuthConfig>>,
+        network: NetworkConfig,
+    ) -> Result<Self> {
+        let identity = client_identity(&auths, &url).await?;
+        let auth_client = network.reqwest_builder()?.build()?;
+        let client = Self::build(&network, HeaderMap::new(), identity.clone())?;
+        Ok(Self {
+            url,
+            auths,
+            network,
+            identity,
+            auth_client,
+            client: Arc::new(Mutex::new((HeaderMap::new(), client))),
+        })
+    }
+
+    fn build(
+        network: &NetworkConfig,
+        headers: HeaderMap,
+        identity: Option<Identity>,
+    ) -> Result<reqwest::Client> {
+        let mut builder = network.reqwest_builder()?.default_headers(headers);
+        if let Some(identity) = identity {
+            builder = builder.use_rustls_tls().identity(identity);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// The client to send the next request with, carrying the current credentials.
+    async fn client(&self) -> Result<reqwest::Client, StreamableHttpError<reqwest::Error>> {
+        let credentials = self
+            .auth_client
+            .get(self.url.clone())
+            .add_auth(&self.auths, &self.url)
+            .await
+            .build()
+            .map_err(StreamableHttpError::Client)?;
+        let mut client = self.client.lock().unwrap_or_else(PoisonError::into_inner);
+        if client.0 != *credentials.headers() {
+            let headers = credentials.headers().clone();
+            let rebuilt = Self::build(&self.network, headers.clone(), self.identity.clone())
+                // Only the CA certificate files can fail, when they changed since
+                .map_err(std::io::Error::other)?;
+            *client = (headers, rebuilt);
+        }
+        Ok(client.1.clone())
+    }
+}
+
+impl StreamableHttpClient for AuthenticatedClient {
+    type Error = reqwest::Error;
+
+    async fn post_message(
+        &self,
+        uri: Arc<str>,
+        message: ClientJsonRpcMessage,
+        session_id: Option<Arc<str>>,
+        auth_header: Option<String>,
+    ) -> Result<StreamableHttpPostResponse, StreamableHttpError<Self::Error>> {
+        self.client()
+            .await?
+            .post_message(uri, message, session_id, auth_header)
+            .await
+    }
+
+    async fn delete_session(
+        &self,
+        uri: Arc<str>,
+        session_id: Arc<str>,
+        auth_header: Option<String>,
+    ) -> Result<(), StreamableHttpError<Self::Error>> {
+        self.client()
+            .await?
+            .delete_session(uri, session_id, auth_header)
+            .await
+    }
+
+    async fn get_stream(
+        &self,
+        uri: Arc<str>,
+        session_id: Arc<str>,
+        last_event_id: Option<String>,
+        auth_header: Option<String>,
+    ) -> Result<BoxStream<'static, Result<Sse, SseError>>, StreamableHttpError<Self::Error>> {
+        self.client()
+            .await?
+            .get_stream(uri, session_id, last_event_id, auth_header)
+            .await
+    }
+}
+
+/// A plugin backed by another MCP server, reached either by running it as a child
+/// process over stdio (`stdio:<command>`) or over streamable HTTP
+/// (`mcp+http://` / `mcp+https://`).
+///
+/// Each session gets its own connection, just as it gets its own wasm instances.
+pub struct UpstreamPlugin {
+    name: PluginName,
+    client: RunningService<RoleClient, UpstreamClient>,
+    call_timeout: Option<Duration>,
+}
+
+impl Debug for UpstreamPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpstreamPlugin")
+            .field("name", &self.name)
+            .field("call_timeout", &self.call_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UpstreamPlugin {
+    pub async fn connect(
+        name: PluginName,
+        plugin_cfg: &PluginConfig,
+        auths: &Option<HashMap<Url, AuthConfig>>,
+        network: &NetworkConfig,
+        session_id: Uuid,
+    ) -> Result<Self> {
+        let handler = UpstreamClient {
+            plugin_name: name.clone(),
+            session_id,
+        };
+        let runtime_cfg = plugin_cfg.runtime_config.as_ref();
+        let client = match plugin_cfg.url.scheme() {
+            "stdio" => {
+                let mut command = Command::new(plugin_cfg.url.path());
+                command.args(plugin_cfg.args.iter().flatten());
+                for (key, value) in runtime_cfg
+                    .and_then(|cfg| cfg.env_vars.as_ref())
+                    .into_iter()
+                    .flatten()
+                {
+                    let value = value.resolve().await.with_context(|| {
+                        format!("Failed to read env_vars {key} of plugin {name}")
+                    })?;
+                    command.env(key, &*value);
+                }
+                handler.serve(TokioChildProcess::new(command)?).await?
+            }
+            "mcp+http" | "mcp+https" => {
+                let uri = plugin_cfg.url.as_str().trim_start_matches("mcp+");
+                let transport = StreamableHttpClientTransport::with_client(
+                    AuthenticatedClient::new(Url::parse(uri)?, auths.clone(), network.clone())
+                        .await?,
+                    StreamableHttpClientTransportConfig::with_uri(uri),
+                );
+                handler.serve(transport).await?
+            }
+            unsupported => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported upstream URL scheme: {unsupported}"
+                ));
+            }
+        };
+
+        Ok(Self {
+            name,
+            client,
+            call_timeout: runtime_cfg.and_then(|cfg| cfg.call_timeout),
+        })
+    }
+
+    fn capabilities(&self) -> Option<&ServerCapabilities> {
+        self.client.peer_info().map(|info| &info.capabilities)
+    }
+
+    async fn send(
+        &self,
+        request: ClientRequest,
+        ct: CancellationToken,
+    ) -> Result<ServerResult, McpError> {
+        let plugin_name = &self.name;
+        let options = PeerRequestOptions {
+            timeout: self.call_timeout,
+            meta: None,
+        };
+        let handle = self
+            .client
+            .send_cancellable_request(request, options)
+            .await
+            .map_err(|e| self.map_error(e))?;
+        let peer = handle.peer.clone();
+        let request_id = handle.id.clone();
+
+        tokio::select! {
+            result = handle.await_response() => result.map_err(|e| self.map_error(e)),
+
+            _ = ct.cancelled() => {
+                if let Err(e) = peer
+                    .notify_cancelled(CancelledNotificationParam {
+                        request_id,
+                        reason: Some("cancelled by client".to_string()),
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to cancel request to plugin {plugin_name}: {e}");
+                }
+                Err(McpError::internal_error(
+                    format!("Plugin {plugin_name} was cancelled"),
+                    None,
+                ))
+            }
+        }
+    }
+
+    fn map_error(&self, e: ServiceError) -> McpError {
+        match e {
+            ServiceError::McpError(e) => e,
+            ServiceError::Timeout { .. } => CallLimit::Timeout.into_error(self.name.as_str()),
+            e => McpError::internal_error(
+                format!(
+                    "Failed to call upstream server of plugin {}: {e}",
+                    self.name
+                ),
+                None,
+            ),
+        }
+    }
+
+    fn unexpected_response(&self) -> McpError {
+        McpError::internal_error(
+            format!(
+                "Unexpected response from upstream server of plugin {}",
+                self.name
+            ),
+            None,
+        )
+    }
+}
+
+#[async_trait]
+impl Plugin for UpstreamPlugin {
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        match self
+            .send(
+                ClientRequest::CallToolRequest(Request::new(request)),
+                context.ct,
+            )
+            .await?
+        {
+            ServerResult::CallToolResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    async fn complete(
+        &self,
+        request: CompleteRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CompleteResult, McpError> {
+        if self.capabilities().is_none_or(|c| c.completions.is_none()) {
+            return Ok(CompleteResult::default());
+        }
+        match self
+            .send(
+                ClientRequest::CompleteRequest(Request::new(request)),
+                context.ct,
+            )
+            .await?
+        {
+            ServerResult::CompleteResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        match self
+            .send(
+                ClientRequest::GetPromptRequest(Request::new(request)),
+                context.ct,
+            )
+            .await?
+        {
+            ServerResult::GetPromptResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    async fn list_prompts(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        if self.capabilities().is_none_or(|c| c.prompts.is_none()) {
+            return Ok(ListPromptsResult::default());
+        }
+        let request = ListPromptsRequest {
+            params: request,
+            ..Default::default()
+        };
+        match self
+            .send(ClientRequest::ListPromptsRequest(request), context.ct)
+            .await?
+        {
+            ServerResult::ListPromptsResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    async fn list_resources(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        if self.capabilities().is_none_or(|c| c.resources.is_none()) {
+            return Ok(ListResourcesResult::default());
+        }
+        let request = ListResourcesRequest {
+            params: request,
+            ..Default::default()
+        };
+        match self
+            .send(ClientRequest::ListResourcesRequest(request), context.ct)
+            .await?
+        {
+            ServerResult::ListResourcesResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    async fn list_resource_templates(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        if self.capabilities().is_none_or(|c| c.resources.is_none()) {
+            return Ok(ListResourceTemplatesResult::default());
+        }
+        let request = ListResourceTemplatesRequest {
+            params: request,
+            ..Default::default()
+        };
+        match self
+            .send(
+                ClientRequest::ListResourceTemplatesRequest(request),
+                context.ct,
+            )
+            .await?
+        {
+            ServerResult::ListResourceTemplatesResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        if self.capabilities().is_none_or(|c| c.tools.is_none()) {
+            return Ok(ListToolsResult::default());
+        }
+        let request = ListToolsRequest {
+            params: request,
+            ..Default::default()
+        };
+        match self
+            .send(ClientRequest::ListToolsRequest(request), context.ct)
+            .await?
+        {
+            ServerResult::ListToolsResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+
+    fn name(&self) -> &PluginName {
+        &self.name
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        match self
+            .send(
+                ClientRequest::ReadResourceRequest(Request::new(request)),
+                context.ct,
+            )
+            .await?
+        {
+            ServerResult::ReadResourceResult(result) => Ok(result),
+            _ => Err(self.unexpected_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::HeaderMap as AxumHeaderMap};
+
+    #[tokio::test]
+    async fn test_authenticated_client_reads_credentials_per_request() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let app = axum::Router::new()
+            .fallback(
+                |State(seen): State<Arc<Mutex<Vec<String>>>>, headers: AxumHeaderMap| async move {
+                    let authorization = headers["authorization"].to_str().unwrap().to_string();
+                    seen.lock().unwrap().push(authorization);
+                },
+            )
+            .with_state(Arc::clone(&seen));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/mcp", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let token_path = temp_dir.path().join("token");
+        std::fs::write(&token_path, "first\n").unwrap();
+        let auths: HashMap<Url, AuthConfig> = serde_yaml::from_str(&format!(
+            "\"{url}\":\n  type: file\n  path: {}\n",
+            token_path.display()
+        ))
+        .unwrap();
+        let client = AuthenticatedClient::new(url.clone(), Some(auths), NetworkConfig::default())
+            .await
+            .unwrap();
+
+        client
+            .client()
+            .await
+            .unwrap()
+            .get(url.clone())
+            .send()
+            .await
+            .unwrap();
+        // A rotated secret is picked up by the next request
+        std::fs::write(&token_path, "second\n").unwrap();
+        client
+            .client()
+            .await
+            .unwrap()
+            .get(url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(*seen.lock().unwrap(), ["Bearer first", "Bearer second"]);
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_client_uses_network_config() {
+        // Stands in for the only proxy that can reach the token endpoint and the server
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let app = axum::Router::new()
+            .route(
+                "/token",
+                axum::routing::post(|| async {
+                    axum::Json(serde_json::json!({ "access_token": "proxied" }))
+                }),
+            )
+            .fallback(
+                |State(seen): State<Arc<Mutex<Vec<String>>>>, headers: AxumHeaderMap| async move {
+                    let authorization = headers["authorization"].to_str().unwrap().to_string();
+                    seen.lock().unwrap().push(authorization);
+                },
+            )
+            .with_state(Arc::clone(&seen));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let url = Url::parse("http://upstream.invalid/mcp").unwrap();
+        let auths: HashMap<Url, AuthConfig> = serde_yaml::from_str(
+            "\"http://upstream.invalid/\":\n  type: oauth2\n  token_url: http://oauth.invalid/token\n  client_id: upstream_client\n  client_secret: secret\n",
+        )
+        .unwrap();
+        let network = NetworkConfig {
+            http_proxy: Some(proxy),
+            ..Default::default()
+        };
+        let client = AuthenticatedClient::new(url.clone(), Some(auths), network)
+            .await
+            .unwrap();
+
+        client
+            .client()
+            .await
+            .unwrap()
+            .get(url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(*seen.lock().unwrap(), ["Bearer proxied"]);
+    }
+}